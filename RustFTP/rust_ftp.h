@@ -11,6 +11,7 @@
 #define RUST_FTP_H
 
 #include <stdint.h>
+#include <stdbool.h>
 
 #ifdef __cplusplus
 extern "C" {
@@ -72,6 +73,37 @@ int32_t rust_ftp_start(
  */
 int32_t rust_ftp_stop(const char *session_id);
 
+/**
+ * Pause an FTP monitoring session without stopping it
+ *
+ * No new files are started while paused. Uploads in progress finish first,
+ * unless abort_transfers is true: then they are cut off, and after resuming
+ * they continue from where they stopped if resume_uploads is on. Without it
+ * they start over (an atomic upload's partial temp file is removed). The
+ * status stage becomes "Paused" once the session is idle.
+ *
+ * @param session_id Unique identifier for the session to pause
+ * @param abort_transfers Cut off uploads in progress instead of waiting for them
+ * @return 0 on success, negative value on error:
+ *         -1: session_id is null
+ *         -2: session_id encoding error
+ *         -3: session not found
+ */
+int32_t rust_ftp_pause(const char *session_id, bool abort_transfers);
+
+/**
+ * Resume a session paused with rust_ftp_pause()
+ *
+ * Continues with the same database, connection state and session statistics.
+ *
+ * @param session_id Unique identifier for the session to resume
+ * @return 0 on success, negative value on error:
+ *         -1: session_id is null
+ *         -2: session_id encoding error
+ *         -3: session not found
+ */
+int32_t rust_ftp_resume(const char *session_id);
+
 /**
  * Get current status for a session
  *
@@ -103,6 +135,81 @@ void rust_ftp_free_string(char *s);
  */
 int32_t rust_ftp_shutdown(void);
 
+/**
+ * Query the upload history (audit trail) for a configuration
+ *
+ * Every file a worker finishes is recorded with its local and remote path, size,
+ * duration, speed, attempt count, outcome and error text. The history is read
+ * from the configuration's own database, so this works whether or not a
+ * session for it (or for another configuration) is running.
+ *
+ * @param config_id Configuration whose history to read
+ * @param filter_json Optional JSON filter (can be NULL), e.g.
+ *        {"since": 1760000000, "until": 1760600000, "outcome": "failed",
+ *         "filename_pattern": "*.jpg", "limit": 100}
+ *        outcome is one of: uploaded, failed, skipped, duplicate_skipped, duplicate_copied
+ * @return JSON array of records (newest first), or NULL on error
+ *         Caller must free the returned string with rust_ftp_free_string()
+ */
+char *rust_ftp_query_upload_history(const char *config_id, const char *filter_json);
+
+/**
+ * List dead-lettered files for a configuration
+ *
+ * A file that fails to upload is retried with a growing delay. After
+ * retry_max_attempts failures it is dead-lettered: it is no longer retried until
+ * it changes or is requeued with rust_ftp_retry_dead_letters(). An "error"
+ * notification is sent when that happens. Like the upload history, this reads
+ * the configuration's own database and works without a running session.
+ *
+ * @param config_id Configuration whose dead letters to list
+ * @return JSON array of entries (local_path, file_size, mod_time, attempts,
+ *         last_error, last_failed_at, next_attempt_at, dead_lettered),
+ *         most recent failure first, or NULL on error
+ *         Caller must free the returned string with rust_ftp_free_string()
+ */
+char *rust_ftp_get_dead_letters(const char *config_id);
+
+/**
+ * Retry dead-lettered files on the next scan
+ *
+ * @param config_id Configuration whose dead letters to requeue
+ * @param local_path Single file to requeue, or NULL for all of them
+ * @return Number of files requeued, or negative on error:
+ *         -1: config_id is NULL
+ *         -2: invalid UTF-8 in an argument
+ *         -3: database error
+ */
+int32_t rust_ftp_retry_dead_letters(const char *config_id, const char *local_path);
+
+/**
+ * Change the upload bandwidth cap of a running session
+ *
+ * The cap is shared by all parallel workers of the session and replaces
+ * max_upload_rate from its config until the session is restarted. It also
+ * applies when called right after rust_ftp_start(), before the session has
+ * read its config, and takes precedence over the max_upload_rate of schedule
+ * windows as they open and close.
+ *
+ * @param session_id Session identifier passed to rust_ftp_start()
+ * @param bytes_per_sec Maximum upload rate, or 0 for unlimited
+ * @return 0 on success, or negative on error:
+ *         -1: session_id is NULL
+ *         -2: invalid UTF-8 in session_id
+ *         -3: session not found
+ */
+int32_t rust_ftp_set_bandwidth_limit(const char *session_id, uint64_t bytes_per_sec);
+
+/**
+ * Change the upload bandwidth cap shared by all sessions
+ *
+ * Every session is held to both this cap and its own limit.
+ *
+ * @param bytes_per_sec Maximum combined upload rate, or 0 for unlimited
+ * @return 0
+ */
+int32_t rust_ftp_set_global_bandwidth_limit(uint64_t bytes_per_sec);
+
 #ifdef __cplusplus
 }
 #endif
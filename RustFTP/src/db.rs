@@ -1,14 +1,16 @@
 // SQLite database for file hash tracking
 // Replaces append-only text files with indexed database for better performance
 
-use rusqlite::{Connection, params, Result as SqlResult};
+use rusqlite::{Connection, OptionalExtension, Transaction, params, Result as SqlResult};
 use std::path::{Path, PathBuf};
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-// Global database connection pool
-static DB_CONNECTION: OnceLock<Mutex<Connection>> = OnceLock::new();
+// Connections of the configs running in this process, by config_id
+// Sessions run as threads of one process, so each needs its own entry
+static DB_CONNECTIONS: OnceLock<Mutex<HashMap<String, Arc<Mutex<Connection>>>>> = OnceLock::new();
 
 /// File metadata stored in database
 #[derive(Debug, Clone)]
@@ -19,30 +21,150 @@ pub struct FileHash {
     pub file_size: u64,
     pub mod_time: i64,
     pub hash: u64,
+    pub content_hash: Option<u64>,
     pub first_seen: i64,
     pub last_seen: i64,
 }
 
-/// Initialize the database and create tables if needed
-/// Should be called once at application startup
-pub fn init_database(db_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🗄️  Initializing SQLite database at: {}", db_path.display());
+/// One processed file in the upload history
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadRecord {
+    pub local_path: String,
+    pub remote_path: String,
+    pub file_size: u64,
+    pub duration_secs: f64,
+    pub speed_mbps: f64,
+    pub attempts: u32,
+    /// "uploaded", "failed", "skipped", "duplicate_skipped" or "duplicate_copied"
+    pub outcome: String,
+    pub error: Option<String>,
+    pub started_at: i64,
+    pub finished_at: i64,
+}
 
-    // Ensure parent directory exists
-    if let Some(parent) = db_path.parent() {
-        std::fs::create_dir_all(parent)?;
+/// A file whose upload failed, waiting for its next attempt or dead-lettered
+#[derive(Debug, Clone, Serialize)]
+pub struct RetryEntry {
+    pub local_path: String,
+    pub file_size: u64,
+    pub mod_time: i64,
+    pub attempts: u32,
+    pub last_error: String,
+    pub last_failed_at: i64,
+    pub next_attempt_at: i64,
+    /// Gave up after the maximum number of attempts; only retried again when
+    /// the file changes or the user requeues it
+    pub dead_lettered: bool,
+}
+
+/// Concurrent session limit learned from a server's 421/530 rejections
+#[derive(Debug, Clone, Copy)]
+pub struct ServerLimit {
+    /// Most sessions the server accepted at once before rejecting the next one
+    pub max_sessions: u32,
+    /// Most sessions that were ever open at once without a rejection
+    pub highest_ok: u32,
+    pub learned_at: i64,
+}
+
+/// Longest wait between two attempts, however often a file has failed
+const MAX_RETRY_DELAY_SECS: u64 = 3600;
+
+/// Filters for `query_upload_history`; every field is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryFilter {
+    /// Unix timestamp (seconds), inclusive
+    pub since: Option<i64>,
+    /// Unix timestamp (seconds), inclusive
+    pub until: Option<i64>,
+    pub outcome: Option<String>,
+    /// Glob matched against the local or remote path, e.g. "*IMG_0042*"
+    pub filename_pattern: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// A schema change, applied once when upgrading from the previous version
+struct Migration {
+    description: &'static str,
+    apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// Schema history, oldest first. The schema version stored in the database
+/// (`PRAGMA user_version`) is the number of steps applied.
+///
+/// Never edit or reorder a released step - append a new one instead.
+/// Databases created before versioning existed report version 0 but may
+/// already contain some of these tables, so every step tolerates that.
+const MIGRATIONS: &[Migration] = &[
+    Migration { description: "file hashes", apply: migrate_file_hashes },
+    Migration { description: "upload resume points", apply: migrate_upload_resume },
+    Migration { description: "sent folder prune log", apply: migrate_sent_prunes },
+    Migration { description: "content hashes", apply: migrate_content_hash },
+    Migration { description: "dedupe index and log", apply: migrate_dedupe },
+    Migration { description: "upload history", apply: migrate_upload_history },
+    Migration { description: "retry queue", apply: migrate_upload_retries },
+    Migration { description: "server connection limits", apply: migrate_server_limits },
+    Migration { description: "sent file arrival times", apply: migrate_sent_files },
+];
+
+/// Schema version this build reads and writes
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// The database was written by a newer FTPUploader with a schema this build does not know
+#[derive(Debug)]
+pub struct SchemaTooNew {
+    pub path: PathBuf,
+    pub found: u32,
+    pub supported: u32,
+}
+
+impl std::fmt::Display for SchemaTooNew {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Database {} has schema version {}, but this version of FTPUploader only supports up to {}. Please update FTPUploader.",
+            self.path.display(), self.found, self.supported)
     }
+}
 
-    // Open or create database
-    let conn = Connection::open(db_path)?;
+impl std::error::Error for SchemaTooNew {}
 
-    // Enable WAL mode for better concurrent access
-    // PRAGMA statements return results, so we need to consume them
-    conn.pragma_update(None, "journal_mode", "WAL")?;
-    conn.pragma_update(None, "synchronous", "NORMAL")?;
+/// Bring the database up to `SCHEMA_VERSION`
+///
+/// Each step runs in its own transaction together with the version bump, so
+/// an interrupted upgrade leaves the database at the last completed version.
+/// A database written by a newer FTPUploader is refused rather than risk
+/// writing data that version does not expect.
+fn migrate(conn: &mut Connection, db_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    apply_migrations(conn, db_path, MIGRATIONS)
+}
 
-    // Create table if it doesn't exist
-    conn.execute(
+fn apply_migrations(conn: &mut Connection, db_path: &Path, migrations: &[Migration]) -> Result<(), Box<dyn std::error::Error>> {
+    let current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let supported = migrations.len() as u32;
+
+    if current > supported {
+        return Err(Box::new(SchemaTooNew {
+            path: db_path.to_path_buf(),
+            found: current,
+            supported,
+        }));
+    }
+
+    for (index, migration) in migrations.iter().enumerate().skip(current as usize) {
+        let version = index as u32 + 1;
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx)
+            .and_then(|_| tx.pragma_update(None, "user_version", version))
+            .map_err(|e| format!("Database migration to version {} ({}) failed: {}", version, migration.description, e))?;
+        tx.commit()?;
+        println!("🗄️  DB: Migrated schema to version {} ({})", version, migration.description);
+    }
+
+    Ok(())
+}
+
+fn migrate_file_hashes(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
         "CREATE TABLE IF NOT EXISTS file_hashes (
             config_id TEXT NOT NULL,
             remote_dir TEXT NOT NULL,
@@ -53,46 +175,228 @@ pub fn init_database(db_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
             first_seen INTEGER NOT NULL,
             last_seen INTEGER NOT NULL,
             PRIMARY KEY (config_id, remote_dir, filename)
-        )",
-        [],
-    )?;
+        );
+        CREATE INDEX IF NOT EXISTS idx_hash_lookup
+         ON file_hashes(config_id, remote_dir, filename);
+        CREATE INDEX IF NOT EXISTS idx_last_seen
+         ON file_hashes(config_id, last_seen);
+        CREATE INDEX IF NOT EXISTS idx_config
+         ON file_hashes(config_id);"
+    )
+}
 
-    // Create indices for fast lookups
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_hash_lookup
-         ON file_hashes(config_id, remote_dir, filename)",
-        [],
-    )?;
+/// Partially uploaded files, so an interrupted transfer can resume after a restart
+fn migrate_upload_resume(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS upload_resume (
+            config_id TEXT NOT NULL,
+            local_path TEXT NOT NULL,
+            remote_path TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            mod_time INTEGER NOT NULL,
+            offset INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (config_id, local_path)
+        );"
+    )
+}
 
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_last_seen
-         ON file_hashes(config_id, last_seen)",
-        [],
-    )?;
+/// Audit trail of files removed from FTPU-Sent / the archive by retention rules
+fn migrate_sent_prunes(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sent_prunes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            config_id TEXT NOT NULL,
+            path TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            pruned_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sent_prunes_config
+         ON sent_prunes(config_id, pruned_at);"
+    )
+}
 
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_config
-         ON file_hashes(config_id)",
-        [],
-    )?;
+/// xxh3 of the file bytes, for content-based change detection (NULL when only metadata is tracked)
+fn migrate_content_hash(tx: &Transaction) -> rusqlite::Result<()> {
+    // Unversioned databases may already have the column
+    if tx.prepare("SELECT content_hash FROM file_hashes LIMIT 0").is_ok() {
+        return Ok(());
+    }
+    tx.execute("ALTER TABLE file_hashes ADD COLUMN content_hash INTEGER", [])?;
+    Ok(())
+}
 
-    println!("✅ Database initialized successfully");
+/// Content hash index of uploaded files, and files that were not uploaded
+/// because identical content was already on the server
+fn migrate_dedupe(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS uploaded_content (
+            config_id TEXT NOT NULL,
+            content_hash INTEGER NOT NULL,
+            file_size INTEGER NOT NULL,
+            remote_path TEXT NOT NULL,
+            local_path TEXT NOT NULL,
+            uploaded_at INTEGER NOT NULL,
+            PRIMARY KEY (config_id, content_hash, file_size)
+        );
+        CREATE TABLE IF NOT EXISTS dedupe_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            config_id TEXT NOT NULL,
+            local_path TEXT NOT NULL,
+            remote_path TEXT NOT NULL,
+            duplicate_of TEXT NOT NULL,
+            content_hash INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );"
+    )
+}
 
-    // Store connection in global static
-    DB_CONNECTION.set(Mutex::new(conn))
-        .map_err(|_| "Failed to set database connection")?;
+/// Every processed file: what was uploaded, where, how fast, or why it failed
+fn migrate_upload_history(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS upload_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            config_id TEXT NOT NULL,
+            local_path TEXT NOT NULL,
+            remote_path TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            duration_secs REAL NOT NULL,
+            speed_mbps REAL NOT NULL,
+            attempts INTEGER NOT NULL,
+            outcome TEXT NOT NULL,
+            error TEXT,
+            started_at INTEGER NOT NULL,
+            finished_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_upload_history_config
+         ON upload_history(config_id, finished_at);"
+    )
+}
+
+/// Failed uploads with their attempt count and backoff
+fn migrate_upload_retries(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS upload_retries (
+            config_id TEXT NOT NULL,
+            local_path TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            mod_time INTEGER NOT NULL,
+            attempts INTEGER NOT NULL,
+            last_error TEXT NOT NULL,
+            last_failed_at INTEGER NOT NULL,
+            next_attempt_at INTEGER NOT NULL,
+            dead_lettered INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (config_id, local_path)
+        );"
+    )
+}
+
+/// Learned concurrent session limit per server (`user@host:port`)
+fn migrate_server_limits(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS server_limits (
+            server_key TEXT PRIMARY KEY,
+            max_sessions INTEGER NOT NULL,
+            highest_ok INTEGER NOT NULL,
+            learned_at INTEGER NOT NULL
+        );"
+    )
+}
+
+/// Files moved into FTPU-Sent / the archive, and when - the only files retention may prune
+fn migrate_sent_files(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sent_files (
+            config_id TEXT NOT NULL,
+            path TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            archived_at INTEGER NOT NULL,
+            PRIMARY KEY (config_id, path)
+        );"
+    )
+}
+
+/// Initialize a config's database and upgrade its schema if needed
+/// Should be called once when its session starts; later calls for the config reopen it
+pub fn init_database(config_id: &str, db_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🗄️  Initializing SQLite database at: {}", db_path.display());
+
+    // Ensure parent directory exists
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Open or create database
+    let mut conn = Connection::open(db_path)?;
+
+    // Enable WAL mode for better concurrent access
+    // PRAGMA statements return results, so we need to consume them
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+    migrate(&mut conn, db_path)?;
+
+    println!("✅ Database initialized successfully (schema version {})", SCHEMA_VERSION);
+
+    // Every later call for this config goes to this file
+    DB_CONNECTIONS.get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .insert(config_id.to_string(), Arc::new(Mutex::new(conn)));
 
     Ok(())
 }
 
-/// Get reference to database connection
-fn get_connection() -> Result<&'static Mutex<Connection>, Box<dyn std::error::Error>> {
-    DB_CONNECTION.get()
-        .ok_or_else(|| "Database not initialized. Call init_database() first.".into())
+/// Folder holding the per-config databases
+/// FTP_DATA_DIR is set for sandboxed apps; otherwise Application Support, or /tmp as a last resort
+pub fn data_dir() -> PathBuf {
+    let data_dir_str = std::env::var("FTP_DATA_DIR").unwrap_or_else(|_| {
+        if let Ok(home) = std::env::var("HOME") {
+            format!("{}/Library/Application Support/FTPUploader", home)
+        } else {
+            "/tmp/FTPUploader".to_string()
+        }
+    });
+    PathBuf::from(data_dir_str)
+}
+
+/// Database of a configuration; keyed by config_id (not session_id) so it persists across restarts
+pub fn config_db_path(config_id: &str) -> PathBuf {
+    data_dir().join(format!("config_{}.db", config_id))
+}
+
+/// Connection to a configuration's own database: its running session's, or,
+/// for a query from the app while it is stopped, the file opened just for
+/// this call. Returns None if the config never ran.
+fn open_config_database(config_id: &str) -> Result<Option<Arc<Mutex<Connection>>>, Box<dyn std::error::Error>> {
+    let running = DB_CONNECTIONS.get().and_then(|connections| connections.lock().unwrap().get(config_id).cloned());
+    if running.is_some() {
+        return Ok(running);
+    }
+
+    let db_path = config_db_path(config_id);
+    if !db_path.exists() {
+        return Ok(None);
+    }
+
+    let mut conn = Connection::open(&db_path)?;
+    // A session in another process may be writing to the same file
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    migrate(&mut conn, &db_path)?;
+    Ok(Some(Arc::new(Mutex::new(conn))))
+}
+
+/// Get the database connection of a config
+fn get_connection(config_id: &str) -> Result<Arc<Mutex<Connection>>, Box<dyn std::error::Error>> {
+    open_config_database(config_id)?
+        .ok_or_else(|| format!("Database of config {} not initialized. Call init_database() first.", config_id).into())
 }
 
 /// Save or update a file hash in the database
 /// Uses UPSERT to handle both insert and update cases
+/// `content_hash` is the xxh3 of the file bytes when content-based change detection is on
 pub fn save_hash(
     config_id: &str,
     remote_dir: &str,
@@ -100,8 +404,9 @@ pub fn save_hash(
     file_size: u64,
     mod_time: DateTime<Utc>,
     hash: u64,
+    content_hash: Option<u64>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let now = Utc::now().timestamp();
@@ -109,15 +414,16 @@ pub fn save_hash(
 
     conn.execute(
         "INSERT INTO file_hashes
-         (config_id, remote_dir, filename, file_size, mod_time, hash, first_seen, last_seen)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         (config_id, remote_dir, filename, file_size, mod_time, hash, content_hash, first_seen, last_seen)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
          ON CONFLICT(config_id, remote_dir, filename)
          DO UPDATE SET
             file_size = excluded.file_size,
             mod_time = excluded.mod_time,
             hash = excluded.hash,
+            content_hash = excluded.content_hash,
             last_seen = excluded.last_seen",
-        params![config_id, remote_dir, filename, file_size as i64, mod_time_ts, hash as i64, now, now],
+        params![config_id, remote_dir, filename, file_size as i64, mod_time_ts, hash as i64, content_hash.map(|h| h as i64), now, now],
     )?;
 
     println!("💾 DB: Saved hash for {}/{} (hash: {})", remote_dir, filename, hash);
@@ -131,7 +437,7 @@ pub fn get_hash(
     remote_dir: &str,
     filename: &str,
 ) -> Result<Option<u64>, Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let mut stmt = conn.prepare(
@@ -153,7 +459,7 @@ pub fn get_hash(
 /// Returns HashMap with key = "remote_dir|filename", value = hash
 /// This is compatible with the existing code that uses HashMap<String, u64>
 pub fn load_hashes_for_config(config_id: &str) -> Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let mut stmt = conn.prepare(
@@ -178,6 +484,36 @@ pub fn load_hashes_for_config(config_id: &str) -> Result<HashMap<String, u64>, B
     Ok(hashes)
 }
 
+/// Load the content hashes recorded for a config
+/// Returns HashMap with key = "remote_dir|filename", value = (file_size, content_hash)
+/// Files recorded without a content hash are left out
+pub fn load_content_hashes_for_config(config_id: &str) -> Result<HashMap<String, (u64, u64)>, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let mut stmt = conn.prepare(
+        "SELECT remote_dir, filename, file_size, content_hash FROM file_hashes
+         WHERE config_id = ?1 AND content_hash IS NOT NULL"
+    )?;
+
+    let rows = stmt.query_map(params![config_id], |row| {
+        let remote_dir: String = row.get(0)?;
+        let filename: String = row.get(1)?;
+        let file_size: i64 = row.get(2)?;
+        let content_hash: i64 = row.get(3)?;
+        Ok((remote_dir, filename, file_size, content_hash))
+    })?;
+
+    let mut hashes = HashMap::new();
+    for row_result in rows {
+        let (remote_dir, filename, file_size, content_hash) = row_result?;
+        hashes.insert(format!("{}|{}", remote_dir, filename), (file_size as u64, content_hash as u64));
+    }
+
+    println!("📊 DB: Loaded {} content hashes for config {}", hashes.len(), config_id);
+    Ok(hashes)
+}
+
 /// Mark a file as "seen" during current scan
 /// Updates last_seen timestamp without changing other fields
 pub fn mark_file_seen(
@@ -185,7 +521,7 @@ pub fn mark_file_seen(
     remote_dir: &str,
     filename: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let now = Utc::now().timestamp();
@@ -206,7 +542,7 @@ pub fn cleanup_stale_files(
     config_id: &str,
     older_than_timestamp: i64,
 ) -> Result<usize, Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let deleted = conn.execute(
@@ -224,7 +560,7 @@ pub fn cleanup_stale_files(
 
 /// Get total count of tracked files for a config
 pub fn get_file_count(config_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let mut stmt = conn.prepare(
@@ -235,6 +571,499 @@ pub fn get_file_count(config_id: &str) -> Result<usize, Box<dyn std::error::Erro
     Ok(count as usize)
 }
 
+/// Resume point for an upload that did not complete
+#[derive(Debug, Clone)]
+pub struct ResumePoint {
+    pub remote_path: String,
+    pub file_size: u64,
+    pub mod_time: i64,
+    pub offset: u64,
+}
+
+/// Record how far an upload of `local_path` got
+/// `file_size` and `mod_time` identify the local file version being uploaded
+pub fn save_resume_point(
+    config_id: &str,
+    local_path: &str,
+    point: &ResumePoint,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO upload_resume
+         (config_id, local_path, remote_path, file_size, mod_time, offset, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(config_id, local_path)
+         DO UPDATE SET
+            remote_path = excluded.remote_path,
+            file_size = excluded.file_size,
+            mod_time = excluded.mod_time,
+            offset = excluded.offset,
+            updated_at = excluded.updated_at",
+        params![
+            config_id,
+            local_path,
+            point.remote_path,
+            point.file_size as i64,
+            point.mod_time,
+            point.offset as i64,
+            Utc::now().timestamp()
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Get the resume point recorded for `local_path`
+/// Returns None if no interrupted upload is known
+pub fn get_resume_point(
+    config_id: &str,
+    local_path: &str,
+) -> Result<Option<ResumePoint>, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let mut stmt = conn.prepare(
+        "SELECT remote_path, file_size, mod_time, offset FROM upload_resume
+         WHERE config_id = ?1 AND local_path = ?2"
+    )?;
+
+    let mut rows = stmt.query(params![config_id, local_path])?;
+
+    if let Some(row) = rows.next()? {
+        let file_size: i64 = row.get(1)?;
+        let offset: i64 = row.get(3)?;
+        Ok(Some(ResumePoint {
+            remote_path: row.get(0)?,
+            file_size: file_size as u64,
+            mod_time: row.get(2)?,
+            offset: offset as u64,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Forget the resume point once an upload has completed
+pub fn clear_resume_point(
+    config_id: &str,
+    local_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    conn.execute(
+        "DELETE FROM upload_resume WHERE config_id = ?1 AND local_path = ?2",
+        params![config_id, local_path],
+    )?;
+
+    Ok(())
+}
+
+/// Append a processed file to the upload history
+pub fn record_upload(config_id: &str, record: &UploadRecord) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO upload_history
+         (config_id, local_path, remote_path, file_size, duration_secs, speed_mbps, attempts, outcome, error, started_at, finished_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            config_id,
+            record.local_path,
+            record.remote_path,
+            record.file_size as i64,
+            record.duration_secs,
+            record.speed_mbps,
+            record.attempts,
+            record.outcome,
+            record.error,
+            record.started_at,
+            record.finished_at
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Upload history for a config, newest first
+/// Reads the config's own database, whether or not a session is running
+pub fn query_upload_history(
+    config_id: &str,
+    filter: &HistoryFilter,
+) -> Result<Vec<UploadRecord>, Box<dyn std::error::Error>> {
+    let Some(conn_mutex) = open_config_database(config_id)? else {
+        return Ok(Vec::new());
+    };
+    let conn = conn_mutex.lock().unwrap();
+
+    let mut stmt = conn.prepare(
+        "SELECT local_path, remote_path, file_size, duration_secs, speed_mbps, attempts, outcome, error, started_at, finished_at
+         FROM upload_history
+         WHERE config_id = ?1
+           AND (?2 IS NULL OR finished_at >= ?2)
+           AND (?3 IS NULL OR finished_at <= ?3)
+           AND (?4 IS NULL OR outcome = ?4)
+           AND (?5 IS NULL OR local_path GLOB ?5 OR remote_path GLOB ?5)
+         ORDER BY finished_at DESC, id DESC
+         LIMIT ?6"
+    )?;
+
+    // SQLite treats a negative LIMIT as "no limit"
+    let limit = filter.limit.map(i64::from).unwrap_or(-1);
+    let rows = stmt.query_map(
+        params![config_id, filter.since, filter.until, filter.outcome, filter.filename_pattern, limit],
+        |row| {
+            let file_size: i64 = row.get(2)?;
+            Ok(UploadRecord {
+                local_path: row.get(0)?,
+                remote_path: row.get(1)?,
+                file_size: file_size as u64,
+                duration_secs: row.get(3)?,
+                speed_mbps: row.get(4)?,
+                attempts: row.get(5)?,
+                outcome: row.get(6)?,
+                error: row.get(7)?,
+                started_at: row.get(8)?,
+                finished_at: row.get(9)?,
+            })
+        },
+    )?;
+
+    let mut records = Vec::new();
+    for row_result in rows {
+        records.push(row_result?);
+    }
+    Ok(records)
+}
+
+/// Remember where content with this hash and size was uploaded
+pub fn record_uploaded_content(
+    config_id: &str,
+    content_hash: u64,
+    file_size: u64,
+    remote_path: &str,
+    local_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO uploaded_content (config_id, content_hash, file_size, remote_path, local_path, uploaded_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(config_id, content_hash, file_size)
+         DO UPDATE SET
+            remote_path = excluded.remote_path,
+            local_path = excluded.local_path,
+            uploaded_at = excluded.uploaded_at",
+        params![config_id, content_hash as i64, file_size as i64, remote_path, local_path, Utc::now().timestamp()],
+    )?;
+
+    Ok(())
+}
+
+/// Remote path and upload time (unix seconds) of an earlier upload with identical content
+/// Returns None if this content was never uploaded for the config
+pub fn find_uploaded_content(
+    config_id: &str,
+    content_hash: u64,
+    file_size: u64,
+) -> Result<Option<(String, i64)>, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let mut stmt = conn.prepare(
+        "SELECT remote_path, uploaded_at FROM uploaded_content
+         WHERE config_id = ?1 AND content_hash = ?2 AND file_size = ?3"
+    )?;
+
+    let mut rows = stmt.query(params![config_id, content_hash as i64, file_size as i64])?;
+
+    if let Some(row) = rows.next()? {
+        Ok(Some((row.get(0)?, row.get(1)?)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Record that a file was not uploaded because its content was already on the server
+/// `action` is "skipped" or "server_copy"
+pub fn record_dedupe(
+    config_id: &str,
+    local_path: &str,
+    remote_path: &str,
+    duplicate_of: &str,
+    content_hash: u64,
+    action: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO dedupe_log (config_id, local_path, remote_path, duplicate_of, content_hash, action, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![config_id, local_path, remote_path, duplicate_of, content_hash as i64, action, Utc::now().timestamp()],
+    )?;
+
+    println!("♻️ DB: {} is a duplicate of {} ({})", local_path, duplicate_of, action);
+    Ok(())
+}
+
+fn retry_entry_from_row(row: &rusqlite::Row) -> SqlResult<RetryEntry> {
+    let file_size: i64 = row.get(1)?;
+    let dead_lettered: i64 = row.get(7)?;
+    Ok(RetryEntry {
+        local_path: row.get(0)?,
+        file_size: file_size as u64,
+        mod_time: row.get(2)?,
+        attempts: row.get(3)?,
+        last_error: row.get(4)?,
+        last_failed_at: row.get(5)?,
+        next_attempt_at: row.get(6)?,
+        dead_lettered: dead_lettered != 0,
+    })
+}
+
+const RETRY_COLUMNS: &str = "local_path, file_size, mod_time, attempts, last_error, last_failed_at, next_attempt_at, dead_lettered";
+
+/// Count a failed upload and schedule the next attempt
+/// The delay doubles with each attempt, starting at `base_delay_secs` and capped at an hour.
+/// After `max_attempts` failures (0 = never) the file is dead-lettered.
+/// A file that changed since its last failure starts counting from 1 again.
+pub fn record_failure(
+    config_id: &str,
+    local_path: &str,
+    file_size: u64,
+    mod_time: i64,
+    error: &str,
+    max_attempts: u32,
+    base_delay_secs: u64,
+) -> Result<RetryEntry, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let previous: Option<(i64, i64, u32)> = conn.query_row(
+        "SELECT file_size, mod_time, attempts FROM upload_retries WHERE config_id = ?1 AND local_path = ?2",
+        params![config_id, local_path],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).optional()?;
+
+    let attempts = match previous {
+        Some((size, time, attempts)) if size as u64 == file_size && time == mod_time => attempts + 1,
+        _ => 1,
+    };
+    let dead_lettered = max_attempts > 0 && attempts >= max_attempts;
+    let delay = base_delay_secs
+        .saturating_mul(1u64 << (attempts - 1).min(20))
+        .min(MAX_RETRY_DELAY_SECS);
+    let now = Utc::now().timestamp();
+
+    let entry = RetryEntry {
+        local_path: local_path.to_string(),
+        file_size,
+        mod_time,
+        attempts,
+        last_error: error.to_string(),
+        last_failed_at: now,
+        next_attempt_at: now + delay as i64,
+        dead_lettered,
+    };
+
+    conn.execute(
+        "INSERT INTO upload_retries (config_id, local_path, file_size, mod_time, attempts, last_error, last_failed_at, next_attempt_at, dead_lettered)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT(config_id, local_path)
+         DO UPDATE SET file_size = ?3, mod_time = ?4, attempts = ?5, last_error = ?6,
+                       last_failed_at = ?7, next_attempt_at = ?8, dead_lettered = ?9",
+        params![config_id, local_path, file_size as i64, mod_time, attempts, error, now, entry.next_attempt_at, dead_lettered as i64],
+    )?;
+
+    Ok(entry)
+}
+
+/// Remove files from the retry queue (after they were uploaded)
+pub fn clear_retries(config_id: &str, local_paths: &[&str]) -> Result<usize, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let mut conn = conn_mutex.lock().unwrap();
+
+    let tx = conn.transaction()?;
+    let mut cleared = 0;
+    {
+        let mut stmt = tx.prepare("DELETE FROM upload_retries WHERE config_id = ?1 AND local_path = ?2")?;
+        for local_path in local_paths {
+            cleared += stmt.execute(params![config_id, local_path])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(cleared)
+}
+
+/// Load the retry queue for a configuration, keyed by local path
+pub fn load_retry_queue(config_id: &str) -> Result<HashMap<String, RetryEntry>, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM upload_retries WHERE config_id = ?1", RETRY_COLUMNS))?;
+    let rows = stmt.query_map(params![config_id], retry_entry_from_row)?;
+
+    let mut queue = HashMap::new();
+    for row_result in rows {
+        let entry = row_result?;
+        queue.insert(entry.local_path.clone(), entry);
+    }
+    Ok(queue)
+}
+
+/// Files that failed too often and are no longer retried, most recent first
+/// Reads the config's own database, whether or not a session is running
+pub fn dead_letters(config_id: &str) -> Result<Vec<RetryEntry>, Box<dyn std::error::Error>> {
+    let Some(conn_mutex) = open_config_database(config_id)? else {
+        return Ok(Vec::new());
+    };
+    let conn = conn_mutex.lock().unwrap();
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM upload_retries WHERE config_id = ?1 AND dead_lettered = 1 ORDER BY last_failed_at DESC",
+        RETRY_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![config_id], retry_entry_from_row)?;
+
+    let mut entries = Vec::new();
+    for row_result in rows {
+        entries.push(row_result?);
+    }
+    Ok(entries)
+}
+
+/// Give dead-lettered files a fresh set of attempts, starting on the next scan
+/// `local_path` selects one file; `None` requeues all of them
+/// Writes to the config's own database; a running session picks the change up on its next scan
+pub fn requeue_dead_letters(config_id: &str, local_path: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+    let Some(conn_mutex) = open_config_database(config_id)? else {
+        return Ok(0);
+    };
+    let conn = conn_mutex.lock().unwrap();
+
+    let requeued = conn.execute(
+        "UPDATE upload_retries SET attempts = 0, dead_lettered = 0, next_attempt_at = 0
+         WHERE config_id = ?1 AND dead_lettered = 1 AND (?2 IS NULL OR local_path = ?2)",
+        params![config_id, local_path],
+    )?;
+
+    println!("🔄 DB: Requeued {} dead-lettered file(s) for config {}", requeued, config_id);
+    Ok(requeued)
+}
+
+/// Load the session limit learned for a server, if any
+pub fn load_server_limit(config_id: &str, server_key: &str) -> Result<Option<ServerLimit>, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let limit = conn.query_row(
+        "SELECT max_sessions, highest_ok, learned_at FROM server_limits WHERE server_key = ?1",
+        params![server_key],
+        |row| Ok(ServerLimit {
+            max_sessions: row.get(0)?,
+            highest_ok: row.get(1)?,
+            learned_at: row.get(2)?,
+        }),
+    ).optional()?;
+
+    Ok(limit)
+}
+
+/// Remember the session limit learned for a server
+pub fn save_server_limit(config_id: &str, server_key: &str, limit: &ServerLimit) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO server_limits (server_key, max_sessions, highest_ok, learned_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(server_key)
+         DO UPDATE SET max_sessions = ?2, highest_ok = ?3, learned_at = ?4",
+        params![server_key, limit.max_sessions, limit.highest_ok, limit.learned_at],
+    )?;
+
+    println!("📏 DB: {} accepts at most {} concurrent sessions", server_key, limit.max_sessions);
+    Ok(())
+}
+
+/// Record files deleted by the sent-files retention rules
+/// Each entry is (path, file_size, reason)
+pub fn record_prunes(
+    config_id: &str,
+    pruned: &[(String, u64, &str)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let now = Utc::now().timestamp();
+    let tx = conn.unchecked_transaction()?;
+    for (path, file_size, reason) in pruned {
+        tx.execute(
+            "INSERT INTO sent_prunes (config_id, path, file_size, reason, pruned_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![config_id, path, *file_size as i64, reason, now],
+        )?;
+    }
+    tx.commit()?;
+
+    println!("🧹 DB: Recorded {} pruned files for config {}", pruned.len(), config_id);
+    Ok(())
+}
+
+/// Remember that a file was moved into FTPU-Sent / the archive now
+pub fn record_sent_files(config_id: &str, files: &[(String, u64)]) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let now = Utc::now().timestamp();
+    let tx = conn.unchecked_transaction()?;
+    for (path, file_size) in files {
+        tx.execute(
+            "INSERT INTO sent_files (config_id, path, file_size, archived_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(config_id, path)
+             DO UPDATE SET file_size = ?3, archived_at = ?4",
+            params![config_id, path, *file_size as i64, now],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Files moved into FTPU-Sent / the archive, as (path, unix time they arrived)
+pub fn load_sent_files(config_id: &str) -> Result<Vec<(String, i64)>, Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let mut stmt = conn.prepare("SELECT path, archived_at FROM sent_files WHERE config_id = ?1")?;
+    let files = stmt
+        .query_map(params![config_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(files)
+}
+
+/// Stop tracking sent files that were pruned or are gone
+pub fn forget_sent_files(config_id: &str, paths: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
+    let conn = conn_mutex.lock().unwrap();
+
+    let tx = conn.unchecked_transaction()?;
+    for path in paths {
+        tx.execute(
+            "DELETE FROM sent_files WHERE config_id = ?1 AND path = ?2",
+            params![config_id, path],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
 /// Migrate data from legacy .hash file to database
 /// Returns number of entries migrated
 pub fn migrate_from_hash_file(
@@ -252,7 +1081,7 @@ pub fn migrate_from_hash_file(
     let mut migrated = 0;
     let now = Utc::now().timestamp();
 
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     // Use transaction for better performance
@@ -313,8 +1142,8 @@ pub fn migrate_from_hash_file(
 }
 
 /// Get database statistics
-pub fn get_stats() -> Result<(usize, usize), Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+pub fn get_stats(config_id: &str) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let mut stmt = conn.prepare("SELECT COUNT(*) FROM file_hashes")?;
@@ -329,7 +1158,7 @@ pub fn get_stats() -> Result<(usize, usize), Box<dyn std::error::Error>> {
 /// Delete all data for a specific configuration
 /// Useful when a configuration is deleted
 pub fn delete_config_data(config_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
-    let conn_mutex = get_connection()?;
+    let conn_mutex = get_connection(config_id)?;
     let conn = conn_mutex.lock().unwrap();
 
     let deleted = conn.execute(
@@ -337,6 +1166,270 @@ pub fn delete_config_data(config_id: &str) -> Result<usize, Box<dyn std::error::
         params![config_id],
     )?;
 
+    conn.execute(
+        "DELETE FROM upload_resume WHERE config_id = ?1",
+        params![config_id],
+    )?;
+
+    conn.execute(
+        "DELETE FROM sent_prunes WHERE config_id = ?1",
+        params![config_id],
+    )?;
+
+    conn.execute(
+        "DELETE FROM uploaded_content WHERE config_id = ?1",
+        params![config_id],
+    )?;
+
+    conn.execute(
+        "DELETE FROM dedupe_log WHERE config_id = ?1",
+        params![config_id],
+    )?;
+
+    conn.execute(
+        "DELETE FROM upload_retries WHERE config_id = ?1",
+        params![config_id],
+    )?;
+
+    conn.execute(
+        "DELETE FROM sent_files WHERE config_id = ?1",
+        params![config_id],
+    )?;
+
+    // upload_history is kept on purpose: it is the audit trail of what was sent
+
     println!("🗑️  DB: Deleted {} entries for config {}", deleted, config_id);
     Ok(deleted)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tables and the columns the current code relies on, after a full upgrade
+    const CURRENT_SCHEMA: &[(&str, &[&str])] = &[
+        ("file_hashes", &["config_id", "remote_dir", "filename", "file_size", "mod_time", "hash", "first_seen", "last_seen", "content_hash"]),
+        ("upload_resume", &["config_id", "local_path", "remote_path", "file_size", "mod_time", "offset", "updated_at"]),
+        ("sent_prunes", &["config_id", "path", "file_size", "reason", "pruned_at"]),
+        ("uploaded_content", &["config_id", "content_hash", "file_size", "remote_path", "local_path", "uploaded_at"]),
+        ("dedupe_log", &["config_id", "local_path", "remote_path", "duplicate_of", "content_hash", "action", "created_at"]),
+        ("upload_history", &["config_id", "local_path", "remote_path", "file_size", "duration_secs", "speed_mbps", "attempts", "outcome", "error", "started_at", "finished_at"]),
+        ("upload_retries", &["config_id", "local_path", "file_size", "mod_time", "attempts", "last_error", "last_failed_at", "next_attempt_at", "dead_lettered"]),
+        ("server_limits", &["server_key", "max_sessions", "highest_ok", "learned_at"]),
+        ("sent_files", &["config_id", "path", "file_size", "archived_at"]),
+    ];
+
+    fn user_version(conn: &Connection) -> u32 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap()
+    }
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table)).unwrap();
+        let names = stmt.query_map([], |row| row.get::<_, String>(1)).unwrap();
+        names.map(|name| name.unwrap()).collect()
+    }
+
+    fn assert_current_schema(conn: &Connection) {
+        assert_eq!(user_version(conn), SCHEMA_VERSION);
+        for (table, expected) in CURRENT_SCHEMA {
+            let found = columns(conn, table);
+            for column in *expected {
+                assert!(found.iter().any(|name| name == column), "{}.{} missing after upgrade (has {:?})", table, column, found);
+            }
+        }
+    }
+
+    /// Database as the given release left it: the first `version` steps applied
+    fn database_at_version(version: usize) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        apply_migrations(&mut conn, Path::new(":memory:"), &MIGRATIONS[..version]).unwrap();
+        assert_eq!(user_version(&conn), version as u32);
+        conn
+    }
+
+    /// Start a config's database in a fresh temp file, as its session would
+    fn start_config(config_id: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ftpu-db-{}-{}.db", config_id, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        init_database(config_id, &path).unwrap();
+        path
+    }
+
+    fn uploaded(local_path: &str) -> UploadRecord {
+        UploadRecord {
+            local_path: local_path.to_string(),
+            remote_path: format!("/up/{}", local_path),
+            file_size: 1,
+            duration_secs: 0.1,
+            speed_mbps: 0.0,
+            attempts: 1,
+            outcome: "uploaded".to_string(),
+            error: None,
+            started_at: 1,
+            finished_at: 2,
+        }
+    }
+
+    fn count_rows(path: &Path, table: &str) -> i64 {
+        Connection::open(path).unwrap().query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn each_running_config_writes_its_own_database() {
+        let first_path = start_config("two-configs-a");
+        let second_path = start_config("two-configs-b");
+        record_upload("two-configs-a", &uploaded("a.jpg")).unwrap();
+        record_upload("two-configs-b", &uploaded("b.jpg")).unwrap();
+
+        let history = query_upload_history("two-configs-b", &HistoryFilter::default()).unwrap();
+        assert_eq!(history.iter().map(|r| r.local_path.as_str()).collect::<Vec<_>>(), vec!["b.jpg"]);
+
+        // The rows are in the second config's file, not in the first session's
+        assert_eq!(count_rows(&second_path, "upload_history"), 1);
+        assert_eq!(count_rows(&first_path, "upload_history"), 1);
+    }
+
+    #[test]
+    fn dead_letters_of_a_later_config_are_readable_and_requeued() {
+        let first_path = start_config("dead-letters-a");
+        let second_path = start_config("dead-letters-b");
+        record_failure("dead-letters-b", "c.jpg", 1, 1, "550 denied", 1, 60).unwrap();
+
+        assert_eq!(dead_letters("dead-letters-b").unwrap().len(), 1);
+        assert!(dead_letters("dead-letters-a").unwrap().is_empty());
+        assert_eq!(requeue_dead_letters("dead-letters-b", None).unwrap(), 1);
+        // The running session's next scan sees the requeued file
+        assert!(!load_retry_queue("dead-letters-b").unwrap()["c.jpg"].dead_lettered);
+
+        assert_eq!(count_rows(&second_path, "upload_retries"), 1);
+        assert_eq!(count_rows(&first_path, "upload_retries"), 0);
+    }
+
+    #[test]
+    fn upgrades_empty_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn, Path::new(":memory:")).unwrap();
+        assert_current_schema(&conn);
+    }
+
+    #[test]
+    fn upgrades_unversioned_baseline_without_content_hash() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE file_hashes (
+                config_id TEXT NOT NULL,
+                remote_dir TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                file_size INTEGER NOT NULL,
+                mod_time INTEGER NOT NULL,
+                hash INTEGER NOT NULL,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                PRIMARY KEY (config_id, remote_dir, filename)
+            );
+            INSERT INTO file_hashes VALUES ('cfg', '/up', 'a.jpg', 10, 20, 30, 40, 50);"
+        ).unwrap();
+
+        migrate(&mut conn, Path::new(":memory:")).unwrap();
+
+        assert_current_schema(&conn);
+        let kept: (i64, Option<i64>) = conn.query_row(
+            "SELECT hash, content_hash FROM file_hashes WHERE filename = 'a.jpg'", [], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!(kept, (30, None));
+    }
+
+    #[test]
+    fn upgrades_unversioned_database_with_content_hash() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE file_hashes (
+                config_id TEXT NOT NULL,
+                remote_dir TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                file_size INTEGER NOT NULL,
+                mod_time INTEGER NOT NULL,
+                hash INTEGER NOT NULL,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                content_hash INTEGER,
+                PRIMARY KEY (config_id, remote_dir, filename)
+            );
+            CREATE TABLE upload_resume (
+                config_id TEXT NOT NULL,
+                local_path TEXT NOT NULL,
+                remote_path TEXT NOT NULL,
+                file_size INTEGER NOT NULL,
+                mod_time INTEGER NOT NULL,
+                offset INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (config_id, local_path)
+            );
+            INSERT INTO file_hashes VALUES ('cfg', '/up', 'a.jpg', 10, 20, 30, 40, 50, 60);
+            INSERT INTO upload_resume VALUES ('cfg', '/src/b.jpg', '.b.jpg.part', 100, 20, 50, 40);"
+        ).unwrap();
+
+        migrate(&mut conn, Path::new(":memory:")).unwrap();
+
+        assert_current_schema(&conn);
+        let content_hash: i64 = conn.query_row(
+            "SELECT content_hash FROM file_hashes WHERE filename = 'a.jpg'", [], |row| row.get(0)).unwrap();
+        assert_eq!(content_hash, 60);
+        let offset: i64 = conn.query_row("SELECT offset FROM upload_resume", [], |row| row.get(0)).unwrap();
+        assert_eq!(offset, 50);
+    }
+
+    #[test]
+    fn upgrades_every_past_version() {
+        for version in 1..SCHEMA_VERSION as usize {
+            let mut conn = database_at_version(version);
+            conn.execute("INSERT INTO file_hashes (config_id, remote_dir, filename, file_size, mod_time, hash, first_seen, last_seen)
+                VALUES ('cfg', '/up', 'a.jpg', 10, 20, 30, 40, 50)", []).unwrap();
+
+            migrate(&mut conn, Path::new(":memory:")).unwrap();
+
+            assert_current_schema(&conn);
+            let rows: i64 = conn.query_row("SELECT COUNT(*) FROM file_hashes", [], |row| row.get(0)).unwrap();
+            assert_eq!(rows, 1, "data lost upgrading from version {}", version);
+        }
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let mut conn = database_at_version(SCHEMA_VERSION as usize);
+        migrate(&mut conn, Path::new(":memory:")).unwrap();
+        assert_current_schema(&conn);
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+
+        let error = migrate(&mut conn, Path::new(":memory:")).unwrap_err();
+
+        let too_new = error.downcast_ref::<SchemaTooNew>().expect("SchemaTooNew");
+        assert_eq!((too_new.found, too_new.supported), (SCHEMA_VERSION + 1, SCHEMA_VERSION));
+        assert_eq!(user_version(&conn), SCHEMA_VERSION + 1);
+    }
+
+    #[test]
+    fn failed_step_keeps_previous_version() {
+        fn create_table(tx: &Transaction) -> rusqlite::Result<()> {
+            tx.execute_batch("CREATE TABLE first (id INTEGER)")
+        }
+        fn fail_halfway(tx: &Transaction) -> rusqlite::Result<()> {
+            tx.execute_batch("CREATE TABLE second (id INTEGER); INSERT INTO missing_table VALUES (1);")
+        }
+        let steps = [
+            Migration { description: "first", apply: create_table },
+            Migration { description: "broken", apply: fail_halfway },
+        ];
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        assert!(apply_migrations(&mut conn, Path::new(":memory:"), &steps).is_err());
+
+        assert_eq!(user_version(&conn), 1);
+        assert!(!columns(&conn, "first").is_empty());
+        assert!(columns(&conn, "second").is_empty(), "partial step was not rolled back");
+    }
+}
@@ -0,0 +1,308 @@
+//!
+//! Post-upload disposition
+//!
+//! What happens to a local file once it has been uploaded: moved into
+//! `<source>/FTPU-Sent` (the original behaviour), moved to an archive folder
+//! outside the source, deleted, renamed in place with a suffix, or left
+//! untouched. Files that are left in place are remembered by the engine in
+//! the database (by size and modification time) so they are not uploaded
+//! again on the next scan.
+//!
+//! Moves can optionally go into a dated subfolder, e.g.
+//! `FTPU-Sent/2026-10-16/photos/a.jpg`. A file never overwrites an earlier
+//! one with the same name: it gets a `_1`, `_2`, ... counter instead.
+//!
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::Local;
+use serde::Deserialize;
+
+/// Folder inside the source that sent files are moved to by default
+pub const SENT_DIR_NAME: &str = "FTPU-Sent";
+
+/// What to do with a local file after a successful upload
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PostUploadAction {
+    /// Move into `<source>/FTPU-Sent`, keeping the folder structure
+    #[default]
+    MoveToSent,
+    /// Move into `archive_path` (outside the source), keeping the folder structure
+    Archive,
+    /// Delete the local file
+    Delete,
+    /// Leave the file untouched (keep/mirror mode); the engine tracks it so
+    /// only new or changed files are uploaded
+    #[serde(alias = "keep")]
+    Leave,
+    /// Rename in place by appending `rename_suffix`
+    Rename,
+}
+
+/// Result of applying the policy to one file, for logging
+#[derive(Debug)]
+pub enum Disposition {
+    Moved(PathBuf),
+    Renamed(PathBuf),
+    Deleted,
+    Left,
+}
+
+impl std::fmt::Display for Disposition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Disposition::Moved(path) => write!(f, "moved to {}", path.display()),
+            Disposition::Renamed(path) => write!(f, "renamed to {}", path.display()),
+            Disposition::Deleted => write!(f, "deleted"),
+            Disposition::Left => write!(f, "left in place"),
+        }
+    }
+}
+
+/// Validated post-upload settings
+#[derive(Debug, Clone, Default)]
+pub struct DispositionPolicy {
+    pub action: PostUploadAction,
+    archive_path: Option<PathBuf>,
+    rename_suffix: String,
+    date_folders: bool,
+}
+
+impl DispositionPolicy {
+    /// Check the settings against the source folder
+    ///
+    /// An archive inside the source folder would be scanned and uploaded
+    /// again, and an empty rename suffix would not rename anything, so both
+    /// are rejected up front.
+    pub fn new(
+        action: PostUploadAction,
+        source_dir: &Path,
+        archive_path: Option<&str>,
+        rename_suffix: &str,
+        date_folders: bool,
+    ) -> Result<DispositionPolicy, String> {
+        let archive_path = match action {
+            PostUploadAction::Archive => {
+                let archive = archive_path
+                    .filter(|p| !p.trim().is_empty())
+                    .map(PathBuf::from)
+                    .ok_or_else(|| "post_upload_action \"archive\" needs an archive_path".to_string())?;
+                // Compare real locations: `..` segments or a symlink could hide that it is inside
+                if resolve_path(&archive).starts_with(resolve_path(source_dir)) {
+                    return Err(format!(
+                        "archive_path {} is inside the source folder {}",
+                        archive.display(),
+                        source_dir.display()
+                    ));
+                }
+                Some(archive)
+            }
+            _ => None,
+        };
+
+        if action == PostUploadAction::Rename && rename_suffix.is_empty() {
+            return Err("post_upload_action \"rename\" needs a non-empty rename_suffix".to_string());
+        }
+
+        Ok(DispositionPolicy {
+            action,
+            archive_path,
+            rename_suffix: rename_suffix.to_string(),
+            date_folders,
+        })
+    }
+
+    /// Whether a file name marks a file this policy already handled, so the
+    /// scan must not pick it up again
+    pub fn is_disposed_name(&self, filename: &str) -> bool {
+        self.action == PostUploadAction::Rename && filename.ends_with(&self.rename_suffix)
+    }
+
+    /// Folder sent files are moved into, if the policy keeps them at all
+    pub fn sent_root(&self, source_dir: &Path) -> Option<PathBuf> {
+        match self.action {
+            PostUploadAction::MoveToSent => Some(source_dir.join(SENT_DIR_NAME)),
+            PostUploadAction::Archive => self.archive_path.clone(),
+            _ => None,
+        }
+    }
+
+    /// Apply the policy to `local_path`, a file under `source_dir`
+    pub fn apply(&self, local_path: &Path, source_dir: &Path) -> io::Result<Disposition> {
+        match self.action {
+            PostUploadAction::MoveToSent => {
+                let dest = unique_path(&self.destination(&source_dir.join(SENT_DIR_NAME), local_path, source_dir));
+                move_file(local_path, &dest)?;
+                Ok(Disposition::Moved(dest))
+            }
+            PostUploadAction::Archive => {
+                // Validated in `new`
+                let archive = self.archive_path.as_deref().unwrap_or(source_dir);
+                let dest = unique_path(&self.destination(archive, local_path, source_dir));
+                move_file(local_path, &dest)?;
+                Ok(Disposition::Moved(dest))
+            }
+            PostUploadAction::Delete => {
+                fs::remove_file(local_path)?;
+                Ok(Disposition::Deleted)
+            }
+            PostUploadAction::Rename => {
+                // A counter goes before the suffix, so the name still marks the file as handled
+                let renamed = |counter: Option<u64>| {
+                    let mut renamed = local_path.as_os_str().to_owned();
+                    if let Some(counter) = counter {
+                        renamed.push(format!("_{}", counter));
+                    }
+                    renamed.push(&self.rename_suffix);
+                    PathBuf::from(renamed)
+                };
+                let dest = free_path(&renamed(None), |counter| renamed(Some(counter)));
+                fs::rename(local_path, &dest)?;
+                Ok(Disposition::Renamed(dest))
+            }
+            PostUploadAction::Leave => Ok(Disposition::Left),
+        }
+    }
+
+    /// `root[/YYYY-MM-DD]/<path relative to source>`
+    fn destination(&self, root: &Path, local_path: &Path, source_dir: &Path) -> PathBuf {
+        let mut dest = root.to_path_buf();
+        if self.date_folders {
+            dest.push(Local::now().format("%Y-%m-%d").to_string());
+        }
+        let relative = local_path.strip_prefix(source_dir).unwrap_or(local_path);
+        dest.join(relative)
+    }
+}
+
+/// Absolute form of `path` with `..` and symlinks resolved, also for paths
+/// that do not exist yet (their missing tail is resolved lexically)
+fn resolve_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().map(|cwd| cwd.join(path)).unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            // `resolved` is already a real path wherever it exists, so its parent is the real parent
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => {
+                resolved.push(other);
+                if let Ok(real) = resolved.canonicalize() {
+                    resolved = real;
+                }
+            }
+        }
+    }
+    resolved
+}
+
+/// `path` itself if nothing exists there, otherwise the first free `name_N.ext`
+fn unique_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    free_path(path, |counter| path.with_file_name(format!("{}_{}{}", stem, counter, extension)))
+}
+
+/// `path` if it is free, otherwise the first free `numbered(1)`, `numbered(2)`, ...
+fn free_path(path: &Path, numbered: impl Fn(u64) -> PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    for counter in 1..=999 {
+        let candidate = numbered(counter);
+        if !candidate.exists() {
+            return candidate;
+        }
+    }
+
+    // Fallback: number with the current timestamp
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    numbered(timestamp)
+}
+
+/// Move a file, creating parent folders; falls back to copy + delete when the
+/// destination is on another volume
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    fn archive_policy(source: &Path, archive: &Path) -> Result<DispositionPolicy, String> {
+        DispositionPolicy::new(PostUploadAction::Archive, source, Some(&archive.to_string_lossy()), "", false)
+    }
+
+    #[test]
+    fn rejects_archive_inside_source_through_dot_dot() {
+        let root = scratch_dir("dotdot");
+        let source = root.join("source");
+        fs::create_dir_all(source.join("sub")).unwrap();
+
+        assert!(archive_policy(&source, &source.join("sub/../archive")).is_err());
+        assert!(archive_policy(&source, &root.join("elsewhere/../source/archive")).is_err());
+        assert!(archive_policy(&source, &source.join("sub/../../archive")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_archive_inside_source_through_symlink() {
+        let root = scratch_dir("symlink");
+        let source = root.join("source");
+        fs::create_dir_all(&source).unwrap();
+        std::os::unix::fs::symlink(&source, root.join("link")).unwrap();
+
+        assert!(archive_policy(&source, &root.join("link/archive")).is_err());
+        assert!(archive_policy(&root.join("link"), &source.join("archive")).is_err());
+    }
+
+    #[test]
+    fn moves_do_not_overwrite_earlier_files() {
+        let source = scratch_dir("collide");
+        let policy = DispositionPolicy::new(PostUploadAction::MoveToSent, &source, None, "", false).unwrap();
+
+        for content in ["first", "second"] {
+            fs::write(source.join("a.txt"), content).unwrap();
+            policy.apply(&source.join("a.txt"), &source).unwrap();
+        }
+        let sent = source.join(SENT_DIR_NAME);
+        assert_eq!(fs::read_to_string(sent.join("a.txt")).unwrap(), "first");
+        assert_eq!(fs::read_to_string(sent.join("a_1.txt")).unwrap(), "second");
+    }
+
+    #[test]
+    fn renames_keep_the_suffix_last() {
+        let source = scratch_dir("rename");
+        let policy = DispositionPolicy::new(PostUploadAction::Rename, &source, None, ".sent.bak", false).unwrap();
+
+        for _ in 0..2 {
+            fs::write(source.join("a.txt"), "x").unwrap();
+            policy.apply(&source.join("a.txt"), &source).unwrap();
+        }
+        assert!(source.join("a.txt.sent.bak").exists());
+        assert!(source.join("a.txt_1.sent.bak").exists());
+        assert!(policy.is_disposed_name("a.txt_1.sent.bak"));
+    }
+}
@@ -0,0 +1,321 @@
+//!
+//! Transfer error classification
+//!
+//! Decides how the engine reacts to a failure from the FTP reply code or the
+//! `FtpError` / `io::Error` variant rather than from the error text: 4xx
+//! replies are transient and retried with backoff, 5xx replies are permanent
+//! and are not, and 421 means the server is over its connection limit, so the
+//! worker count is reduced. A 530 is wrong credentials unless other sessions
+//! with the same credentials are logged in, see `classify_login`. SFTP status
+//! codes arrive as the equivalent FTP reply (see `sftp.rs`), and the SFTP
+//! login reports failed authentication as a 530.
+//!
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use ftp::types::FtpError;
+
+/// What kind of failure an error is, as far as retrying is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 421: too many sessions
+    ServerBusy,
+    /// 530 while other sessions with the same credentials are logged in - what
+    /// many servers reply once the per-user session limit is reached
+    LoginRefused,
+    /// 530, or failed SFTP authentication: wrong user name, password or key
+    AuthFailed,
+    /// Any other 4xx reply (425 no data connection, 426 aborted, 450/451 busy, 452 out of space)
+    Transient(u32),
+    /// 550 / 553: no permission, or the file name is not allowed
+    PermissionDenied(u32),
+    /// 552: storage quota exceeded
+    QuotaExceeded,
+    /// Any other 5xx reply
+    Permanent(u32),
+    /// Timeout, reset, broken pipe and other dropped connections
+    Network,
+    /// TLS or SSH host key verification failed
+    Security,
+    /// The local file could not be read, e.g. locked or briefly unreadable; retried
+    LocalFile,
+    /// The local file is gone (moved, renamed or deleted since the scan); skipped, not a failure
+    Vanished,
+    /// The upload was abandoned because the session is shutting down
+    Cancelled,
+    /// Not attempted: the server is considered down until a probe succeeds
+    ServerUnavailable,
+    /// Anything else, e.g. a garbled reply or a failed verification; retried
+    Other,
+}
+
+impl ErrorKind {
+    /// Map an FTP reply code
+    pub fn from_reply_code(code: u32) -> ErrorKind {
+        match code {
+            421 => ErrorKind::ServerBusy,
+            530 => ErrorKind::AuthFailed,
+            400..=499 => ErrorKind::Transient(code),
+            550 | 553 => ErrorKind::PermissionDenied(code),
+            552 => ErrorKind::QuotaExceeded,
+            500..=599 => ErrorKind::Permanent(code),
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Retrying cannot help until something changes on the server or locally
+    pub fn is_permanent(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::AuthFailed
+                | ErrorKind::PermissionDenied(_)
+                | ErrorKind::QuotaExceeded
+                | ErrorKind::Permanent(_)
+                | ErrorKind::Security
+        )
+    }
+
+    /// The server is refusing more sessions; back off and use fewer connections
+    pub fn is_server_rejection(self) -> bool {
+        matches!(self, ErrorKind::ServerBusy | ErrorKind::LoginRefused)
+    }
+
+    pub fn is_network(self) -> bool {
+        self == ErrorKind::Network
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::ServerBusy => write!(f, "server busy"),
+            ErrorKind::LoginRefused => write!(f, "login refused"),
+            ErrorKind::AuthFailed => write!(f, "authentication failed"),
+            ErrorKind::Transient(code) => write!(f, "transient error {}", code),
+            ErrorKind::PermissionDenied(code) => write!(f, "permission denied ({})", code),
+            ErrorKind::QuotaExceeded => write!(f, "quota exceeded"),
+            ErrorKind::Permanent(code) => write!(f, "permanent error {}", code),
+            ErrorKind::Network => write!(f, "network error"),
+            ErrorKind::Security => write!(f, "security check failed"),
+            ErrorKind::LocalFile => write!(f, "local file error"),
+            ErrorKind::Vanished => write!(f, "local file vanished"),
+            ErrorKind::Cancelled => write!(f, "cancelled"),
+            ErrorKind::ServerUnavailable => write!(f, "server unavailable"),
+            ErrorKind::Other => write!(f, "error"),
+        }
+    }
+}
+
+/// Reply code carried by an FTP error, e.g. 502 for an unimplemented command
+pub fn reply_code(error: &FtpError) -> Option<u32> {
+    match error {
+        FtpError::InvalidResponse(message) => message
+            .split("got response: ")
+            .nth(1)
+            .and_then(|reply| reply.get(0..3))
+            .and_then(|code| code.parse().ok()),
+        _ => None,
+    }
+}
+
+/// Reading the local file failed while it was being sent
+///
+/// The transports return it inside `FtpError::ConnectionError` (see
+/// `local_read_error`), so it has to be told apart from a dropped connection.
+#[derive(Debug)]
+pub struct LocalReadError(pub io::Error);
+
+impl fmt::Display for LocalReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not read the local file: {}", self.0)
+    }
+}
+
+impl Error for LocalReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Transport error for a failed read of the file being uploaded
+pub fn local_read_error(error: io::Error) -> FtpError {
+    FtpError::ConnectionError(io::Error::new(error.kind(), LocalReadError(error)))
+}
+
+/// Classify an error from the FTP or SFTP transport
+pub fn classify_ftp(error: &FtpError) -> ErrorKind {
+    match error {
+        FtpError::ConnectionError(io_error) => match io_error.get_ref().and_then(|inner| inner.downcast_ref::<LocalReadError>()) {
+            Some(LocalReadError(local_error)) => classify_local(local_error),
+            None => classify_connection(io_error),
+        },
+        FtpError::SecureError(_) => ErrorKind::Security,
+        FtpError::InvalidResponse(_) => reply_code(error).map_or(ErrorKind::Other, ErrorKind::from_reply_code),
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Classify a failed connect or login
+///
+/// `other_sessions` counts sessions with the same credentials that are logged
+/// in right now. A refused login then cannot be a wrong password, so it is the
+/// server's per-user session limit.
+pub fn classify_login(error: &FtpError, other_sessions: usize) -> ErrorKind {
+    match classify_ftp(error) {
+        ErrorKind::AuthFailed if other_sessions > 0 => ErrorKind::LoginRefused,
+        kind => kind,
+    }
+}
+
+/// The server refused the TCP connection (down, or the port is closed)
+pub fn is_connection_refused(error: &FtpError) -> bool {
+    matches!(error, FtpError::ConnectionError(io_error) if io_error.kind() == io::ErrorKind::ConnectionRefused)
+}
+
+/// Classify a boxed error from the upload path (transport, local file or verification)
+pub fn classify(error: &(dyn Error + 'static)) -> ErrorKind {
+    if let Some(ftp_error) = error.downcast_ref::<FtpError>() {
+        classify_ftp(ftp_error)
+    } else if let Some(io_error) = error.downcast_ref::<io::Error>() {
+        // Plain I/O errors on this path come from reading the local file
+        classify_local(io_error)
+    } else {
+        ErrorKind::Other
+    }
+}
+
+/// I/O errors reading the local file
+fn classify_local(error: &io::Error) -> ErrorKind {
+    match classify_connection(error) {
+        ErrorKind::Network => ErrorKind::Network,
+        _ if error.kind() == io::ErrorKind::NotFound => ErrorKind::Vanished,
+        _ => ErrorKind::LocalFile,
+    }
+}
+
+/// I/O errors on the control or data connection
+fn classify_connection(error: &io::Error) -> ErrorKind {
+    match error.kind() {
+        io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::TimedOut
+        | io::ErrorKind::WouldBlock
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::HostUnreachable
+        | io::ErrorKind::NetworkUnreachable
+        | io::ErrorKind::NetworkDown => ErrorKind::Network,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Why a file could not be uploaded
+#[derive(Debug, Clone)]
+pub struct TransferError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl TransferError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> TransferError {
+        TransferError { kind, message: message.into() }
+    }
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for TransferError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(line: &str) -> FtpError {
+        FtpError::InvalidResponse(format!("Expected code [226], got response: {}\r\n", line))
+    }
+
+    fn connection(kind: io::ErrorKind) -> FtpError {
+        FtpError::ConnectionError(io::Error::new(kind, "test"))
+    }
+
+    #[test]
+    fn maps_reply_codes() {
+        assert_eq!(ErrorKind::from_reply_code(421), ErrorKind::ServerBusy);
+        assert_eq!(ErrorKind::from_reply_code(425), ErrorKind::Transient(425));
+        assert_eq!(ErrorKind::from_reply_code(451), ErrorKind::Transient(451));
+        assert_eq!(ErrorKind::from_reply_code(530), ErrorKind::AuthFailed);
+        assert_eq!(ErrorKind::from_reply_code(550), ErrorKind::PermissionDenied(550));
+        assert_eq!(ErrorKind::from_reply_code(553), ErrorKind::PermissionDenied(553));
+        assert_eq!(ErrorKind::from_reply_code(552), ErrorKind::QuotaExceeded);
+        assert_eq!(ErrorKind::from_reply_code(501), ErrorKind::Permanent(501));
+        assert_eq!(ErrorKind::from_reply_code(226), ErrorKind::Other);
+    }
+
+    #[test]
+    fn only_5xx_and_auth_failures_are_permanent() {
+        for code in [421, 425, 450, 451, 452] {
+            assert!(!ErrorKind::from_reply_code(code).is_permanent(), "{}", code);
+        }
+        for code in [500, 501, 502, 530, 550, 552, 553] {
+            assert!(ErrorKind::from_reply_code(code).is_permanent(), "{}", code);
+        }
+        assert!(!ErrorKind::LoginRefused.is_permanent());
+    }
+
+    #[test]
+    fn reads_reply_code_from_error() {
+        assert_eq!(reply_code(&reply("550 No such file")), Some(550));
+        assert_eq!(reply_code(&FtpError::InvalidResponse("garbage".to_string())), None);
+        assert_eq!(reply_code(&connection(io::ErrorKind::TimedOut)), None);
+        assert_eq!(classify_ftp(&reply("452 Insufficient storage")), ErrorKind::Transient(452));
+        assert_eq!(classify_ftp(&reply("552 Quota exceeded")), ErrorKind::QuotaExceeded);
+    }
+
+    #[test]
+    fn refused_login_is_auth_failure_unless_others_are_logged_in() {
+        assert_eq!(classify_login(&reply("530 Login incorrect"), 0), ErrorKind::AuthFailed);
+        assert_eq!(classify_login(&reply("530 Too many users"), 2), ErrorKind::LoginRefused);
+        assert!(classify_login(&reply("530 Too many users"), 2).is_server_rejection());
+        assert!(!classify_login(&reply("530 Login incorrect"), 0).is_server_rejection());
+        assert_eq!(classify_login(&reply("421 Too many connections"), 0), ErrorKind::ServerBusy);
+    }
+
+    #[test]
+    fn permission_denied_on_the_connection_is_not_an_auth_failure() {
+        assert_eq!(classify_ftp(&connection(io::ErrorKind::PermissionDenied)), ErrorKind::Other);
+    }
+
+    #[test]
+    fn local_read_errors_during_a_transfer() {
+        let unreadable = local_read_error(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        assert_eq!(classify_ftp(&unreadable), ErrorKind::LocalFile);
+        assert_eq!(classify_login(&unreadable, 0), ErrorKind::LocalFile);
+        let gone = local_read_error(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert_eq!(classify(&gone), ErrorKind::Vanished);
+    }
+
+    #[test]
+    fn refused_connection_is_a_network_error() {
+        assert_eq!(classify_ftp(&connection(io::ErrorKind::ConnectionRefused)), ErrorKind::Network);
+        assert_eq!(classify_ftp(&connection(io::ErrorKind::ConnectionReset)), ErrorKind::Network);
+        assert_eq!(classify_ftp(&FtpError::SecureError("bad certificate".to_string())), ErrorKind::Security);
+    }
+
+    #[test]
+    fn local_io_errors() {
+        let not_found = io::Error::new(io::ErrorKind::NotFound, "gone");
+        assert_eq!(classify(&not_found), ErrorKind::Vanished);
+        let locked = io::Error::new(io::ErrorKind::PermissionDenied, "locked");
+        assert_eq!(classify(&locked), ErrorKind::LocalFile);
+        let transfer = TransferError::new(ErrorKind::Other, "verification failed");
+        assert_eq!(classify(&transfer), ErrorKind::Other);
+    }
+}
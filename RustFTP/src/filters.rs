@@ -0,0 +1,302 @@
+//!
+//! Include / exclude rules for the local scan
+//!
+//! Patterns are matched against the path relative to the source folder, using
+//! `/` separators. Each pattern is a glob (`**/*.cr2`, `*.tmp`) unless it starts
+//! with `re:`, in which case the rest is a regular expression. Globs ignore
+//! case, since the source folders usually live on case-insensitive volumes.
+//! An include pattern starting with `!` is treated as an exclude, so a single
+//! list like `["**/*.cr2", "!**/tmp/**"]` works.
+//!
+//! Users can also drop `.ftpignore` files into any folder under the source
+//! folder. They use gitignore syntax and apply to that folder and everything
+//! below it, with deeper files able to override (`!keep.jpg`) outer ones.
+//!
+
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use regex::Regex;
+
+/// Per-directory ignore file, never uploaded itself
+pub const IGNORE_FILE_NAME: &str = ".ftpignore";
+
+/// One side (include or exclude) of the filter
+#[derive(Debug, Clone, Default)]
+struct PatternSet {
+    globs: Option<GlobSet>,
+    regexes: Vec<Regex>,
+}
+
+impl PatternSet {
+    fn build(patterns: &[&str]) -> Result<PatternSet, String> {
+        let mut globs = GlobSetBuilder::new();
+        let mut glob_count = 0;
+        let mut regexes = Vec::new();
+
+        for pattern in patterns {
+            if let Some(expression) = pattern.strip_prefix("re:") {
+                let regex = Regex::new(expression)
+                    .map_err(|e| format!("invalid regex '{}': {}", expression, e))?;
+                regexes.push(regex);
+            } else {
+                let glob = GlobBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("invalid glob '{}': {}", pattern, e))?;
+                globs.add(glob);
+                glob_count += 1;
+            }
+        }
+
+        let globs = if glob_count > 0 {
+            Some(globs.build().map_err(|e| format!("invalid glob set: {}", e))?)
+        } else {
+            None
+        };
+        Ok(PatternSet { globs, regexes })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.globs.is_none() && self.regexes.is_empty()
+    }
+
+    fn matches(&self, relative_path: &str) -> bool {
+        self.globs.as_ref().is_some_and(|g| g.is_match(relative_path))
+            || self.regexes.iter().any(|r| r.is_match(relative_path))
+    }
+}
+
+/// Compiled upload filter built from the `include` / `exclude` / size settings
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    include: PatternSet,
+    exclude: PatternSet,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl FileFilter {
+    /// Compile the configured patterns; an invalid pattern is reported instead of ignored
+    pub fn new(include: &[String], exclude: &[String], min_size: Option<u64>, max_size: Option<u64>) -> Result<FileFilter, String> {
+        let (negated, included): (Vec<&str>, Vec<&str>) = include
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .partition(|p| p.starts_with('!'));
+
+        let mut excluded: Vec<&str> = exclude.iter().map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+        excluded.extend(negated.iter().map(|p| &p[1..]));
+
+        Ok(FileFilter {
+            include: PatternSet::build(&included)?,
+            exclude: PatternSet::build(&excluded)?,
+            min_size,
+            max_size,
+        })
+    }
+
+    /// Whether a file at `relative_path` of `size` bytes should be uploaded
+    pub fn allows(&self, relative_path: &str, size: u64) -> bool {
+        // Normalise separators so patterns behave the same on every platform
+        let relative_path = relative_path.replace('\\', "/");
+
+        if self.min_size.is_some_and(|min| size < min) || self.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.matches(&relative_path) {
+            return false;
+        }
+        !self.exclude.matches(&relative_path)
+    }
+}
+
+/// The `.ftpignore` files in effect while walking a directory tree, outermost first
+#[derive(Debug, Default)]
+pub struct IgnoreStack {
+    matchers: Vec<Gitignore>,
+}
+
+impl IgnoreStack {
+    /// Load every `.ftpignore` from `base_dir` down to `dir` (inclusive)
+    ///
+    /// Used when a single path is checked without walking the tree, e.g. for
+    /// paths reported by the filesystem watcher.
+    pub fn for_directory(base_dir: &Path, dir: &Path) -> IgnoreStack {
+        let mut stack = IgnoreStack::default();
+        stack.push_dir(base_dir);
+        if let Ok(relative) = dir.strip_prefix(base_dir) {
+            let mut current = base_dir.to_path_buf();
+            for component in relative.components() {
+                current.push(component);
+                stack.push_dir(&current);
+            }
+        }
+        stack
+    }
+
+    /// Enter `dir`: load its `.ftpignore` if it has one. Returns whether a
+    /// matcher was pushed, so the caller knows to `pop` when leaving.
+    pub fn push_dir(&mut self, dir: &Path) -> bool {
+        let ignore_file = dir.join(IGNORE_FILE_NAME);
+        if !ignore_file.is_file() {
+            return false;
+        }
+
+        // Like git, unparsable lines are skipped and the rest still apply
+        let mut builder = GitignoreBuilder::new(dir);
+        let _ = builder.add(&ignore_file);
+        match builder.build() {
+            Ok(matcher) => {
+                self.matchers.push(matcher);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Leave the directory whose `.ftpignore` was pushed last
+    pub fn pop(&mut self) {
+        self.matchers.pop();
+    }
+
+    /// Whether `path` (or a folder containing it) is ignored
+    ///
+    /// The innermost `.ftpignore` with an opinion wins, so a subfolder can
+    /// re-include what a parent folder excluded.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|name| name == IGNORE_FILE_NAME) {
+            return true;
+        }
+
+        for matcher in self.matchers.iter().rev() {
+            if !path.starts_with(matcher.path()) {
+                continue;
+            }
+            match matcher.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    fn write_ignore(dir: &Path, contents: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(IGNORE_FILE_NAME), contents).unwrap();
+    }
+
+    fn filter(include: &[&str], exclude: &[&str]) -> FileFilter {
+        let strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        FileFilter::new(&strings(include), &strings(exclude), None, None).unwrap()
+    }
+
+    #[test]
+    fn empty_filter_allows_everything() {
+        assert!(filter(&[], &[]).allows("a/b/c.jpg", 0));
+    }
+
+    #[test]
+    fn negated_include_excludes() {
+        let filter = filter(&["**/*.cr2", "!**/tmp/**"], &[]);
+        assert!(filter.allows("shoot/IMG_1.CR2", 10));
+        assert!(!filter.allows("shoot/tmp/IMG_2.cr2", 10));
+        assert!(!filter.allows("shoot/IMG_1.jpg", 10));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let filter = filter(&["**/*.jpg"], &["**/thumbs/**"]);
+        assert!(filter.allows("a/b.jpg", 10));
+        assert!(!filter.allows("a/thumbs/b.jpg", 10));
+    }
+
+    #[test]
+    fn globs_match_the_whole_relative_path() {
+        let filter = filter(&["photos/*.jpg"], &[]);
+        assert!(filter.allows("photos/a.jpg", 10));
+        assert!(!filter.allows("old/photos/a.jpg", 10));
+        assert!(!filter.allows("photos/a.jpg.part", 10));
+    }
+
+    #[test]
+    fn regexes_are_anchored_only_when_asked() {
+        let filter = filter(&[], &["re:^raw/", "re:~$"]);
+        assert!(!filter.allows("raw/a.jpg", 10));
+        assert!(filter.allows("edited/raw/a.jpg", 10));
+        assert!(!filter.allows("edited/a.jpg~", 10));
+    }
+
+    #[test]
+    fn windows_separators_are_normalised() {
+        assert!(!filter(&[], &["**/tmp/**"]).allows("a\\tmp\\b.jpg", 10));
+    }
+
+    #[test]
+    fn size_limits_are_inclusive() {
+        let filter = FileFilter::new(&[], &[], Some(10), Some(20)).unwrap();
+        assert!(!filter.allows("a.jpg", 9));
+        assert!(filter.allows("a.jpg", 10));
+        assert!(filter.allows("a.jpg", 20));
+        assert!(!filter.allows("a.jpg", 21));
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        assert!(FileFilter::new(&["re:(".to_string()], &[], None, None).is_err());
+        assert!(FileFilter::new(&[], &["a[".to_string()], None, None).is_err());
+    }
+
+    #[test]
+    fn deeper_ignore_file_overrides_outer_one() {
+        let root = scratch_dir("override");
+        write_ignore(&root, "*.jpg\n");
+        write_ignore(&root.join("keep"), "!keep.jpg\n");
+
+        let stack = IgnoreStack::for_directory(&root, &root.join("keep"));
+        assert!(stack.is_ignored(&root.join("a.jpg"), false));
+        assert!(stack.is_ignored(&root.join("keep/other.jpg"), false));
+        assert!(!stack.is_ignored(&root.join("keep/keep.jpg"), false));
+        assert!(!stack.is_ignored(&root.join("keep/notes.txt"), false));
+    }
+
+    #[test]
+    fn anchored_patterns_only_match_next_to_the_ignore_file() {
+        let root = scratch_dir("anchored");
+        write_ignore(&root, "/drafts/\nbuild/\n");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+
+        let stack = IgnoreStack::for_directory(&root, &root.join("sub"));
+        assert!(stack.is_ignored(&root.join("drafts/a.jpg"), false));
+        assert!(!stack.is_ignored(&root.join("sub/drafts/a.jpg"), false));
+        assert!(stack.is_ignored(&root.join("sub/build/a.jpg"), false));
+    }
+
+    #[test]
+    fn popping_a_directory_drops_its_rules() {
+        let root = scratch_dir("pop");
+        write_ignore(&root.join("sub"), "*.tmp\n");
+
+        let mut stack = IgnoreStack::default();
+        assert!(!stack.push_dir(&root));
+        assert!(stack.push_dir(&root.join("sub")));
+        assert!(stack.is_ignored(&root.join("sub/a.tmp"), false));
+        stack.pop();
+        assert!(!stack.is_ignored(&root.join("sub/a.tmp"), false));
+    }
+
+    #[test]
+    fn ignore_file_itself_is_never_uploaded() {
+        let root = scratch_dir("self");
+        assert!(IgnoreStack::default().is_ignored(&root.join(IGNORE_FILE_NAME), false));
+    }
+}
@@ -0,0 +1,567 @@
+//!
+//! FTP / FTPS control-channel client
+//!
+//! The `ftp` crate can only do TLS through its `secure` feature, which is pinned
+//! to openssl 0.9 (does not build against OpenSSL 3) and has no implicit mode.
+//! This client speaks the protocol directly so the engine can connect in plain,
+//! explicit (`AUTH TLS`) or implicit (port 990) mode with `PROT P` data channels.
+//!
+//! It deliberately mirrors the `ftp::FtpStream` API and reuses the crate's
+//! error, type and reply-code definitions, so call sites read the same.
+//!
+//! Limitation: data connections start a fresh TLS session instead of resuming
+//! the control connection's (native-tls has no API for it). Servers that insist
+//! on session reuse - vsftpd with `require_ssl_reuse=YES`, its default - refuse
+//! every `PROT P` transfer; they have to turn the requirement off.
+//!
+
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use ftp::status;
+use ftp::types::{FileType, FtpError, Line, Result};
+use native_tls::{TlsConnector, TlsStream};
+use serde::Deserialize;
+
+use crate::transport::send_file;
+
+/// Longest wait for a reply or for the socket to accept more data; generous
+/// because some servers only answer STOR or a digest command once the file is
+/// on disk or hashed
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Wait for replies a live server sends at once (NOOP, QUIT), so a session that
+/// was silently dropped (e.g. by NAT) while idle is detected quickly
+const QUICK_REPLY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How (and whether) the connection is protected with TLS
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsMode {
+    /// Plain FTP - credentials and data in cleartext
+    #[default]
+    None,
+    /// Connect in plain text, then upgrade with `AUTH TLS` (usually port 21)
+    Explicit,
+    /// TLS handshake immediately on connect (usually port 990)
+    Implicit,
+}
+
+/// Socket that is either plain TCP or wrapped in TLS
+enum NetStream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl NetStream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            NetStream::Tcp(stream) => stream,
+            NetStream::Tls(stream) => stream.get_ref(),
+        }
+    }
+
+    /// Bound every blocking read and write so a dead peer cannot hang the caller
+    fn set_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.tcp().set_read_timeout(Some(timeout))?;
+        self.tcp().set_write_timeout(Some(timeout))
+    }
+
+    /// End an upload cleanly: send close_notify (TLS), half-close, then drain
+    /// whatever the server still sends until it closes its side
+    ///
+    /// Closing a socket that has unread data (e.g. TLS 1.3 session tickets)
+    /// makes the kernel answer with RST, and the server may then discard the
+    /// tail of the file it has not read yet.
+    fn finish(&mut self) -> io::Result<()> {
+        if let NetStream::Tls(stream) = self {
+            stream.shutdown()?;
+        }
+        self.tcp().shutdown(Shutdown::Write)?;
+        self.tcp().set_read_timeout(Some(Duration::from_secs(30)))?;
+        let mut scratch = [0u8; 4096];
+        while let Ok(n) = self.read(&mut scratch) {
+            if n == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Read for NetStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            NetStream::Tcp(stream) => stream.read(buf),
+            NetStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for NetStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            NetStream::Tcp(stream) => stream.write(buf),
+            NetStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            NetStream::Tcp(stream) => stream.flush(),
+            NetStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// TLS settings shared by the control channel and every data channel
+struct TlsContext {
+    connector: TlsConnector,
+    domain: String,
+}
+
+impl TlsContext {
+    fn wrap(&self, stream: TcpStream) -> Result<NetStream> {
+        self.connector
+            .connect(&self.domain, stream)
+            .map(|s| NetStream::Tls(Box::new(s)))
+            .map_err(|e| FtpError::SecureError(e.to_string()))
+    }
+}
+
+/// Command stream to an FTP or FTPS server
+pub struct FtpClient {
+    reader: BufReader<NetStream>,
+    tls: Option<TlsContext>,
+    peer_ip: IpAddr,
+    features: Option<Vec<String>>,
+}
+
+impl std::fmt::Debug for FtpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FtpClient")
+            .field("secure", &self.is_secure())
+            .field("peer_ip", &self.peer_ip)
+            .finish()
+    }
+}
+
+impl FtpClient {
+    /// Connect to `host:port` and read the greeting, negotiating TLS per `tls_mode`
+    ///
+    /// `accept_invalid_certs` disables certificate and hostname validation; only
+    /// meant for servers with self-signed certificates.
+    pub fn connect(host: &str, port: u16, tls_mode: TlsMode, accept_invalid_certs: bool) -> Result<FtpClient> {
+        let tcp = TcpStream::connect((host, port)).map_err(FtpError::ConnectionError)?;
+        let peer_ip = tcp.peer_addr().map_err(FtpError::ConnectionError)?.ip();
+        // Also bounds the TLS handshake
+        tcp.set_read_timeout(Some(CONTROL_TIMEOUT)).map_err(FtpError::ConnectionError)?;
+        tcp.set_write_timeout(Some(CONTROL_TIMEOUT)).map_err(FtpError::ConnectionError)?;
+
+        let tls = if tls_mode == TlsMode::None {
+            None
+        } else {
+            let connector = TlsConnector::builder()
+                .danger_accept_invalid_certs(accept_invalid_certs)
+                .danger_accept_invalid_hostnames(accept_invalid_certs)
+                .build()
+                .map_err(|e| FtpError::SecureError(e.to_string()))?;
+            Some(TlsContext { connector, domain: host.to_string() })
+        };
+
+        let stream = match (&tls, tls_mode) {
+            (Some(ctx), TlsMode::Implicit) => ctx.wrap(tcp)?,
+            _ => NetStream::Tcp(tcp),
+        };
+
+        let mut client = FtpClient {
+            reader: BufReader::new(stream),
+            tls,
+            peer_ip,
+            features: None,
+        };
+        client.read_response(status::READY)?;
+
+        if tls_mode == TlsMode::Explicit {
+            client.write_str("AUTH TLS\r\n")?;
+            client.read_response(status::AUTH_OK)?;
+
+            let tcp = match client.reader.into_inner() {
+                NetStream::Tcp(tcp) => tcp,
+                NetStream::Tls(_) => unreachable!("control channel is plain before AUTH TLS"),
+            };
+            let ctx = client.tls.as_ref().expect("TLS context exists for explicit mode");
+            client.reader = BufReader::new(ctx.wrap(tcp)?);
+        }
+
+        Ok(client)
+    }
+
+    /// Whether the control channel (and therefore data channels) use TLS
+    pub fn is_secure(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Log in, then switch data channels to private protection on TLS connections
+    pub fn login(&mut self, user: &str, password: &str) -> Result<()> {
+        self.write_str(format!("USER {}\r\n", user))?;
+        let Line(code, _) = self.read_response_in(&[status::LOGGED_IN, status::NEED_PASSWORD])?;
+        if code == status::NEED_PASSWORD {
+            self.write_str(format!("PASS {}\r\n", password))?;
+            self.read_response(status::LOGGED_IN)?;
+        }
+
+        if self.is_secure() {
+            // RFC 4217: PBSZ must precede PROT; "P" encrypts every data connection
+            self.write_str("PBSZ 0\r\n")?;
+            self.read_response(status::COMMAND_OK)?;
+            self.write_str("PROT P\r\n")?;
+            self.read_response(status::COMMAND_OK)?;
+        }
+        Ok(())
+    }
+
+    /// Change the current directory to the path specified
+    pub fn cwd(&mut self, path: &str) -> Result<()> {
+        self.write_str(format!("CWD {}\r\n", path))?;
+        self.read_response(status::REQUESTED_FILE_ACTION_OK).map(|_| ())
+    }
+
+    /// Create a directory on the server
+    pub fn mkdir(&mut self, pathname: &str) -> Result<()> {
+        self.write_str(format!("MKD {}\r\n", pathname))?;
+        self.read_response(status::PATH_CREATED).map(|_| ())
+    }
+
+    /// Set the transfer type (`TYPE A` / `TYPE I`)
+    pub fn transfer_type(&mut self, file_type: FileType) -> Result<()> {
+        self.write_str(format!("TYPE {}\r\n", file_type.to_string()))?;
+        self.read_response(status::COMMAND_OK).map(|_| ())
+    }
+
+    /// Keep the session alive / check that it still is (NOOP)
+    pub fn noop(&mut self) -> Result<()> {
+        self.with_quick_reply(|client| {
+            client.write_str("NOOP\r\n")?;
+            client.read_response(status::COMMAND_OK).map(|_| ())
+        })
+    }
+
+    /// End the session
+    pub fn quit(&mut self) -> Result<()> {
+        self.with_quick_reply(|client| {
+            client.write_str("QUIT\r\n")?;
+            client.read_response(status::CLOSING).map(|_| ())
+        })
+    }
+
+    /// Run a command that a live server answers at once with the short timeout
+    fn with_quick_reply<T>(&mut self, command: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let stream = self.reader.get_ref();
+        stream.set_timeout(QUICK_REPLY_TIMEOUT).map_err(FtpError::ConnectionError)?;
+        let result = command(self);
+        self.reader.get_ref().set_timeout(CONTROL_TIMEOUT).map_err(FtpError::ConnectionError)?;
+        result
+    }
+
+    /// Rename a file on the server (RNFR/RNTO)
+    pub fn rename(&mut self, from_name: &str, to_name: &str) -> Result<()> {
+        self.write_str(format!("RNFR {}\r\n", from_name))?;
+        self.read_response(status::REQUEST_FILE_PENDING)?;
+        self.write_str(format!("RNTO {}\r\n", to_name))?;
+        self.read_response(status::REQUESTED_FILE_ACTION_OK).map(|_| ())
+    }
+
+    /// Copy a file on the server with `SITE CPFR`/`SITE CPTO` (ProFTPD mod_copy and others)
+    ///
+    /// Returns `false` when the server does not support copying.
+    pub fn site_copy(&mut self, from_name: &str, to_name: &str) -> Result<bool> {
+        self.write_str(format!("SITE CPFR {}\r\n", from_name))?;
+        match self.read_response(status::REQUEST_FILE_PENDING) {
+            Ok(_) => {}
+            Err(FtpError::InvalidResponse(_)) => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        self.write_str(format!("SITE CPTO {}\r\n", to_name))?;
+        match self.read_response(status::REQUESTED_FILE_ACTION_OK) {
+            Ok(_) => Ok(true),
+            Err(FtpError::InvalidResponse(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Delete a file on the server
+    pub fn rm(&mut self, filename: &str) -> Result<()> {
+        self.write_str(format!("DELE {}\r\n", filename))?;
+        self.read_response(status::REQUESTED_FILE_ACTION_OK).map(|_| ())
+    }
+
+    /// Store `r` on the server as `filename` (STOR)
+    pub fn put<R: Read>(&mut self, filename: &str, r: &mut R) -> Result<()> {
+        let addr = self.pasv()?;
+        self.store(addr, &format!("STOR {}\r\n", filename), r)
+    }
+
+    /// Continue an interrupted upload of `filename` at byte `offset`
+    ///
+    /// `r` must already be positioned at `offset`. Uses `REST` + `STOR`, and
+    /// falls back to `APPE` on servers that refuse `REST` in STOR context.
+    pub fn resume_put<R: Read>(&mut self, filename: &str, r: &mut R, offset: u64) -> Result<()> {
+        let addr = self.pasv()?;
+        self.write_str(format!("REST {}\r\n", offset))?;
+        let command = match self.read_response(status::REQUEST_FILE_PENDING) {
+            Ok(_) => format!("STOR {}\r\n", filename),
+            Err(FtpError::InvalidResponse(_)) => format!("APPE {}\r\n", filename),
+            Err(e) => return Err(e),
+        };
+        self.store(addr, &command, r)
+    }
+
+    /// Send a STOR/APPE command and stream `r` over the data connection
+    fn store<R: Read>(&mut self, addr: SocketAddr, command: &str, r: &mut R) -> Result<()> {
+        self.write_str(command)?;
+        let data_stream = self.open_data(addr)?;
+        self.read_response_in(&[status::ALREADY_OPEN, status::ABOUT_TO_SEND])?;
+
+        let mut writer = BufWriter::new(data_stream);
+        send_file(r, &mut writer, FtpError::ConnectionError)?;
+        let mut data_stream = writer.into_inner().map_err(|e| FtpError::ConnectionError(e.into_error()))?;
+        data_stream.finish().map_err(FtpError::ConnectionError)?;
+        drop(data_stream);
+
+        self.read_response_in(&[status::CLOSING_DATA_CONNECTION, status::REQUESTED_FILE_ACTION_OK])
+            .map(|_| ())
+    }
+
+    /// Extensions the server advertises in its FEAT reply (cached per session)
+    ///
+    /// Servers that do not implement FEAT report no features.
+    pub fn features(&mut self) -> Result<Vec<String>> {
+        if let Some(features) = &self.features {
+            return Ok(features.clone());
+        }
+
+        self.write_str("FEAT\r\n")?;
+        let features = match self.read_response(status::SYSTEM) {
+            // Feature lines are the indented ones between "211-" and "211 End"
+            Ok(Line(_, text)) => text
+                .lines()
+                .filter(|l| l.starts_with(' '))
+                .map(|l| l.trim().to_string())
+                .collect(),
+            Err(FtpError::InvalidResponse(_)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        self.features = Some(features.clone());
+        Ok(features)
+    }
+
+    /// Run a digest command (`HASH`, `XCRC`, `XMD5`, `XSHA256`) and return the reply text
+    pub fn digest(&mut self, command: &str, pathname: &str) -> Result<String> {
+        self.write_str(format!("{} {}\r\n", command, pathname))?;
+        let Line(_, content) = self.read_response_in(&[status::FILE, status::REQUESTED_FILE_ACTION_OK, status::COMMAND_OK])?;
+        Ok(content[4..].trim().to_string())
+    }
+
+    /// Retrieve a file fully into memory (RETR)
+    pub fn simple_retr(&mut self, filename: &str) -> Result<Cursor<Vec<u8>>> {
+        let mut data_stream = self.data_command(&format!("RETR {}\r\n", filename))?;
+        self.read_response_in(&[status::ABOUT_TO_SEND, status::ALREADY_OPEN])?;
+
+        let mut buffer = Vec::new();
+        data_stream.read_to_end(&mut buffer).map_err(FtpError::ConnectionError)?;
+        drop(data_stream);
+
+        self.read_response_in(&[status::CLOSING_DATA_CONNECTION, status::REQUESTED_FILE_ACTION_OK])
+            .map(|_| Cursor::new(buffer))
+    }
+
+    /// Detailed directory listing (LIST)
+    pub fn list(&mut self, pathname: Option<&str>) -> Result<Vec<String>> {
+        let command = pathname.map_or("LIST\r\n".to_string(), |path| format!("LIST {}\r\n", path));
+        let mut data_stream = self.data_command(&command)?;
+        self.read_response_in(&[status::ABOUT_TO_SEND, status::ALREADY_OPEN])?;
+
+        let mut raw = Vec::new();
+        data_stream.read_to_end(&mut raw).map_err(FtpError::ConnectionError)?;
+        drop(data_stream);
+
+        let lines = String::from_utf8_lossy(&raw)
+            .lines()
+            .map(|l| l.trim_end_matches('\r').to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+
+        self.read_response_in(&[status::CLOSING_DATA_CONNECTION, status::REQUESTED_FILE_ACTION_OK])
+            .map(|_| lines)
+    }
+
+    /// Size of `pathname` in bytes (SIZE), `None` if the reply has no number
+    pub fn size(&mut self, pathname: &str) -> Result<Option<usize>> {
+        self.write_str(format!("SIZE {}\r\n", pathname))?;
+        let Line(_, content) = self.read_response(status::FILE)?;
+        Ok(content[4..].trim().parse().ok())
+    }
+
+    /// Modification time of `pathname` in unix seconds (MDTM), `None` if the reply has no timestamp
+    pub fn mdtm(&mut self, pathname: &str) -> Result<Option<i64>> {
+        self.write_str(format!("MDTM {}\r\n", pathname))?;
+        let Line(_, content) = self.read_response(status::FILE)?;
+        // 213 YYYYMMDDHHMMSS[.sss], always UTC
+        let stamp = content[4..].trim();
+        Ok(chrono::NaiveDateTime::parse_from_str(stamp.get(0..14).unwrap_or(stamp), "%Y%m%d%H%M%S")
+            .ok()
+            .map(|time| time.and_utc().timestamp()))
+    }
+
+    /// Enter passive mode and return the data address to connect to
+    fn pasv(&mut self) -> Result<SocketAddr> {
+        self.write_str("PASV\r\n")?;
+        // 227 Entering Passive Mode (h1,h2,h3,h4,p1,p2).
+        let Line(_, line) = self.read_response(status::PASSIVE_MODE)?;
+
+        let invalid = || FtpError::InvalidResponse(format!("Invalid PASV response: {}", line.trim_end()));
+        let start = line.find('(').ok_or_else(invalid)?;
+        let end = line[start..].find(')').map(|i| start + i).ok_or_else(invalid)?;
+        let parts: Vec<u8> = line[start + 1..end]
+            .split(',')
+            .map(|p| p.trim().parse::<u8>())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| invalid())?;
+        if parts.len() != 6 {
+            return Err(invalid());
+        }
+
+        let ip = IpAddr::from([parts[0], parts[1], parts[2], parts[3]]);
+        let port = ((parts[4] as u16) << 8) | parts[5] as u16;
+
+        // Servers behind NAT often advertise 0.0.0.0 or their private address;
+        // the control connection's peer is always reachable
+        let ip = if ip.is_unspecified() || (ip.is_ipv4() && is_private(&ip) && !is_private(&self.peer_ip)) {
+            self.peer_ip
+        } else {
+            ip
+        };
+        Ok(SocketAddr::new(ip, port))
+    }
+
+    /// Send a command that transfers data over a separate (passive) connection
+    fn data_command(&mut self, cmd: &str) -> Result<NetStream> {
+        let addr = self.pasv()?;
+        self.write_str(cmd)?;
+        self.open_data(addr)
+    }
+
+    /// Connect to the passive data port, wrapping it in TLS when the session is secure
+    fn open_data(&self, addr: SocketAddr) -> Result<NetStream> {
+        let tcp = TcpStream::connect(addr).map_err(FtpError::ConnectionError)?;
+        tcp.set_read_timeout(Some(CONTROL_TIMEOUT)).map_err(FtpError::ConnectionError)?;
+        tcp.set_write_timeout(Some(CONTROL_TIMEOUT)).map_err(FtpError::ConnectionError)?;
+        match &self.tls {
+            // The usual cause is a server that requires TLS session reuse (see the module docs)
+            Some(ctx) => ctx.wrap(tcp).map_err(|e| match e {
+                FtpError::SecureError(message) => FtpError::SecureError(format!(
+                    "TLS handshake on the data connection failed (the server may require TLS session reuse, which is not supported): {}",
+                    message
+                )),
+                other => other,
+            }),
+            None => Ok(NetStream::Tcp(tcp)),
+        }
+    }
+
+    fn write_str<S: AsRef<str>>(&mut self, command: S) -> Result<()> {
+        let stream = self.reader.get_mut();
+        stream.write_all(command.as_ref().as_bytes())
+            .and_then(|_| stream.flush())
+            .map_err(FtpError::ConnectionError)
+    }
+
+    /// Read a (possibly multi-line) reply and check its code
+    pub fn read_response(&mut self, expected_code: u32) -> Result<Line> {
+        self.read_response_in(&[expected_code])
+    }
+
+    /// Read a (possibly multi-line) reply and check its code against `expected_code`
+    pub fn read_response_in(&mut self, expected_code: &[u32]) -> Result<Line> {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).map_err(FtpError::ConnectionError)?;
+
+        // A short line or a multibyte character up front is a garbled reply, not a reason to panic
+        let code_str = match line.get(0..3) {
+            Some(code_str) if line.len() >= 4 => code_str,
+            _ => return Err(FtpError::InvalidResponse("error: could not read reply code".to_owned())),
+        };
+
+        let code: u32 = code_str.parse()
+            .map_err(|err| FtpError::InvalidResponse(format!("error: could not parse reply code: {}", err)))?;
+
+        // Multi-line replies end with "<code> " (code followed by a space); keep every line
+        let terminator = format!("{} ", code_str);
+        let mut last_start = 0;
+        while line.get(last_start..last_start + 4) != Some(terminator.as_str()) {
+            last_start = line.len();
+            let read = self.reader.read_line(&mut line).map_err(FtpError::ConnectionError)?;
+            if read == 0 {
+                return Err(FtpError::InvalidResponse("error: connection closed during multi-line reply".to_owned()));
+            }
+        }
+
+        if expected_code.contains(&code) {
+            Ok(Line(code, line))
+        } else {
+            Err(FtpError::InvalidResponse(format!("Expected code {:?}, got response: {}", expected_code, line)))
+        }
+    }
+}
+
+fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_loopback() || v4.is_link_local(),
+        IpAddr::V6(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Connect to a one-shot server that greets, then answers the first command with `reply`
+    fn client_with_reply(reply: &'static [u8]) -> FtpClient {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"220 ready\r\n").unwrap();
+            let mut command = String::new();
+            BufReader::new(stream.try_clone().unwrap()).read_line(&mut command).unwrap();
+            stream.write_all(reply).unwrap();
+        });
+        FtpClient::connect("127.0.0.1", port, TlsMode::None, false).unwrap()
+    }
+
+    #[test]
+    fn garbled_reply_codes_are_errors_not_panics() {
+        for reply in [&b"\xc3\xa9\xc3\xa9 hello\r\n"[..], b"2\xc3\xa9 ok\r\n", b"20\r\n", b"abc nope\r\n"] {
+            let mut client = client_with_reply(reply);
+            assert!(matches!(client.noop(), Err(FtpError::InvalidResponse(_))), "{:?}", reply);
+        }
+    }
+
+    #[test]
+    fn reads_multi_line_replies() {
+        let mut client = client_with_reply(b"211-Features:\r\n MDTM\r\n SIZE\r\n211 End\r\n");
+        assert_eq!(client.features().unwrap(), vec!["MDTM".to_string(), "SIZE".to_string()]);
+    }
+
+    #[test]
+    fn reads_mdtm_as_utc() {
+        let mut client = client_with_reply(b"213 20261016211400.123\r\n");
+        assert_eq!(client.mdtm("a.jpg").unwrap(), Some(1_792_185_240));
+        let mut client = client_with_reply(b"213 yesterday\r\n");
+        assert_eq!(client.mdtm("a.jpg").unwrap(), None);
+    }
+}
@@ -1,15 +1,26 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Instant, Duration};
 use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}};
 use rayon::prelude::*;
 use crossbeam::channel;
 use log::{info, warn, error, debug};
-use chrono::Utc;
+use chrono::{Local, Utc};
 use colored::*;
-use xxhash_rust::xxh3::xxh3_64;
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
 use crate::db;
+use crate::errors::{self, ErrorKind, TransferError};
+use crate::disposition::{Disposition, DispositionPolicy, PostUploadAction};
+use crate::retention::{self, RetentionPolicy, SentFile};
+use crate::schedule::{Schedule, ScheduleState};
+use crate::filters::{FileFilter, IgnoreStack};
+use crate::ftp_client::{FtpClient, TlsMode};
+use crate::pool::ConnectionPool;
+use crate::sftp::{SftpOptions, SftpTransport};
+use crate::throttle::{RateLimiter, Throttle};
+use crate::transport::{ChecksumAlgorithm, Protocol, RemoteEntry, Transport};
+use crate::watcher::DirectoryWatcher;
 
 #[derive(Debug, Deserialize, Clone)]
 struct FTPConfig {
@@ -27,6 +38,149 @@ struct FTPConfig {
     pub config_id: String, // Changed from u32 to String to use stable UUID instead of hash
     pub config_name: String,
     pub session_id: String, // Added: Session ID from Swift
+    #[serde(default)]
+    pub ftps_mode: TlsMode, // "none", "explicit" (AUTH TLS) or "implicit" (TLS on connect, usually port 990)
+    #[serde(default)]
+    pub ftps_accept_invalid_certs: bool, // Skip certificate validation (self-signed servers only)
+    #[serde(default)]
+    pub protocol: Protocol, // "ftp" (default, see ftps_mode) or "sftp"
+    #[serde(default)]
+    pub sftp_private_key_path: Option<String>, // Use public-key auth instead of password when set
+    #[serde(default)]
+    pub sftp_private_key_passphrase: Option<String>,
+    #[serde(default)]
+    pub sftp_known_hosts_path: Option<String>, // Defaults to ~/.ssh/known_hosts
+    #[serde(default)]
+    pub sftp_accept_new_host_keys: bool, // Trust-on-first-use for hosts missing from known_hosts
+    #[serde(default = "default_upload_buffer_size")]
+    pub upload_buffer_size: usize, // Bytes read from disk per chunk while streaming an upload
+    #[serde(default = "default_resume_uploads")]
+    pub resume_uploads: bool, // Continue interrupted uploads with REST/APPE instead of starting over; keeps partial temp files for that
+    #[serde(default)]
+    pub atomic_uploads: bool, // Upload under a temporary name, then RNFR/RNTO to the final name
+    #[serde(default = "default_temp_upload_prefix")]
+    pub temp_upload_prefix: String, // Temporary name is prefix + filename + suffix, e.g. ".photo.jpg.part"
+    #[serde(default = "default_temp_upload_suffix")]
+    pub temp_upload_suffix: String,
+    #[serde(default = "default_verify_uploads")]
+    pub verify_uploads: bool, // Compare remote SIZE to the local length before a file counts as uploaded (ASCII: only that it is there)
+    #[serde(default = "default_verify_uploads")]
+    pub verify_checksums: bool, // Also compare HASH/XSHA256/XMD5/XCRC digests when the server supports one
+    #[serde(default = "default_reuse_connections")]
+    pub reuse_connections: bool, // Keep logged-in sessions in a pool instead of reconnecting for every file
+    #[serde(default)]
+    pub watch_mode: bool, // React to filesystem events instead of rescanning every sync_interval
+    #[serde(default = "default_full_rescan_interval")]
+    pub full_rescan_interval: f64, // Watch mode safety-net full scan period (seconds)
+    #[serde(default)]
+    pub include: Vec<String>, // Globs ("**/*.cr2") or "re:<regex>"; empty = everything. "!pattern" excludes
+    #[serde(default)]
+    pub exclude: Vec<String>, // Globs or "re:<regex>" for files never to upload (".DS_Store", "*.swp")
+    #[serde(default)]
+    pub min_file_size: Option<u64>, // bytes
+    #[serde(default)]
+    pub max_file_size: Option<u64>, // bytes
+    #[serde(skip)]
+    pub file_filter: FileFilter, // Compiled from include/exclude/size limits after loading
+    #[serde(default)]
+    pub post_upload_action: PostUploadAction, // "move_to_sent" (default), "archive", "delete", "rename" or "leave"/"keep" (mirror)
+    #[serde(default)]
+    pub archive_path: Option<String>, // Destination for "archive"; must be outside local_source_path
+    #[serde(default = "default_rename_suffix")]
+    pub rename_suffix: String, // Appended by "rename", e.g. "photo.jpg" -> "photo.jpg.sent"
+    #[serde(default)]
+    pub sent_date_folders: bool, // Move into a YYYY-MM-DD subfolder of FTPU-Sent / the archive
+    #[serde(skip)]
+    pub disposition: DispositionPolicy, // Validated from the post-upload settings after loading
+    #[serde(default)]
+    pub change_detection: ChangeDetection, // Keep mode: "metadata" (size + mtime) or "content" (xxh3 of the bytes)
+    #[serde(default)]
+    pub dedupe: DedupeMode, // "off", "skip" or "server_copy" for files whose bytes were already uploaded
+    #[serde(default)]
+    pub sent_max_age_days: Option<f64>, // Prune sent files older than this
+    #[serde(default)]
+    pub sent_max_total_size: Option<u64>, // bytes; prune oldest sent files beyond this
+    #[serde(default)]
+    pub sent_max_files: Option<usize>, // Prune oldest sent files beyond this count
+    #[serde(default = "default_retry_max_attempts")]
+    pub retry_max_attempts: u32, // Stop retrying a failing file (dead-letter it) after this many attempts; 0 = never
+    #[serde(default = "default_retry_backoff_secs")]
+    pub retry_backoff_secs: u64, // Wait before retrying a failed file; doubles per failure, up to an hour
+    #[serde(default)]
+    pub max_upload_rate: Option<u64>, // bytes/sec shared by all workers of this session; None or 0 = unlimited
+    #[serde(skip)]
+    pub throttle: Throttle, // Session and global rate limiters every upload reads through
+    #[serde(default)]
+    pub schedule: Schedule, // Active windows and blackouts in local time; empty = upload at all hours
+    #[serde(skip)]
+    pub pause: Arc<PauseControl>, // Set by rust_ftp_pause / rust_ftp_resume
+}
+
+// 1 MiB per worker keeps memory bounded regardless of file size
+fn default_upload_buffer_size() -> usize {
+    1024 * 1024
+}
+
+fn default_resume_uploads() -> bool {
+    true
+}
+
+fn default_full_rescan_interval() -> f64 {
+    300.0
+}
+
+fn default_reuse_connections() -> bool {
+    true
+}
+
+fn default_verify_uploads() -> bool {
+    true
+}
+
+fn default_temp_upload_prefix() -> String {
+    ".".to_string()
+}
+
+fn default_temp_upload_suffix() -> String {
+    ".part".to_string()
+}
+
+fn default_rename_suffix() -> String {
+    ".sent".to_string()
+}
+
+fn default_retry_max_attempts() -> u32 {
+    5
+}
+
+fn default_retry_backoff_secs() -> u64 {
+    30
+}
+
+// How keep mode decides whether a file changed since it was uploaded
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum ChangeDetection {
+    // Size and modification time
+    #[default]
+    Metadata,
+    // xxh3 over the file contents; metadata (size, mtime, inode and ctime) is still checked first so
+    // unchanged files are not re-read. ctime catches in-place edits that keep size and mtime, at the
+    // cost of a re-read after metadata-only changes (chmod, xattrs, rename) - the bytes then still
+    // match, so the file is not uploaded again
+    Content,
+}
+
+// What to do with a file whose exact bytes were already uploaded for this config
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum DedupeMode {
+    #[default]
+    Off,
+    // Don't upload it again
+    Skip,
+    // Copy (FTP SITE CPFR/CPTO) or link (SFTP) the earlier upload to the new path; uploads normally if unsupported
+    ServerCopy,
 }
 
 #[derive(Debug, Serialize)]
@@ -37,6 +191,7 @@ struct FTPStatus {
     pub progress: f64,
     pub timestamp: u64,
     pub file_size: Option<u64>, // bytes
+    pub bytes_transferred: Option<u64>, // bytes sent so far while uploading
     pub upload_speed_mbps: Option<f64>, // MB/s for completed uploads
     pub upload_time_secs: Option<f64>, // seconds for completed uploads
 }
@@ -182,6 +337,7 @@ struct StatusUpdate {
     pub progress: f64,
     pub thread_id: u64,
     pub file_size: Option<u64>, // bytes
+    pub bytes_transferred: Option<u64>, // bytes sent so far while uploading
 }
 
 // Helper function to compute a stable u32 hash from UUID string (for FFI callbacks)
@@ -199,12 +355,84 @@ fn config_id_to_hash(config_id: &str) -> u32 {
     (hash & 0xFFFFFFFF) as u32
 }
 
+// Consecutive failed connection attempts (from any worker) that open the circuit
+const CIRCUIT_FAILURE_THRESHOLD: usize = 5;
+// How long an open circuit blocks connections before one probe is let through
+const CIRCUIT_COOLDOWN: Duration = Duration::from_secs(30);
+
+// Circuit breaker over connection attempts to the server.
+// Closed: connect normally. Open: the server looks down - nobody connects until the cooldown
+// has passed. Half-open: a single probe connection decides whether to close or re-open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    state: CircuitState,
+    consecutive_failures: usize,
+    changed_at: Instant, // when the circuit opened, or when the current probe started
+}
+
+// Whether a connection attempt may go ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectPermit {
+    Allowed,
+    Probe,
+    Blocked(Duration), // time left until the next probe
+}
+
+// State change caused by a connection outcome, reported once to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitTransition {
+    Unchanged,
+    Opened,
+    Recovered,
+}
+
+/// Pause requests from the FFI (rust_ftp_pause / rust_ftp_resume), shared by the main loop and the workers
+#[derive(Debug, Default)]
+pub struct PauseControl {
+    paused: AtomicBool,
+    abort_transfers: AtomicBool, // cut off uploads in progress instead of letting them finish
+}
+
+impl PauseControl {
+    pub fn pause(&self, abort_transfers: bool) {
+        self.abort_transfers.store(abort_transfers, Ordering::SeqCst);
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.abort_transfers.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn is_aborting(&self) -> bool {
+        self.is_paused() && self.abort_transfers.load(Ordering::SeqCst)
+    }
+}
+
 // Connection error analysis and retry management
 #[derive(Debug)]
 struct ConnectionManager {
     failed_attempts: AtomicUsize,
     last_failure_time: Arc<Mutex<Option<Instant>>>,
     server_limit_detected: AtomicBool,
+    rejections: AtomicUsize, // total server rejections (421/530/...) - never reset, used by auto-tune
+    pool: ConnectionPool, // logged-in sessions reused across files and iterations
+    circuit: Mutex<CircuitBreaker>,
+    session_limit: AtomicUsize, // most concurrent sessions the server accepts (learned or remembered); 0 = unknown
+    highest_ok_sessions: AtomicUsize, // most sessions that were open at once without a rejection
+    limit_changed: AtomicBool, // learned something since the limit was last saved
+    window_connections: AtomicUsize, // upload_aggressiveness of the active schedule window; 0 = use the config's
 }
 
 impl ConnectionManager {
@@ -213,46 +441,151 @@ impl ConnectionManager {
             failed_attempts: AtomicUsize::new(0),
             last_failure_time: Arc::new(Mutex::new(None)),
             server_limit_detected: AtomicBool::new(false),
+            rejections: AtomicUsize::new(0),
+            pool: ConnectionPool::new(),
+            circuit: Mutex::new(CircuitBreaker {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                changed_at: Instant::now(),
+            }),
+            session_limit: AtomicUsize::new(0),
+            highest_ok_sessions: AtomicUsize::new(0),
+            limit_changed: AtomicBool::new(false),
+            window_connections: AtomicUsize::new(0),
         }
     }
-    
-    fn is_server_rejection_error(error_msg: &str) -> bool {
-        let error_lower = error_msg.to_lowercase();
-        error_lower.contains("too many connections") ||
-        error_lower.contains("connection limit") ||
-        error_lower.contains("max connections") ||
-        error_lower.contains("server full") ||
-        error_lower.contains("connection refused") ||
-        error_lower.contains("service unavailable") ||
-        error_lower.contains("421") || // FTP 421 Service not available
-        error_lower.contains("530") || // FTP 530 Not logged in
-        error_lower.contains("exceeded") ||
-        error_lower.contains("busy")
+
+    // Start from a limit remembered from an earlier run
+    fn seed_session_limit(&self, limit: &db::ServerLimit) {
+        self.session_limit.store(limit.max_sessions as usize, Ordering::SeqCst);
+        self.highest_ok_sessions.store(limit.highest_ok as usize, Ordering::SeqCst);
     }
-    
-    fn is_network_error(error_msg: &str) -> bool {
-        let error_lower = error_msg.to_lowercase();
-        error_lower.contains("timeout") ||
-        error_lower.contains("connection reset") ||
-        error_lower.contains("network unreachable") ||
-        error_lower.contains("connection lost") ||
-        error_lower.contains("broken pipe") ||
-        error_lower.contains("connection aborted")
+
+    // A new session logged in while `open_sessions` sessions (counting it) were open
+    fn record_session_ok(&self, open_sessions: usize) {
+        if self.highest_ok_sessions.fetch_max(open_sessions, Ordering::SeqCst) < open_sessions {
+            self.limit_changed.store(true, Ordering::SeqCst);
+        }
+        // The server evidently allows more than we thought (its limit was raised, or other clients had left)
+        let limit = self.session_limit.load(Ordering::SeqCst);
+        if limit != 0 && open_sessions > limit {
+            self.session_limit.store(open_sessions, Ordering::SeqCst);
+            self.limit_changed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // The server refused session number `attempted` over its session limit (421, or 530 while others are logged in)
+    fn record_session_rejected(&self, attempted: usize) {
+        // A lone session being refused says nothing about concurrency
+        if attempted < 2 {
+            return;
+        }
+        let limit = attempted - 1;
+        let lowered = self.session_limit.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            (current == 0 || limit < current).then_some(limit)
+        });
+        if lowered.is_ok() {
+            self.limit_changed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn session_limit(&self) -> Option<usize> {
+        Some(self.session_limit.load(Ordering::SeqCst)).filter(|&limit| limit > 0)
+    }
+
+    // Upload workers that fit within the session limit, leaving room for the scan connection
+    fn worker_limit(&self) -> Option<usize> {
+        self.session_limit().map(|limit| limit.saturating_sub(1).max(1))
+    }
+
+    // Connection count set by the active schedule window, replacing upload_aggressiveness
+    fn set_window_connections(&self, connections: Option<u32>) {
+        self.window_connections.store(connections.unwrap_or(0) as usize, Ordering::SeqCst);
+    }
+
+    fn window_connections(&self) -> Option<usize> {
+        Some(self.window_connections.load(Ordering::SeqCst)).filter(|&connections| connections > 0)
+    }
+
+    // The learned limit, if it changed since the last call
+    fn take_limit_update(&self) -> Option<db::ServerLimit> {
+        if !self.limit_changed.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+        let max_sessions = self.session_limit()?;
+        Some(db::ServerLimit {
+            max_sessions: max_sessions as u32,
+            highest_ok: self.highest_ok_sessions.load(Ordering::SeqCst) as u32,
+            learned_at: Utc::now().timestamp(),
+        })
+    }
+
+    // Ask the circuit breaker before opening a new connection
+    fn begin_connect(&self) -> ConnectPermit {
+        let mut circuit = self.circuit.lock().unwrap();
+        let elapsed = circuit.changed_at.elapsed();
+        match circuit.state {
+            CircuitState::Closed => ConnectPermit::Allowed,
+            // A probe that never reported back (e.g. its worker stopped) is replaced after a cooldown
+            CircuitState::Open | CircuitState::HalfOpen if elapsed >= CIRCUIT_COOLDOWN => {
+                circuit.state = CircuitState::HalfOpen;
+                circuit.changed_at = Instant::now();
+                ConnectPermit::Probe
+            }
+            CircuitState::Open => ConnectPermit::Blocked(CIRCUIT_COOLDOWN - elapsed),
+            CircuitState::HalfOpen => ConnectPermit::Blocked(CIRCUIT_COOLDOWN - elapsed),
+        }
+    }
+
+    // A connection or login failed because the server did not answer or refused us
+    fn connect_failed(&self) -> CircuitTransition {
+        let mut circuit = self.circuit.lock().unwrap();
+        circuit.consecutive_failures += 1;
+        match circuit.state {
+            CircuitState::HalfOpen => {
+                // Probe failed - stay unavailable for another cooldown (already reported)
+                circuit.state = CircuitState::Open;
+                circuit.changed_at = Instant::now();
+                CircuitTransition::Unchanged
+            }
+            CircuitState::Closed if circuit.consecutive_failures >= CIRCUIT_FAILURE_THRESHOLD => {
+                circuit.state = CircuitState::Open;
+                circuit.changed_at = Instant::now();
+                CircuitTransition::Opened
+            }
+            _ => CircuitTransition::Unchanged,
+        }
+    }
+
+    // A new connection logged in
+    fn connect_succeeded(&self) -> CircuitTransition {
+        let mut circuit = self.circuit.lock().unwrap();
+        circuit.consecutive_failures = 0;
+        if circuit.state == CircuitState::Closed {
+            return CircuitTransition::Unchanged;
+        }
+        circuit.state = CircuitState::Closed;
+        CircuitTransition::Recovered
+    }
+
+    fn is_circuit_open(&self) -> bool {
+        self.circuit.lock().unwrap().state != CircuitState::Closed
     }
     
-    fn record_failure(&self, error_msg: &str, sync_interval: f64) -> (bool, Duration) {
+    fn record_failure(&self, kind: ErrorKind, error_msg: &str, sync_interval: f64) -> (bool, Duration) {
         let attempts = self.failed_attempts.fetch_add(1, Ordering::SeqCst) + 1;
         *self.last_failure_time.lock().unwrap() = Some(Instant::now());
 
-        let is_server_rejection = Self::is_server_rejection_error(error_msg);
-        let is_network_issue = Self::is_network_error(error_msg);
+        let is_server_rejection = kind.is_server_rejection();
+        let is_network_issue = kind.is_network();
 
         // DEBUG: Log what type of error we detected
-        println!("🔍 CONNECTION DEBUG: Error='{}' ServerRejection={} NetworkIssue={} Attempt={} SyncInterval={}s",
-            error_msg, is_server_rejection, is_network_issue, attempts, sync_interval);
+        println!("🔍 CONNECTION DEBUG: Error='{}' Kind={} ServerRejection={} NetworkIssue={} Attempt={} SyncInterval={}s",
+            error_msg, kind, is_server_rejection, is_network_issue, attempts, sync_interval);
 
         if is_server_rejection {
             self.server_limit_detected.store(true, Ordering::SeqCst);
+            self.rejections.fetch_add(1, Ordering::SeqCst);
         }
 
         // For very fast sync intervals (< 5s), use much shorter retry delays to avoid blocking
@@ -318,6 +651,252 @@ impl ConnectionManager {
     fn get_failure_count(&self) -> usize {
         self.failed_attempts.load(Ordering::SeqCst)
     }
+
+    fn get_rejection_count(&self) -> usize {
+        self.rejections.load(Ordering::SeqCst)
+    }
+}
+
+// Highest connection count the UI offers ("Extreme Max")
+const MAX_AUTO_TUNE_CONNECTIONS: usize = 200;
+
+// Remembered session limits are re-learned after this long, in case the server changed
+const SERVER_LIMIT_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// Session limits apply per account on a server
+fn server_limit_key(config: &FTPConfig) -> String {
+    format!("{}@{}:{}", config.username, config.server_address, config.port)
+}
+
+// Hill-climbing controller for the parallel connection count, adjusted between iterations.
+// Grows while aggregate throughput keeps rising, turns around when it falls, and halves
+// (remembering the limit) whenever the server rejects connections.
+#[derive(Debug)]
+struct AutoTuner {
+    current: usize,
+    ceiling: usize,              // highest count not known to trigger rejections
+    server_limit: usize,         // upload workers the learned session limit allows
+    growing: bool,               // direction of the last adjustment
+    last_throughput: Option<f64>, // aggregate MB/s measured in the previous iteration
+    files_seen: usize,           // session files already evaluated
+    bytes_seen: usize,           // session bytes already evaluated
+    upload_secs: f64,            // wall time of the upload phases since the last evaluation
+    rejections_seen: usize,      // ConnectionManager rejections already acted on
+}
+
+impl AutoTuner {
+    fn new(initial: usize) -> Self {
+        AutoTuner {
+            current: initial.clamp(1, MAX_AUTO_TUNE_CONNECTIONS),
+            ceiling: MAX_AUTO_TUNE_CONNECTIONS,
+            server_limit: MAX_AUTO_TUNE_CONNECTIONS,
+            growing: true,
+            last_throughput: None,
+            files_seen: 0,
+            bytes_seen: 0,
+            upload_secs: 0.0,
+            rejections_seen: 0,
+        }
+    }
+
+    // Never go above what the server is known to accept; called every iteration with the current
+    // worker limit, so the count can grow again once the server accepts more sessions
+    fn cap(&mut self, max_workers: Option<usize>) {
+        let limit = max_workers.unwrap_or(MAX_AUTO_TUNE_CONNECTIONS).clamp(1, MAX_AUTO_TUNE_CONNECTIONS);
+        if limit > self.server_limit {
+            // Rejections seen under the old limit no longer apply
+            self.ceiling = MAX_AUTO_TUNE_CONNECTIONS;
+        }
+        self.server_limit = limit;
+        self.current = self.current.min(self.max_connections());
+    }
+
+    fn max_connections(&self) -> usize {
+        self.ceiling.min(self.server_limit)
+    }
+
+    // An upload phase ran for `elapsed`, with all of its connections in parallel
+    fn upload_phase_finished(&mut self, elapsed: Duration) {
+        self.upload_secs += elapsed.as_secs_f64();
+    }
+
+    // Pick the connection count for the next iteration from what the last one measured.
+    // Returns the count and a short reason for logging.
+    fn next_connection_count(&mut self, session_state: &Mutex<SessionState>, connection_manager: &ConnectionManager) -> (usize, String) {
+        let rejections = connection_manager.get_rejection_count();
+        let new_rejections = rejections.saturating_sub(self.rejections_seen);
+        self.rejections_seen = rejections;
+
+        // Aggregate throughput: everything uploaded over the wall time of the upload phase
+        let (new_files, new_bytes) = match session_state.lock() {
+            Ok(state) => {
+                let new = (state.total_files.saturating_sub(self.files_seen), state.total_bytes.saturating_sub(self.bytes_seen));
+                self.files_seen = state.total_files;
+                self.bytes_seen = state.total_bytes;
+                new
+            }
+            Err(_) => (0, 0),
+        };
+        let upload_secs = std::mem::take(&mut self.upload_secs);
+
+        if new_rejections > 0 {
+            // The server is telling us we are over its limit
+            self.ceiling = self.current.saturating_sub(1).max(1);
+            self.current = (self.current / 2).max(1);
+            self.growing = true; // probe back up, but never past the ceiling
+            self.last_throughput = None;
+            return (self.current, format!("{} server rejection(s), backing off", new_rejections));
+        }
+
+        // A couple of small files says little about the link
+        if new_files < 2 || upload_secs <= 0.0 {
+            return (self.current, "not enough transfers to measure".to_string());
+        }
+
+        let throughput = new_bytes as f64 / 1024.0 / 1024.0 / upload_secs;
+        let step = (self.current / 4).max(1);
+
+        let reason = match self.last_throughput {
+            Some(previous) if throughput < previous * 0.95 => {
+                // Last move hurt - reverse it
+                self.growing = !self.growing;
+                format!("throughput fell {:.2} → {:.2} MB/s", previous, throughput)
+            }
+            Some(previous) if throughput <= previous * 1.05 => {
+                self.last_throughput = Some(throughput);
+                return (self.current, format!("throughput steady at {:.2} MB/s", throughput));
+            }
+            Some(previous) => format!("throughput rose {:.2} → {:.2} MB/s", previous, throughput),
+            None => format!("measured {:.2} MB/s", throughput),
+        };
+        self.last_throughput = Some(throughput);
+
+        self.current = if self.growing {
+            (self.current + step).min(self.max_connections())
+        } else {
+            self.current.saturating_sub(step).max(1)
+        };
+        (self.current, reason)
+    }
+}
+
+// Seed the connection manager and auto-tuner with the session limit remembered for this server,
+// unless it is older than SERVER_LIMIT_MAX_AGE
+fn restore_session_limit(config: &FTPConfig, connection_manager: &ConnectionManager, auto_tuner: &mut AutoTuner) {
+    match db::load_server_limit(&config.config_id, &server_limit_key(config)) {
+        Ok(Some(limit)) if Utc::now().timestamp() - limit.learned_at <= SERVER_LIMIT_MAX_AGE.as_secs() as i64 => {
+            connection_manager.seed_session_limit(&limit);
+            let workers = connection_manager.worker_limit().unwrap_or(1);
+            auto_tuner.cap(Some(workers));
+            config_log(config, &format!("{} {} accepts {} concurrent sessions (learned earlier) - using at most {} upload connections",
+                "📏".blue(), config.server_address, limit.max_sessions, workers));
+        }
+        Ok(Some(_)) => config_log(config, &format!("{} Remembered session limit for {} is outdated, learning it again", "📏".blue(), config.server_address)),
+        Ok(None) => {}
+        Err(e) => config_log(config, &format!("⚠️ Failed to load the server's session limit: {}", e)),
+    }
+}
+
+// Hold the main loop while the schedule is closed, reporting "Paused by schedule" (or "Paused"
+// while the session is also paused through the FFI).
+// Returns the window that applies once it opens, or None if the session is stopped meanwhile.
+fn wait_for_schedule(
+    config: &FTPConfig,
+    status_file: &str,
+    shutdown_flag: &AtomicBool,
+    shutdown_file: &str,
+    connection_manager: &ConnectionManager,
+) -> Option<Option<usize>> {
+    let mut paused = false;
+    loop {
+        match config.schedule.state_at(Local::now().naive_local()) {
+            ScheduleState::Open(window) => {
+                if paused {
+                    config_log(config, &format!("{} Schedule window open, resuming uploads", "▶️".green()));
+                    let _ = send_notification(config, "info", "▶️ Schedule window open, resuming uploads", None, None);
+                }
+                return Some(window);
+            }
+            ScheduleState::Paused { blackout, until } if !paused => {
+                paused = true;
+                let reason = if blackout { "blackout period" } else { "outside upload windows" };
+                let resumes = until.map_or("no upcoming window".to_string(), |at| format!("until {}", at.format("%a %H:%M")));
+                config_log(config, &format!("{} Paused by schedule ({}) {}", "🗓️".yellow(), reason, resumes));
+                let _ = send_notification(config, "info", &format!("🗓️ Paused by schedule ({}) {}", reason, resumes), None, None);
+                let _ = send_status(status_file, config, "Paused by schedule", &format!("{} {}", reason, resumes), 0.0, None);
+                // Idle connections would only time out on the server
+                connection_manager.pool.close_all();
+            }
+            ScheduleState::Paused { .. } => {}
+        }
+
+        // Re-check the schedule every second, shutdown and pause every 100ms
+        for _ in 0..10 {
+            if shutdown_flag.load(Ordering::SeqCst) || fs::metadata(shutdown_file).is_ok() {
+                return None;
+            }
+            if config.pause.is_paused() {
+                if !wait_while_paused(config, status_file, shutdown_flag, shutdown_file, connection_manager) {
+                    return None;
+                }
+                // Report the schedule again if it is still closed
+                paused = false;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+// Hold the main loop while the session is paused through the FFI, reporting "Paused".
+// Returns false if the session is stopped meanwhile.
+fn wait_while_paused(
+    config: &FTPConfig,
+    status_file: &str,
+    shutdown_flag: &AtomicBool,
+    shutdown_file: &str,
+    connection_manager: &ConnectionManager,
+) -> bool {
+    config_log(config, &format!("{} Session paused", "⏸️".yellow()));
+    let _ = send_notification(config, "info", "⏸️ Session paused", None, None);
+    let _ = send_status(status_file, config, "Paused", "", 0.0, None);
+    // Idle connections would only time out on the server
+    connection_manager.pool.close_all();
+
+    while config.pause.is_paused() {
+        if shutdown_flag.load(Ordering::SeqCst) || fs::metadata(shutdown_file).is_ok() {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    config_log(config, &format!("{} Session resumed", "▶️".green()));
+    let _ = send_notification(config, "info", "▶️ Session resumed", None, None);
+    let _ = send_status(status_file, config, "Resumed", "", 0.0, None);
+    true
+}
+
+// Apply the bandwidth cap and connection count of the schedule window that just became active
+fn apply_schedule_window(config: &FTPConfig, connection_manager: &ConnectionManager, window: Option<usize>) {
+    let (rate, connections) = match window.and_then(|index| config.schedule.windows.get(index)) {
+        Some(window) => (window.max_upload_rate.or(config.max_upload_rate), window.upload_aggressiveness),
+        None => (config.max_upload_rate, None),
+    };
+    // A limit set with rust_ftp_set_bandwidth_limit takes precedence over the window's
+    config.throttle.session.set_rate(rate.unwrap_or(0));
+    connection_manager.set_window_connections(connections);
+    if let Some(index) = window {
+        let bandwidth = match rate {
+            _ if config.throttle.session.is_overridden() => match config.throttle.session.rate() {
+                0 => "unlimited bandwidth (set by the app)".to_string(),
+                rate => format!("{} bytes/sec (set by the app)", rate),
+            },
+            Some(rate) if rate > 0 => format!("{} bytes/sec", rate),
+            _ => "unlimited bandwidth".to_string(),
+        };
+        config_log(config, &format!("{} Schedule window {} active: {}, {} parallel connections",
+            "🗓️".blue(), index + 1, bandwidth, connections.unwrap_or(config.upload_aggressiveness)));
+    }
 }
 
 // Helper function to prefix all output with config name
@@ -325,33 +904,40 @@ fn config_log(config: &FTPConfig, message: &str) {
     println!("[{}] {}", config.config_name, message);
 }
 
+// Open a connection to the server using the configured protocol
+// FTPS: explicit mode upgrades with AUTH TLS here; PBSZ/PROT P follow in login()
+// SFTP: the host key is verified here; authentication happens in login()
+fn connect_transport(config: &FTPConfig) -> ftp::types::Result<Box<dyn Transport>> {
+    match config.protocol {
+        Protocol::Ftp => {
+            let client = FtpClient::connect(&config.server_address, config.port, config.ftps_mode, config.ftps_accept_invalid_certs)?;
+            Ok(Box::new(client))
+        }
+        Protocol::Sftp => {
+            let options = SftpOptions {
+                private_key_path: config.sftp_private_key_path.as_ref().map(PathBuf::from),
+                private_key_passphrase: config.sftp_private_key_passphrase.clone(),
+                known_hosts_path: config.sftp_known_hosts_path.as_ref().map(PathBuf::from),
+                accept_new_host_keys: config.sftp_accept_new_host_keys,
+            };
+            let transport = SftpTransport::connect(&config.server_address, config.port, options)?;
+            Ok(Box::new(transport))
+        }
+    }
+}
+
 // Read _monitored.json file from remote directory (Phase 1: Read-Only)
 // Returns None if file doesn't exist or can't be read
 // This function checks the file listing first to see if _monitored.json exists
 // before attempting to retrieve it, avoiding unnecessary connection attempts
 // NOTE: Using underscore prefix instead of dot so it appears in all FTP server listings
-fn read_monitor_file(ftp: &mut ftp::FtpStream, remote_dir: &str, file_listing: &[String]) -> Option<MonitorFile> {
+fn read_monitor_file(ftp: &mut dyn Transport, remote_dir: &str, file_listing: &[RemoteEntry]) -> Option<MonitorFile> {
     let monitor_filename = "_monitored.json";
 
     println!("🔍 DEBUG: Looking for {} in directory listing of {}", monitor_filename, remote_dir);
 
     // First, check if _monitored.json appears in the file listing
-    let found_in_listing = file_listing.iter().any(|entry| {
-        // Extract filename from various FTP listing formats
-        let trimmed = entry.trim();
-
-        // Check if this entry is for .monitored.json
-        // Handle both simple listings (just filename) and detailed listings (permissions, size, etc.)
-        if trimmed.ends_with(monitor_filename) {
-            true
-        } else if trimmed == monitor_filename {
-            true
-        } else {
-            // For detailed listings, filename is usually the last part
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            parts.last().map(|&name| name == monitor_filename).unwrap_or(false)
-        }
-    });
+    let found_in_listing = file_listing.iter().any(|entry| !entry.is_dir && entry.name == monitor_filename);
 
     if !found_in_listing {
         println!("ℹ️  DEBUG: {} not found in directory listing of {}", monitor_filename, remote_dir);
@@ -585,10 +1171,10 @@ fn get_local_ip() -> String {
 // 4. Uploads the updated file back to the server
 // Returns Ok(true) if write succeeded, Ok(false) if write failed (non-fatal), Err for fatal errors
 fn write_monitor_file(
-    ftp: &mut ftp::FtpStream,
+    ftp: &mut dyn Transport,
     remote_dir: &str,
     config: &FTPConfig,
-    file_listing: &[String]
+    file_listing: &[RemoteEntry]
 ) -> Result<bool, Box<dyn std::error::Error>> {
     let monitor_filename = "_monitored.json";
 
@@ -724,10 +1310,10 @@ fn write_monitor_file(
 // Remove our entry from _monitored.json file on the FTP server
 // Called during cleanup when stopping monitoring or shutting down
 fn cleanup_monitor_file(
-    ftp: &mut ftp::FtpStream,
+    ftp: &mut dyn Transport,
     remote_dir: &str,
     config: &FTPConfig,
-    file_listing: &[String]
+    file_listing: &[RemoteEntry]
 ) -> Result<bool, Box<dyn std::error::Error>> {
     let monitor_filename = "_monitored.json";
 
@@ -826,8 +1412,7 @@ fn cleanup_all_monitor_files(
     println!("🧹 CLEANUP ALL: Starting cleanup for {} directories", config.remote_destination.len());
 
     // Create new FTP connection for cleanup
-    let server_addr = format!("{}:{}", config.server_address, config.port);
-    let mut ftp = match ftp::FtpStream::connect(&server_addr) {
+    let mut ftp = match connect_transport(config) {
         Ok(stream) => stream,
         Err(e) => {
             println!("❌ CLEANUP ALL: Failed to connect to FTP server: {}", e);
@@ -866,7 +1451,7 @@ fn cleanup_all_monitor_files(
     };
 
     // Cleanup monitor file in this directory
-    let _ = cleanup_monitor_file(&mut ftp, remote_dir, config, &files);
+    let _ = cleanup_monitor_file(&mut *ftp, remote_dir, config, &files);
 
     println!("✅ CLEANUP ALL: Finished cleaning up all directories");
     Ok(())
@@ -884,29 +1469,6 @@ fn get_hash_file_path(hash_file: &str) -> Result<PathBuf, Box<dyn std::error::Er
     Ok(path)
 }
 
-// Helper function to get file modification time from FTP server
-fn get_file_mod_time(ftp: &mut ftp::FtpStream, filename: &str) -> Result<chrono::DateTime<chrono::Utc>, Box<dyn std::error::Error>> {
-    match ftp.mdtm(filename) {
-        Ok(Some(time)) => {
-            // Convert from chrono v0.2.25 to chrono v0.4.41
-            // The FTP crate uses chrono v0.2.25, we use v0.4.41
-            let timestamp = time.timestamp();
-            let naive = chrono::DateTime::from_timestamp(timestamp, 0)
-                .unwrap_or_else(|| chrono::Utc::now())
-                .naive_utc();
-            Ok(chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(naive, chrono::Utc))
-        }
-        Ok(None) => {
-            // If MDTM returns None, return current time as fallback
-            Ok(chrono::Utc::now())
-        }
-        Err(_) => {
-            // If MDTM fails, return current time as fallback
-            Ok(chrono::Utc::now())
-        }
-    }
-}
-
 // Helper function to compute file metadata hash
 fn compute_file_hash(filename: &str, remote_dir: &str, size: u64, mod_time: chrono::DateTime<chrono::Utc>) -> u64 {
     let metadata_string = format!("{}|{}|{}|{}", remote_dir, filename, size, mod_time.timestamp());
@@ -1146,7 +1708,9 @@ impl SessionStats {
 /// standalone binary execution and FFI library integration.
 pub fn run_ftp_with_args(
     args: Vec<String>,
-    shutdown_flag: Arc<AtomicBool>
+    shutdown_flag: Arc<AtomicBool>,
+    rate_limiter: Arc<RateLimiter>,
+    pause: Arc<PauseControl>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use std::io::Write;
 
@@ -1217,6 +1781,37 @@ pub fn run_ftp_with_args(
     
     // Convert stabilization_interval from milliseconds to seconds (Swift sends milliseconds)
     config.stabilization_interval = config.stabilization_interval / 1000;
+
+    // Compile include/exclude rules once - a bad pattern is a configuration error, not a silent no-op
+    config.file_filter = FileFilter::new(&config.include, &config.exclude, config.min_file_size, config.max_file_size)
+        .map_err(|e| {
+            config_log(&config, &format!("❌ Invalid file filter: {}", e));
+            format!("Invalid file filter: {}", e)
+        })?;
+
+    config.disposition = DispositionPolicy::new(
+        config.post_upload_action,
+        Path::new(&config.local_source_path),
+        config.archive_path.as_deref(),
+        &config.rename_suffix,
+        config.sent_date_folders,
+    )
+    .map_err(|e| {
+        config_log(&config, &format!("❌ Invalid post-upload action: {}", e));
+        format!("Invalid post-upload action: {}", e)
+    })?;
+
+    rate_limiter.set_rate(config.max_upload_rate.unwrap_or(0));
+    config.throttle = Throttle {
+        session: rate_limiter,
+        global: crate::GLOBAL_RATE_LIMITER.clone(),
+    };
+    config.pause = pause;
+    if config.throttle.session.is_overridden() {
+        config_log(&config, &format!("🐢 Upload bandwidth set by the app to {} bytes/sec (0 = unlimited)", config.throttle.session.rate()));
+    } else if let Some(rate) = config.max_upload_rate.filter(|rate| *rate > 0) {
+        config_log(&config, &format!("🐢 Upload bandwidth limited to {} bytes/sec", rate));
+    }
     
     info!("🔧 Config loaded: {}@{}:{}", config.username, config.server_address, config.port);
     config_log(&config, &format!("🔧 {}@{}:{}", config.username.green(), config.server_address.cyan(), config.port.to_string().cyan()));
@@ -1249,6 +1844,25 @@ pub fn run_ftp_with_args(
     config_log(&config, &format!("🔧 Stabilization Interval: {}s (file stabilization wait)", config.stabilization_interval.to_string().yellow()));
     config_log(&config, &format!("🔧 Upload Aggressiveness: {} parallel connections", config.upload_aggressiveness.to_string().cyan()));
     config_log(&config, &format!("🔧 Auto-tune Aggressiveness: {}", if config.auto_tune_aggressiveness { "enabled".green() } else { "disabled".red() }));
+    if !config.include.is_empty() || !config.exclude.is_empty() || config.min_file_size.is_some() || config.max_file_size.is_some() {
+        config_log(&config, &format!("🔧 File Filter: include {:?}, exclude {:?}, size {:?}..{:?} bytes",
+            config.include, config.exclude, config.min_file_size, config.max_file_size));
+    }
+    config_log(&config, &format!("🔧 Protocol: {:?}", config.protocol));
+    match config.protocol {
+        Protocol::Sftp => {
+            config_log(&config, &format!("🔧 SFTP Auth: {}", if config.sftp_private_key_path.is_some() { "private key" } else { "password" }));
+        }
+        Protocol::Ftp => {
+            config_log(&config, &format!("🔧 FTPS Mode: {:?}{}", config.ftps_mode,
+                if config.ftps_accept_invalid_certs { " (certificate validation disabled)" } else { "" }));
+            if config.ftps_mode == TlsMode::None {
+                config_log(&config, &format!("⚠️ {} Plain FTP - credentials and files are sent unencrypted", "WARNING:".yellow()));
+            } else if config.ftps_mode == TlsMode::Implicit && config.port == 21 {
+                config_log(&config, &format!("⚠️ {} Implicit FTPS on port 21 - implicit TLS servers normally listen on 990", "WARNING:".yellow()));
+            }
+        }
+    }
 
     // Send sync interval as notification so it appears in UI
     let _ = send_notification(&config, "info", &format!("🔧 Sync Interval: {} seconds", config.sync_interval), None, None);
@@ -1278,15 +1892,7 @@ pub fn run_ftp_with_args(
 
     // Initialize SQLite database for hash tracking
     // Use FTP_DATA_DIR environment variable for sandboxed apps, fallback to tmp dir
-    let data_dir_str = std::env::var("FTP_DATA_DIR").unwrap_or_else(|_| {
-        // Fallback: try to construct Application Support path
-        if let Ok(home) = std::env::var("HOME") {
-            format!("{}/Library/Application Support/FTPUploader", home)
-        } else {
-            "/tmp/FTPUploader".to_string()
-        }
-    });
-    let data_dir = PathBuf::from(data_dir_str);
+    let data_dir = db::data_dir();
 
     // Ensure data directory exists
     if let Err(e) = std::fs::create_dir_all(&data_dir) {
@@ -1294,10 +1900,16 @@ pub fn run_ftp_with_args(
     }
 
     // Use config_id (not session_id) for database path so it persists across restarts
-    let db_path = data_dir.join(format!("config_{}.db", config.config_id));
+    let db_path = db::config_db_path(&config.config_id);
 
     config_log(&config, &format!("🗄️  Initializing SQLite database at: {}", db_path.display()));
-    if let Err(e) = db::init_database(&db_path) {
+    if let Err(e) = db::init_database(&config.config_id, &db_path) {
+        // Falling back would ignore everything the newer version recorded
+        if e.downcast_ref::<db::SchemaTooNew>().is_some() {
+            config_log(&config, &format!("❌ {}", e));
+            let _ = send_notification(&config, "error", &e.to_string(), None, None);
+            return Err(e);
+        }
         config_log(&config, &format!("⚠️  Database initialization failed: {}, falling back to legacy hash files", e));
     } else {
         config_log(&config, &format!("✅ Database initialized successfully"));
@@ -1306,7 +1918,7 @@ pub fn run_ftp_with_args(
         if let Ok(hash_file_path) = get_hash_file_path(hash_file) {
             if hash_file_path.exists() {
                 config_log(&config, &format!("🔄 Found legacy hash file, attempting migration..."));
-                match db::migrate_from_hash_file(&config.session_id, &hash_file_path) {
+                match db::migrate_from_hash_file(&config.config_id, &hash_file_path) {
                     Ok(migrated) => {
                         if migrated > 0 {
                             config_log(&config, &format!("✅ Migrated {} entries from legacy hash file", migrated));
@@ -1323,6 +1935,51 @@ pub fn run_ftp_with_args(
     // Create persistent session state that accumulates across all iterations
     let session_state = Arc::new(Mutex::new(SessionState::new()));
 
+    // Connection count controller; only consulted when auto_tune_aggressiveness is on
+    let mut auto_tuner = AutoTuner::new(config.upload_aggressiveness as usize);
+
+    // Start from the session limit learned in earlier runs instead of rediscovering it through rejections
+    let server_key = server_limit_key(&config);
+    restore_session_limit(&config, &connection_manager, &mut auto_tuner);
+
+    // Watch mode: filesystem events drive iterations, with a periodic full scan as a safety net
+    let local_root = PathBuf::from(&config.local_source_path);
+    let watcher = if config.watch_mode {
+        match DirectoryWatcher::start(&local_root, Duration::from_secs(1)) {
+            Ok(watcher) => {
+                config_log(&config, &format!("{} Watching {} for changes (full rescan every {}s)",
+                    "👀".blue(), config.local_source_path.cyan(), config.full_rescan_interval));
+                Some(watcher)
+            }
+            Err(e) => {
+                config_log(&config, &format!("{} Could not watch {} ({}), falling back to scanning every {}s",
+                    "⚠️".yellow(), config.local_source_path, e, config.sync_interval));
+                let _ = send_notification(&config, "warning", &format!("File watching unavailable, using periodic scans: {}", e), None, None);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut scan_scope = ScanScope::Full;
+    let mut last_full_scan = Instant::now();
+
+    // Retention for FTPU-Sent / the archive, checked between iterations
+    let retention = RetentionPolicy {
+        max_age: config.sent_max_age_days
+            .filter(|days| *days > 0.0)
+            .map(|days| Duration::from_secs_f64(days * 86_400.0)),
+        max_total_bytes: config.sent_max_total_size,
+        max_files: config.sent_max_files,
+    };
+    let mut last_retention_check: Option<Instant> = None;
+
+    if !config.schedule.is_empty() {
+        config_log(&config, &format!("{} Upload schedule: {} window(s), {} blackout(s)",
+            "🗓️".blue(), config.schedule.windows.len(), config.schedule.blackouts.len()));
+    }
+    let mut active_window: Option<Option<usize>> = None;
+
     // Main continuous processing loop
     let mut iteration = 0;
     loop {
@@ -1337,7 +1994,33 @@ pub fn run_ftp_with_args(
             config_log(&config, &format!("{} Config {} stopped, exiting gracefully", "⏸️".yellow(), config.config_name));
             break;
         }
-        
+
+        // Paused through the FFI: hold here, keeping the DB, connection manager and session stats
+        if config.pause.is_paused() && !wait_while_paused(&config, status_file, &shutdown_flag, &shutdown_file, &connection_manager) {
+            config_log(&config, &format!("{} Shutdown received while paused, exiting gracefully", "🛑".red()));
+            let _ = cleanup_all_monitor_files(&config);
+            connection_manager.pool.close_all();
+            return Ok(());
+        }
+
+        // Outside the schedule: idle until the next window opens
+        if !config.schedule.is_empty() {
+            let Some(window) = wait_for_schedule(&config, status_file, &shutdown_flag, &shutdown_file, &connection_manager) else {
+                config_log(&config, &format!("{} Shutdown received while paused by schedule, exiting gracefully", "🛑".red()));
+                let _ = cleanup_all_monitor_files(&config);
+                connection_manager.pool.close_all();
+                return Ok(());
+            };
+            // Paused just as the window opened
+            if config.pause.is_paused() {
+                continue;
+            }
+            if active_window != Some(window) {
+                active_window = Some(window);
+                apply_schedule_window(&config, &connection_manager, window);
+            }
+        }
+
         iteration += 1;
         let _start_time = Instant::now();
         let start_datetime = Utc::now();
@@ -1356,7 +2039,9 @@ pub fn run_ftp_with_args(
             &shutdown_flag,
             &connection_manager,
             iteration,
-            &session_state
+            &session_state,
+            &mut auto_tuner,
+            &scan_scope
         );
         
         match result {
@@ -1369,6 +2054,17 @@ pub fn run_ftp_with_args(
             }
         }
         
+        if let Some(limit) = connection_manager.take_limit_update() {
+            if let Err(e) = db::save_server_limit(&config.config_id, &server_key, &limit) {
+                config_log(&config, &format!("⚠️ Failed to save the server's session limit: {}", e));
+            }
+        }
+
+        if retention.is_enabled() && last_retention_check.is_none_or(|t| t.elapsed() >= RETENTION_CHECK_INTERVAL) {
+            last_retention_check = Some(Instant::now());
+            apply_sent_retention(&config, &retention);
+        }
+
         // Check if we should continue or exit
         config_log(&config, &format!("🔍 DEBUG: Checking sync_interval: {}", config.sync_interval));
         if config.sync_interval <= 0.0 {
@@ -1386,8 +2082,13 @@ pub fn run_ftp_with_args(
         config_log(&config, &format!("{} Process will stay alive and continue monitoring", "🔄".blue()));
         
         // Check for shutdown during interval wait - check every 100ms for faster response
-        let wait_ms = (config.sync_interval * 1000.0) as u64; // Convert to milliseconds
+        let wait_ms = match &watcher {
+            // Watch mode: events end the wait early; otherwise wait for the next full rescan
+            Some(_) => ((config.full_rescan_interval - last_full_scan.elapsed().as_secs_f64()).max(0.0) * 1000.0) as u64,
+            None => (config.sync_interval * 1000.0) as u64, // Convert to milliseconds
+        };
         let mut elapsed_ms = 0;
+        scan_scope = ScanScope::Full;
         
         config_log(&config, &format!("🔍 DEBUG: Starting interval wait for {} ms", wait_ms));
         
@@ -1398,6 +2099,7 @@ pub fn run_ftp_with_args(
                 // Cleanup: Remove our entry from monitor files before exiting
                 println!("🧹 CLEANUP: Removing monitor entries (shutdown during wait)");
                 let _ = cleanup_all_monitor_files(&config);
+                connection_manager.pool.close_all();
 
                 return Ok(());
             }
@@ -1407,12 +2109,36 @@ pub fn run_ftp_with_args(
                 // Cleanup: Remove our entry from monitor files before exiting
                 println!("🧹 CLEANUP: Removing monitor entries (config stopped during wait)");
                 let _ = cleanup_all_monitor_files(&config);
+                connection_manager.pool.close_all();
 
                 return Ok(());
             }
+            if config.pause.is_paused() {
+                break;
+            }
+            if let Some(watcher) = &watcher {
+                if watcher.take_rescan_request() {
+                    config_log(&config, &format!("{} Watcher lost events, running a full rescan", "👀".yellow()));
+                    break;
+                }
+                if watcher.has_settled() {
+                    // Map event paths (canonical) back onto the configured source folder
+                    let changed: Vec<PathBuf> = watcher.take_settled().into_iter()
+                        .filter_map(|path| path.strip_prefix(watcher.root()).ok().map(|rel| local_root.join(rel)))
+                        .collect();
+                    if !changed.is_empty() {
+                        config_log(&config, &format!("{} {} changed path(s) ready for upload", "👀".blue(), changed.len()));
+                        scan_scope = ScanScope::Changed(changed);
+                        break;
+                    }
+                }
+            }
             std::thread::sleep(std::time::Duration::from_millis(100));
             elapsed_ms += 100;
         }
+        if matches!(scan_scope, ScanScope::Full) {
+            last_full_scan = Instant::now();
+        }
         
         config_log(&config, &format!("✅ DEBUG: Interval wait completed, continuing to next iteration"));
         
@@ -1436,6 +2162,9 @@ pub fn run_ftp_with_args(
     println!("🔄 RUST DEBUG: LOOP ENDED - process exiting");
     config_log(&config, &format!("{} Main loop ended, performing cleanup...", "🏁".blue()));
 
+    // Log out of pooled sessions
+    connection_manager.pool.close_all();
+
     // Cleanup: Remove our entry from monitor files in all directories
     println!("🧹 CLEANUP: Removing monitor entries from all directories");
     match cleanup_all_monitor_files(&config) {
@@ -1463,24 +2192,52 @@ fn process_single_iteration(
     shutdown_flag: &Arc<AtomicBool>,
     connection_manager: &Arc<ConnectionManager>,
     iteration: usize,
-    session_state: &Arc<Mutex<SessionState>>
+    session_state: &Arc<Mutex<SessionState>>,
+    auto_tuner: &mut AutoTuner,
+    scan_scope: &ScanScope
 ) -> Result<(), Box<dyn std::error::Error>> {
     
+    // Don't touch a server the circuit breaker considers down until its next probe
+    match connection_manager.begin_connect() {
+        ConnectPermit::Blocked(remaining) => {
+            config_log(config, &format!("{} Server unavailable - next connection probe in {:.0}s", "⏸️".yellow(), remaining.as_secs_f64()));
+            send_status(status_file, config, "Server unavailable", &format!("Next probe in {:.0}s", remaining.as_secs_f64()), 0.0, None)?;
+            // Wait for the probe in 100ms steps so shutdown and pause still take effect
+            let probe_at = Instant::now() + remaining;
+            while Instant::now() < probe_at {
+                if shutdown_flag.load(Ordering::SeqCst) || fs::metadata(shutdown_file).is_ok() || config.pause.is_paused() {
+                    break;
+                }
+                std::thread::sleep(probe_at.saturating_duration_since(Instant::now()).min(Duration::from_millis(100)));
+            }
+            return Ok(());
+        }
+        ConnectPermit::Probe => config_log(config, &format!("{} Probing whether {} is reachable again...", "🔌".blue(), config.server_address)),
+        ConnectPermit::Allowed => {}
+    }
+
     // Connect to FTP for directory scanning
     config_log(&config, &format!("{} Connecting to FTP server...", "🔌".blue()));
     send_status(status_file, &config, "Connecting", "", 0.1, None)?;
     
-    let mut ftp = match ftp::FtpStream::connect((config.server_address.clone(), config.port)) {
+    let mut ftp = match connect_transport(config) {
         Ok(stream) => {
-            config_log(&config, &format!("{} Connected to {}:{}", "✅".green(), config.server_address, config.port));
+            config_log(&config, &format!("{} Connected to {}:{}{}", "✅".green(), config.server_address, config.port,
+                if stream.is_secure() { " (encrypted)" } else { "" }));
             stream
         },
         Err(e) => {
             let error_msg = format!("Connection failed: {}", e);
             error!("{}", error_msg);
             
-            // Analyze error and determine retry strategy
-            let (is_server_rejection, retry_delay) = connection_manager.record_failure(&error_msg, config.sync_interval);
+            // Analyze error and determine retry strategy; idle pooled sessions are still logged in
+            let other_sessions = connection_manager.pool.open_count();
+            let kind = errors::classify_login(&e, other_sessions);
+            // Only a 421, or a 530 while other sessions are logged in, says anything about the session limit
+            if kind.is_server_rejection() {
+                connection_manager.record_session_rejected(connection_manager.pool.open_count() + 1);
+            }
+            let (is_server_rejection, retry_delay) = connection_manager.record_failure(kind, &error_msg, config.sync_interval);
             let failure_count = connection_manager.get_failure_count();
             
             if is_server_rejection {
@@ -1494,9 +2251,12 @@ fn process_single_iteration(
             send_status(status_file, &config, "Error", &format!("Connection failed (attempt {}), retrying in {:.0}s", failure_count, retry_delay.as_secs_f64()), 0.0, None)?;
             write_result(result_file, &config, false, &error_msg, 0)?;
 
-            // Only send notification if this is the 2nd+ failure (don't spam on initial connection)
-            if failure_count >= 2 {
-                send_notification(&config, "warning", &format!("Connection failed (attempt {}), retrying...", failure_count), None, None)?;
+            // The circuit breaker notifies once when the server is down, instead of on every attempt;
+            // anything else (e.g. a failed certificate check) is reported now
+            if is_server_down(&e, kind, other_sessions) {
+                report_circuit_transition(config, connection_manager.connect_failed());
+            } else {
+                send_notification(config, "error", &error_msg, None, None)?;
             }
 
             std::thread::sleep(retry_delay);
@@ -1508,8 +2268,13 @@ fn process_single_iteration(
         let error_msg = format!("Login failed: {}", e);
         error!("{}", error_msg);
         
-        // Analyze error and determine retry strategy
-        let (is_server_rejection, retry_delay) = connection_manager.record_failure(&error_msg, config.sync_interval);
+        // Analyze error and determine retry strategy; idle pooled sessions are still logged in
+        let other_sessions = connection_manager.pool.open_count();
+        let kind = errors::classify_login(&e, other_sessions);
+        if kind.is_server_rejection() {
+            connection_manager.record_session_rejected(connection_manager.pool.open_count() + 1);
+        }
+        let (is_server_rejection, retry_delay) = connection_manager.record_failure(kind, &error_msg, config.sync_interval);
         let failure_count = connection_manager.get_failure_count();
         
         if is_server_rejection {
@@ -1522,8 +2287,13 @@ fn process_single_iteration(
         config_log(&config, &format!("{} Waiting {:.1} seconds before retry...", "⏳".yellow(), retry_delay.as_secs_f64()));
         send_status(status_file, &config, "Error", &format!("Login failed (attempt {}), retrying in {:.0}s", failure_count, retry_delay.as_secs_f64()), 0.0, None)?;
 
-        // Send error notification via FFI callback to Swift
-        send_notification(&config, "error", &error_msg, None, None)?;
+        // Server not answering counts towards the circuit breaker; anything else (e.g. bad credentials) is reported now
+        if is_server_down(&e, kind, other_sessions) {
+            report_circuit_transition(config, connection_manager.connect_failed());
+        } else {
+            // Send error notification via FFI callback to Swift
+            send_notification(&config, "error", &error_msg, None, None)?;
+        }
 
         write_result(result_file, &config, false, &error_msg, 0)?;
         
@@ -1532,6 +2302,8 @@ fn process_single_iteration(
     }
 
     // Record successful connection
+    report_circuit_transition(config, connection_manager.connect_succeeded());
+    connection_manager.record_session_ok(connection_manager.pool.open_count() + 1);
     connection_manager.record_success();
     let failure_count = connection_manager.get_failure_count();
     
@@ -1543,7 +2315,7 @@ fn process_single_iteration(
     send_notification(&config, "info", &format!("Connected to {}", config.server_address), None, None)?;
 
     // Scan local directory for files to upload
-    let local_files = scan_local_directory_for_files(&config, status_file, shutdown_file, shutdown_flag, iteration)?;
+    let local_files = scan_local_directory_for_files(&config, status_file, shutdown_file, shutdown_flag, iteration, scan_scope)?;
 
     config_log(&config, &format!("🔍 DEBUG: Local scan found {} files to upload", local_files.len()));
     // Only show first 10 files to avoid log flooding
@@ -1562,7 +2334,7 @@ fn process_single_iteration(
         .map(|(rel_path, full_path, _)| (rel_path.clone(), full_path.to_string_lossy().to_string()))
         .collect();
 
-    config_log(&config, &format!("🔍 DEBUG: Files will be moved to FTPU-Sent after successful upload"));
+    config_log(config, &format!("🔍 DEBUG: Post-upload action: {:?}", config.post_upload_action));
     
     if all_files.is_empty() {
         config_log(&config, &format!("{} No files found to process, will wait for interval and retry", "⚠️".yellow()));
@@ -1588,22 +2360,44 @@ fn process_single_iteration(
     config_log(&config, &format!("{} STARTING UPLOAD PHASE - {} files to process", "🚀🚀🚀".green(), all_files.len()));
     config_log(&config, &format!("========================================"));
 
+    // The active schedule window may override the configured connection count
+    let configured_connections = connection_manager.window_connections().unwrap_or(config.upload_aggressiveness as usize);
+
     // Check if we should reduce parallel connections due to server limits
-    let max_connections = if connection_manager.should_reduce_connections() {
-        let reduced = (config.upload_aggressiveness as usize / 4).max(1); // Reduce to 1/4 of configured aggressiveness
+    let max_connections = if config.auto_tune_aggressiveness {
+        auto_tuner.cap(connection_manager.worker_limit());
+        let previous = auto_tuner.current;
+        let (mut tuned, mut reason) = auto_tuner.next_connection_count(session_state, connection_manager);
+        if let Some(cap) = connection_manager.window_connections().filter(|&cap| tuned > cap) {
+            tuned = cap;
+            auto_tuner.current = cap;
+            reason = format!("{}, capped by the schedule window", reason);
+        }
+        config_log(config, &format!("{} Auto-tune: {} parallel connections ({})", "🎛️".blue(), tuned, reason));
+        send_status(status_file, config, "Auto-tuned", &format!("{} parallel connections ({})", tuned, reason), 0.45, None)?;
+        if tuned != previous {
+            let _ = send_notification(config, "info",
+                &format!("🎛️ Auto-tune: {} → {} parallel connections ({})", previous, tuned, reason), None, None);
+        }
+        tuned
+    } else if let Some(workers) = connection_manager.worker_limit().filter(|&w| w < configured_connections) {
+        config_log(config, &format!("{} Server accepts {} concurrent sessions - using {} instead of {} parallel connections",
+            "📏".yellow(), workers + 1, workers, configured_connections));
+        workers
+    } else if connection_manager.should_reduce_connections() {
+        let reduced = (configured_connections / 4).max(1); // Reduce to 1/4 of configured aggressiveness
         config_log(&config, &format!("{} Server limit detected - reducing from {} to {} parallel connections",
-            "🔧".yellow(), config.upload_aggressiveness, reduced));
+            "🔧".yellow(), configured_connections, reduced));
         reduced
     } else {
-        // TODO: Implement auto-tuning logic using config.auto_tune_aggressiveness
-        // For now, just use the configured aggressiveness
-        config.upload_aggressiveness as usize // Use configured aggressiveness
+        configured_connections // Use configured aggressiveness
     };
 
     config_log(&config, &format!("{} Using {} parallel connections for upload", "🔧".blue(), max_connections));
 
+    let upload_started = Instant::now();
     let files_processed = process_files(
-        &mut ftp,
+        &mut *ftp,
         &all_files,
         &config,
         status_file,
@@ -1615,6 +2409,7 @@ fn process_single_iteration(
         max_connections,
         session_state
     )?;
+    auto_tuner.upload_phase_finished(upload_started.elapsed());
     
     // Close FTP connection
     ftp.quit().ok();
@@ -1627,12 +2422,20 @@ fn process_single_iteration(
 }
 
 // Function to scan local directory for files to upload
+// What an iteration looks at: the whole source tree, or only paths reported by the watcher
+#[derive(Debug)]
+enum ScanScope {
+    Full,
+    Changed(Vec<PathBuf>),
+}
+
 fn scan_local_directory_for_files(
     config: &FTPConfig,
     status_file: &str,
     shutdown_file: &str,
     shutdown_flag: &Arc<AtomicBool>,
-    _iteration: usize
+    _iteration: usize,
+    scan_scope: &ScanScope
 ) -> Result<Vec<(String, PathBuf, u64)>, Box<dyn std::error::Error>> {
 
     config_log(&config, &format!("{} Scanning local directory for files to upload...", "🔍".blue()));
@@ -1662,9 +2465,12 @@ fn scan_local_directory_for_files(
     }
 
     // Recursively scan local directory
-    fn scan_dir_recursive(dir: &PathBuf, base_dir: &PathBuf, files: &mut Vec<(String, PathBuf, u64)>, config: &FTPConfig) -> std::io::Result<()> {
+    fn scan_dir_recursive(dir: &PathBuf, base_dir: &PathBuf, files: &mut Vec<(String, PathBuf, u64)>, config: &FTPConfig, ignores: &mut IgnoreStack) -> std::io::Result<()> {
         config_log(&config, &format!("   📂 Scanning: {}", dir.display()));
 
+        // This folder's .ftpignore applies to everything below it
+        let pushed_ignore = ignores.push_dir(dir);
+
         if let Ok(entries) = fs::read_dir(dir) {
             let mut file_count = 0;
             let mut dir_count = 0;
@@ -1685,10 +2491,23 @@ fn scan_local_directory_for_files(
                     continue;
                 }
 
-                if path.is_dir() {
+                // Files already renamed after upload ("rename" post-upload action)
+                if config.disposition.is_disposed_name(&filename) {
+                    skipped_count += 1;
+                    continue;
+                }
+
+                // Honor .ftpignore rules (ignored folders are not descended into)
+                let is_dir = path.is_dir();
+                if ignores.is_ignored(&path, is_dir) {
+                    skipped_count += 1;
+                    continue;
+                }
+
+                if is_dir {
                     dir_count += 1;
                     // Recursively scan subdirectories
-                    scan_dir_recursive(&path, base_dir, files, config)?;
+                    scan_dir_recursive(&path, base_dir, files, config, ignores)?;
                 } else if path.is_file() {
                     // Get file size
                     if let Ok(metadata) = fs::metadata(&path) {
@@ -1699,6 +2518,12 @@ fn scan_local_directory_for_files(
                             .map(|p| p.to_string_lossy().to_string())
                             .unwrap_or_else(|_| filename.clone());
 
+                        // Apply include/exclude patterns and size limits
+                        if !config.file_filter.allows(&relative_path, size) {
+                            skipped_count += 1;
+                            continue;
+                        }
+
                         files.push((relative_path, path.clone(), size));
                         file_count += 1;
                     }
@@ -1713,17 +2538,175 @@ fn scan_local_directory_for_files(
         } else {
             config_log(&config, &format!("   ⚠️ Could not read directory: {}", dir.display()));
         }
+
+        if pushed_ignore {
+            ignores.pop();
+        }
         Ok(())
     }
 
-    scan_dir_recursive(&local_dir, &local_dir, &mut all_files, config)?;
+    match scan_scope {
+        ScanScope::Full => scan_dir_recursive(&local_dir, &local_dir, &mut all_files, config, &mut IgnoreStack::default())?,
+        ScanScope::Changed(paths) => {
+            config_log(config, &format!("   👀 Checking {} changed path(s) reported by the watcher", paths.len()));
+            for path in paths {
+                // Rules from every .ftpignore between the source folder and this path
+                let mut ignores = IgnoreStack::for_directory(&local_dir, path.parent().unwrap_or(&local_dir));
+                if ignores.is_ignored(path, path.is_dir()) {
+                    continue;
+                }
 
-    config_log(&config, &format!("{} Found {} files to upload", "📊".blue(), all_files.len()));
+                if path.is_dir() {
+                    // A folder moved or created in one go only reports the folder itself
+                    scan_dir_recursive(path, &local_dir, &mut all_files, config, &mut ignores)?;
+                } else if let Ok(metadata) = fs::metadata(path) {
+                    if let Ok(relative) = path.strip_prefix(&local_dir) {
+                        let relative_path = relative.to_string_lossy().to_string();
+                        let renamed = path.file_name()
+                            .is_some_and(|name| config.disposition.is_disposed_name(&name.to_string_lossy()));
+                        if !renamed && config.file_filter.allows(&relative_path, metadata.len()) {
+                            all_files.push((relative_path, path.clone(), metadata.len()));
+                        }
+                    }
+                }
+            }
+            // A folder and a file inside it may both have been reported
+            all_files.sort_by(|a, b| a.0.cmp(&b.0));
+            all_files.dedup_by(|a, b| a.0 == b.0);
+        }
+    }
+
+    config_log(&config, &format!("{} Found {} files to upload", "📊".blue(), all_files.len()));
     send_notification(&config, "info", &format!("Found {} files", all_files.len()), None, None)?;
 
     Ok(all_files)
 }
 
+// How often the sent-files retention rules are evaluated (walking a large archive is not free)
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Prune FTPU-Sent / the archive folder, then report and record what was removed
+fn apply_sent_retention(config: &FTPConfig, retention: &RetentionPolicy) {
+    let Some(sent_root) = config.disposition.sent_root(Path::new(&config.local_source_path)) else {
+        return;
+    };
+
+    let mut sent = match db::load_sent_files(&config.config_id) {
+        Ok(files) => files,
+        Err(e) => {
+            config_log(config, &format!("⚠️ Retention check of {} failed: {}", sent_root.display(), e));
+            return;
+        }
+    };
+
+    // FTPU-Sent belongs to FTPUploader, so files moved there before arrival times were recorded
+    // are adopted now and get a full retention period. An archive folder may hold anything, so
+    // only files recorded as moved there are ever pruned.
+    if config.post_upload_action == PostUploadAction::MoveToSent {
+        let unrecorded: Vec<(String, u64)> = retention::list_files(&sent_root).unwrap_or_default().into_iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .filter(|path| !sent.iter().any(|(recorded, _)| recorded == path))
+            .map(|path| {
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                (path, size)
+            })
+            .collect();
+        if !unrecorded.is_empty() {
+            match db::record_sent_files(&config.config_id, &unrecorded) {
+                Ok(()) => {
+                    let now = Utc::now().timestamp();
+                    sent.extend(unrecorded.into_iter().map(|(path, _)| (path, now)));
+                }
+                Err(e) => config_log(config, &format!("⚠️ Failed to record sent files for retention: {}", e)),
+            }
+        }
+    }
+
+    let files: Vec<SentFile> = sent.into_iter()
+        .map(|(path, archived_at)| SentFile {
+            path: PathBuf::from(path),
+            archived_at: std::time::UNIX_EPOCH + Duration::from_secs(archived_at.max(0) as u64),
+        })
+        .filter(|file| file.path.starts_with(&sent_root))
+        .collect();
+    let report = retention.prune(&sent_root, &files);
+
+    for (path, e) in &report.failures {
+        config_log(config, &format!("⚠️ Could not prune {}: {}", path.display(), e));
+    }
+    let gone: Vec<String> = report.pruned.iter().map(|f| &f.path).chain(&report.missing)
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    if !gone.is_empty() {
+        let gone: Vec<&str> = gone.iter().map(String::as_str).collect();
+        if let Err(e) = db::forget_sent_files(&config.config_id, &gone) {
+            config_log(config, &format!("⚠️ Failed to update sent files for retention: {}", e));
+        }
+    }
+    if report.pruned.is_empty() {
+        return;
+    }
+
+    let reclaimed_mb = report.bytes_reclaimed() as f64 / 1024.0 / 1024.0;
+    config_log(config, &format!("{} Pruned {} sent files from {} ({:.2} MB reclaimed)",
+        "🧹".green(), report.pruned.len(), sent_root.display(), reclaimed_mb));
+
+    let records: Vec<(String, u64, &str)> = report.pruned.iter()
+        .map(|f| (f.path.to_string_lossy().to_string(), f.size, f.reason.as_str()))
+        .collect();
+    if let Err(e) = db::record_prunes(&config.config_id, &records) {
+        config_log(config, &format!("⚠️ Failed to record pruned files: {}", e));
+    }
+
+    let _ = send_notification(config, "info",
+        &format!("🧹 Pruned {} old sent files, reclaimed {:.2} MB", report.pruned.len(), reclaimed_mb),
+        None, None);
+}
+
+// Metadata hash identifying the current version of a local file in keep mode
+fn keep_mode_hash(config: &FTPConfig, relative_path: &str, full_path: &Path) -> Option<u64> {
+    let metadata = fs::metadata(full_path).ok()?;
+    let mod_time: chrono::DateTime<Utc> = metadata.modified().ok()?.into();
+    let hash = compute_file_hash(relative_path, &config.local_source_path, metadata.len(), mod_time);
+
+    // Content mode also folds in the inode and ctime, so a file replaced by another one, or edited
+    // in place with its size and mtime restored, gets its bytes re-checked
+    #[cfg(unix)]
+    if config.change_detection == ChangeDetection::Content {
+        use std::os::unix::fs::MetadataExt;
+        return Some(xxh3_64(format!("{}|{}|{}.{}", hash, metadata.ino(), metadata.ctime(), metadata.ctime_nsec()).as_bytes()));
+    }
+    Some(hash)
+}
+
+// Size and modification time (unix seconds) identifying the version of a file that failed
+fn file_identity(path: &Path) -> (u64, i64) {
+    match fs::metadata(path) {
+        Ok(metadata) => (
+            metadata.len(),
+            metadata.modified().map(|t| chrono::DateTime::<Utc>::from(t).timestamp()).unwrap_or(0),
+        ),
+        Err(_) => (0, 0),
+    }
+}
+
+// Streaming xxh3 over the file contents (content-based change detection)
+fn content_hash(path: &Path) -> std::io::Result<u64> {
+    use std::io::Read;
+
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Xxh3::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.digest())
+}
+
 /* Legacy download function - commented out for upload conversion
 fn scan_directories_for_files(
     ftp: &mut ftp::FtpStream,
@@ -1957,7 +2940,7 @@ fn scan_directories_for_files(
 
 // Function to process files
 fn process_files(
-    _ftp: &mut ftp::FtpStream, // Unused - each thread creates own connection
+    ftp: &mut dyn Transport, // Scan connection - each thread creates own connection; used to clean up dead letters
     all_files: &[(String, String)],
     config: &FTPConfig,
     status_file: &str,
@@ -1973,10 +2956,11 @@ fn process_files(
     // Session state is passed in from the main loop to accumulate across iterations
     
     // Hash-based file discovery for keep mode
-    let files_to_process = all_files.to_vec();
+    let mut files_to_process = all_files.to_vec();
+    let keep_mode = config.post_upload_action == PostUploadAction::Leave;
 
-    // Load existing hashes for keep mode (used by worker threads later)
-    let existing_hashes = if "upload" == "keep" {
+    // Load existing hashes for keep mode (files stay in place, only new/changed ones are uploaded)
+    let existing_hashes = if keep_mode {
         config_log(&config, &format!("🔍 Keep mode enabled - checking existing file hashes..."));
 
         // Try database first, fallback to hash files
@@ -1985,14 +2969,6 @@ fn process_files(
             Ok(existing_hashes) => {
                 config_log(&config, &format!("📋 Loaded {} existing file hashes from database", existing_hashes.len()));
 
-                // Filter out files that haven't changed based on hash comparison
-                let _original_count = files_to_process.len();
-
-                // For keep mode, we need to check each file's current hash against existing hashes
-                // This will be done during the parallel processing phase
-                config_log(&config, &format!("🔍 Will check {} files against {} existing hashes during processing",
-                    files_to_process.len(), existing_hashes.len()));
-
                 existing_hashes
             }
             Err(e) => {
@@ -2003,8 +2979,6 @@ fn process_files(
                     Ok(hash_file_path) => {
                         let existing_hashes = load_existing_hashes(&hash_file_path);
                         config_log(&config, &format!("📋 Loaded {} existing file hashes from legacy file", existing_hashes.len()));
-                        config_log(&config, &format!("🔍 Will check {} files against {} existing hashes during processing",
-                            files_to_process.len(), existing_hashes.len()));
                         existing_hashes
                     }
                     Err(e) => {
@@ -2015,14 +2989,117 @@ fn process_files(
             }
         }
     } else {
-        config_log(&config, &format!("🗑️ Delete mode enabled - will only process files that still exist on server"));
+        config_log(&config, &format!("📦 Move mode - uploaded files leave the source folder ({:?})", config.post_upload_action));
         std::collections::HashMap::new()
     };
+
+    // Content hashes read during the keep-mode scan, by local path, with the (size, mtime) they
+    // were taken at - dedupe and the post-upload record reuse them instead of reading the file again
+    let mut scanned_content: std::collections::HashMap<String, ((u64, i64), u64)> = std::collections::HashMap::new();
+
+    // Keep mode: drop files that match what was last uploaded
+    if keep_mode {
+        let content_mode = config.change_detection == ChangeDetection::Content;
+        let content_hashes = if content_mode {
+            db::load_content_hashes_for_config(&config.config_id).unwrap_or_else(|e| {
+                config_log(config, &format!("⚠️ Failed to load content hashes: {}", e));
+                std::collections::HashMap::new()
+            })
+        } else {
+            std::collections::HashMap::new()
+        };
+
+        let before = files_to_process.len();
+        let mut rehashed = 0;
+        files_to_process.retain(|(filename, local_path)| {
+            let key = format!("{}|{}", config.local_source_path, filename);
+            let local_path = Path::new(local_path);
+            let Some(current_hash) = keep_mode_hash(config, filename, local_path) else {
+                return true;
+            };
+
+            // Cheap pre-check: metadata unchanged means the file was not touched
+            if existing_hashes.get(&key) == Some(&current_hash) {
+                return false;
+            }
+            if !content_mode {
+                return true;
+            }
+
+            // Metadata changed - compare the bytes (only worth reading when the size still matches)
+            let (Some(&(recorded_size, recorded_content)), Ok(metadata)) = (content_hashes.get(&key), fs::metadata(local_path)) else {
+                return true;
+            };
+            if metadata.len() != recorded_size {
+                return true;
+            }
+            rehashed += 1;
+            let identity = file_identity(local_path);
+            match content_hash(local_path) {
+                Ok(current_content) if current_content == recorded_content => {
+                    // Same bytes with new timestamps - refresh the record so the next pre-check passes
+                    let mod_time = metadata.modified().map(chrono::DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+                    if let Err(e) = db::save_hash(&config.config_id, &config.local_source_path, filename, metadata.len(), mod_time, current_hash, Some(current_content)) {
+                        config_log(config, &format!("⚠️ Failed to refresh hash for {}: {}", filename, e));
+                    }
+                    false
+                }
+                Ok(current_content) => {
+                    scanned_content.insert(local_path.to_string_lossy().to_string(), (identity, current_content));
+                    true
+                }
+                Err(_) => true,
+            }
+        });
+
+        let unchanged = before - files_to_process.len();
+        config_log(config, &format!("{} Keep mode: {} new or changed, {} unchanged ({} re-read for content)",
+            "🔍".blue(), files_to_process.len().to_string().green(), unchanged, rehashed));
+        if files_to_process.is_empty() {
+            send_status(status_file, config, "Complete", &format!("All {} files unchanged", before), 1.0, None)?;
+            return Ok(0);
+        }
+    }
     
+    // Retry queue: hold back files that failed recently or were dead-lettered.
+    // A file that changed since it failed gets another chance right away.
+    let retry_queue = db::load_retry_queue(&config.config_id).unwrap_or_else(|e| {
+        config_log(config, &format!("⚠️ Failed to load retry queue: {}", e));
+        std::collections::HashMap::new()
+    });
+    if !retry_queue.is_empty() {
+        let now = Utc::now().timestamp();
+        let before = files_to_process.len();
+        let mut dead_lettered = 0;
+        files_to_process.retain(|(_, local_path)| {
+            let Some(entry) = retry_queue.get(local_path) else { return true };
+            let (file_size, mod_time) = file_identity(Path::new(local_path));
+            if file_size != entry.file_size || mod_time != entry.mod_time {
+                return true;
+            }
+            if entry.dead_lettered {
+                dead_lettered += 1;
+                return false;
+            }
+            entry.next_attempt_at <= now
+        });
+
+        let held_back = before - files_to_process.len();
+        if held_back > 0 {
+            config_log(config, &format!("{} Retry queue: holding back {} file(s) ({} waiting for backoff, {} dead-lettered)",
+                "⏳".yellow(), held_back, held_back - dead_lettered, dead_lettered));
+        }
+        if files_to_process.is_empty() {
+            send_status(status_file, config, "Complete", &format!("{} failed file(s) waiting for retry", held_back), 1.0, None)?;
+            return Ok(0);
+        }
+    }
+
     send_status(status_file, &config, "Preparing parallel processing", &format!("{} total files", files_to_process.len()), 0.5, None)?;
 
     // Process files in parallel using rayon
     let files_processed = Arc::new(AtomicUsize::new(0));
+    let in_flight_content = InFlightContent::default();
     let status_sender = Arc::new(Mutex::new(status_file.to_string()));
     let config_arc = Arc::new(config.clone());
     let status_sender_clone = status_sender.clone();
@@ -2052,6 +3129,7 @@ fn process_files(
                             .unwrap_or_default()
                             .as_secs(),
                         file_size: status_update.file_size,
+                        bytes_transferred: None,
                         upload_speed_mbps: None,
                         upload_time_secs: None,
                     };
@@ -2071,6 +3149,7 @@ fn process_files(
                             .unwrap_or_default()
                             .as_secs(),
                         file_size: status_update.file_size,
+                        bytes_transferred: status_update.bytes_transferred,
                         upload_speed_mbps: None, // Will be filled by specific status updates
                         upload_time_secs: None,  // Will be filled by specific status updates
                     };
@@ -2195,10 +3274,6 @@ fn process_files(
     // Use the session state tracking already initialized above
     let session_state_clone = session_state.clone();
 
-    // Use the existing_hashes HashMap loaded earlier (from database or legacy file)
-    // This will be cloned for each worker thread in the parallel processing below
-    let existing_hashes_clone = existing_hashes.clone();
-
     // Clone shutdown_file for parallel processing
     let shutdown_file_str = shutdown_file.to_string();
 
@@ -2206,7 +3281,7 @@ fn process_files(
     config_log(&config, &format!("🔧 Processing with {} parallel connections", max_parallel_connections));
 
     // Use custom thread pool with exactly max_parallel_connections threads
-    let results: Vec<Result<(), String>> = pool.install(|| {
+    let results: Vec<Result<(), TransferError>> = pool.install(|| {
         files_to_upload
             .par_iter()
             .with_max_len(1) // Each file gets its own task
@@ -2216,14 +3291,21 @@ fn process_files(
         if shutdown_flag.load(Ordering::SeqCst) {
             // Only exit if shutdown file also exists for this config
             if fs::metadata(&shutdown_file_str).is_ok() {
-                return Err("Shutdown requested".to_string());
+                return Err(TransferError::new(ErrorKind::Cancelled, "Shutdown requested"));
             }
             // If only general shutdown flag is set (Ctrl-C), continue processing this iteration
         }
+        // Paused: leave the remaining files for after resume
+        if config.pause.is_paused() {
+            return Err(TransferError::new(ErrorKind::Cancelled, "Paused"));
+        }
+        // The upload window closed or a blackout began mid-batch: leave the rest for the next window
+        if !config.schedule.is_open(Local::now().naive_local()) {
+            return Err(TransferError::new(ErrorKind::Cancelled, "Outside the upload schedule"));
+        }
         
         let thread_id = file_index as u64;
         let file_progress = 0.5 + (0.4 * (file_index as f64) / (files_to_upload.len() as f64));
-        let existing_hashes = existing_hashes_clone.clone();
         let session_file = session_file.to_string(); // Convert to String for parallel processing
         let _status_sender_local = status_sender_clone.clone();
         let config_arc_local = config_arc_clone.clone();
@@ -2241,21 +3323,51 @@ fn process_files(
             progress: file_progress,
             thread_id,
             file_size: None,
+            bytes_transferred: None,
         });
 
         // DEBUG: Log FTP connection attempt
         // File processing with connection retry loop
         let max_connection_retries = 3;
         let mut connection_attempt = 0;
+
+        // Upload history: how this file ended up (set on success), recorded once after the retry loop
+        let history_started = Utc::now();
+        let history_size = fs::metadata(remote_dir).map(|m| m.len()).unwrap_or(0);
+        let mut history_outcome: Option<(&str, f64)> = None;
         
         let file_result = loop {
             connection_attempt += 1;
             
-            config_log(&config, &format!("🔗 DEBUG: [Thread-{}] Attempting FTP connection for {} (attempt {})", 
-                thread_id, filename.cyan(), connection_attempt));
+            // Reuse an idle logged-in session when one is available (already in the right directory)
+            let pooled = if config.reuse_connections { connection_manager_local.pool.checkout() } else { None };
+            let reused = pooled.is_some();
+
+            config_log(&config, &format!("🔗 DEBUG: [Thread-{}] {} FTP connection for {} (attempt {})",
+                thread_id, if reused { "Reusing pooled" } else { "Attempting" }, filename.cyan(), connection_attempt));
             
-            // Create new FTP connection for this thread
-            let mut ftp = match ftp::FtpStream::connect((config.server_address.clone(), config.port)) {
+            // New connections need the circuit breaker's permission while the server looks down
+            if !reused {
+                if let ConnectPermit::Blocked(_) = connection_manager_local.begin_connect() {
+                    config_log(config, &format!("⏸️ [Thread-{}] Server unavailable, leaving {} for a later cycle", thread_id, filename));
+                    let _ = status_tx.send(StatusUpdate {
+                        stage: "Server unavailable".to_string(),
+                        filename: filename.clone(),
+                        progress: file_progress,
+                        thread_id,
+                        file_size: None,
+                        bytes_transferred: None,
+                    });
+                    break Err(TransferError::new(ErrorKind::ServerUnavailable, "Server unavailable"));
+                }
+            }
+
+            // Create new FTP connection for this thread unless the pool had one
+            let connection = match pooled {
+                Some(connection) => Ok(connection),
+                None => connect_transport(config).map(|stream| connection_manager_local.pool.adopt(stream)),
+            };
+            let mut ftp = match connection {
             Ok(stream) => {
                 debug!("[Thread-{}] FTP connection established", thread_id);
                 config_log(&config, &format!("✅ DEBUG: [Thread-{}] FTP connection successful for {}", thread_id, filename.green()));
@@ -2265,8 +3377,14 @@ fn process_files(
                 let error_msg = format!("Failed to connect: {}", e);
                 error!("[Thread-{}] {}", thread_id, error_msg);
                 
-                // Record connection failure in connection manager
-                let (is_server_rejection, retry_delay) = connection_manager_local.record_failure(&error_msg, config.sync_interval);
+                // Record connection failure in connection manager; pooled sessions and the scan connection are logged in
+                let other_sessions = connection_manager_local.pool.open_count() + 1;
+                let kind = errors::classify_login(&e, other_sessions);
+                // Sessions open at the time: pooled ones, the scan connection and this attempt
+                if kind.is_server_rejection() {
+                    connection_manager_local.record_session_rejected(connection_manager_local.pool.open_count() + 2);
+                }
+                let (is_server_rejection, retry_delay) = connection_manager_local.record_failure(kind, &error_msg, config.sync_interval);
                 let failure_count = connection_manager_local.get_failure_count();
                 
                 if is_server_rejection {
@@ -2284,13 +3402,25 @@ fn process_files(
                     progress: file_progress,
                     thread_id,
                     file_size: None,
+                    bytes_transferred: None,
                 });
                 
-                // Check if we should retry
+                // Check if we should retry; only an unreachable server counts towards the circuit breaker
+                if is_server_down(&e, kind, other_sessions) {
+                    report_circuit_transition(config, connection_manager_local.connect_failed());
+                    if connection_manager_local.is_circuit_open() {
+                        break Err(TransferError::new(ErrorKind::ServerUnavailable, error_msg));
+                    }
+                }
+                if kind.is_permanent() {
+                    config_log(config, &format!("{} [Thread-{}] Connection for {} failed permanently ({}), not retrying",
+                        "❌".red(), thread_id, filename.red(), kind));
+                    break Err(TransferError::new(kind, error_msg));
+                }
                 if connection_attempt >= max_connection_retries {
                     config_log(&config, &format!("{} [Thread-{}] Max connection retries ({}) reached for {}, giving up", 
                         "❌".red(), thread_id, max_connection_retries, filename.red()));
-                    break Err(format!("Failed to connect after {} attempts: {}", max_connection_retries, e));
+                    break Err(TransferError::new(kind, format!("Failed to connect after {} attempts: {}", max_connection_retries, e)));
                 }
                 
                 config_log(&config, &format!("{} [Thread-{}] Will retry connection for {} in {:.1}s", 
@@ -2303,12 +3433,19 @@ fn process_files(
         // DEBUG: Log login attempt
         config_log(&config, &format!("🔐 DEBUG: [Thread-{}] Attempting FTP login for {}", thread_id, filename.cyan()));
         
-        if let Err(e) = ftp.login(&config.username, &config.password) {
+        let login_result = if reused { Ok(()) } else { ftp.login(&config.username, &config.password) };
+        if let Err(e) = login_result {
             let error_msg = format!("Failed to login: {}", e);
             error!("[Thread-{}] {}", thread_id, error_msg);
             
-            // Record login failure in connection manager
-            let (is_server_rejection, retry_delay) = connection_manager_local.record_failure(&error_msg, config.sync_interval);
+            // Record login failure in connection manager; the pool counts this session, the scan connection is logged in
+            let other_sessions = connection_manager_local.pool.open_count();
+            let kind = errors::classify_login(&e, other_sessions);
+            // This session is already counted by the pool; add the scan connection
+            if kind.is_server_rejection() {
+                connection_manager_local.record_session_rejected(connection_manager_local.pool.open_count() + 1);
+            }
+            let (is_server_rejection, retry_delay) = connection_manager_local.record_failure(kind, &error_msg, config.sync_interval);
             let failure_count = connection_manager_local.get_failure_count();
             
             if is_server_rejection {
@@ -2328,16 +3465,28 @@ fn process_files(
                 progress: file_progress,
                 thread_id,
                 file_size: None,
+                bytes_transferred: None,
             });
             
             // Clean up connection gracefully
-            ftp.quit().ok();
+            ftp.discard();
             
-            // Check if we should retry
+            // Check if we should retry; only an unreachable server counts towards the circuit breaker
+            if is_server_down(&e, kind, other_sessions) {
+                report_circuit_transition(config, connection_manager_local.connect_failed());
+                if connection_manager_local.is_circuit_open() {
+                    break Err(TransferError::new(ErrorKind::ServerUnavailable, error_msg));
+                }
+            }
+            if kind.is_permanent() {
+                config_log(config, &format!("{} [Thread-{}] Login for {} failed permanently ({}), not retrying",
+                    "❌".red(), thread_id, filename.red(), kind));
+                break Err(TransferError::new(kind, error_msg));
+            }
             if connection_attempt >= max_connection_retries {
                 config_log(&config, &format!("{} [Thread-{}] Max login retries ({}) reached for {}, giving up", 
                     "❌".red(), thread_id, max_connection_retries, filename.red()));
-                break Err(format!("Failed to login after {} attempts: {}", max_connection_retries, e));
+                break Err(TransferError::new(kind, format!("Failed to login after {} attempts: {}", max_connection_retries, e)));
             }
             
             config_log(&config, &format!("{} [Thread-{}] Will retry login for {} in {:.1}s", 
@@ -2347,6 +3496,10 @@ fn process_files(
         }
         
         config_log(&config, &format!("✅ DEBUG: [Thread-{}] FTP login successful for {}", thread_id, filename.green()));
+        if !reused {
+            report_circuit_transition(config, connection_manager_local.connect_succeeded());
+            connection_manager_local.record_session_ok(connection_manager_local.pool.open_count() + 1);
+        }
 
         // DEBUG: Log directory change attempt
         // Note: remote_dir contains the LOCAL file path, we use config.remote_destination for FTP directory
@@ -2355,109 +3508,19 @@ fn process_files(
             thread_id, ftp_remote_dir.cyan(), filename.cyan()));
 
         // Change to directory on FTP server (use remote_destination, not local path)
-        if let Err(e) = ftp.cwd(ftp_remote_dir) {
+        let cwd_result = if reused { Ok(()) } else { ftp.cwd(ftp_remote_dir) };
+        if let Err(e) = cwd_result {
             let error_msg = format!("Failed to change to directory: {}", ftp_remote_dir);
             error!("[Thread-{}] {}", thread_id, error_msg);
             config_log(&config, &format!("❌ DEBUG: [Thread-{}] Server rejected CWD to '{}': {}",
                 thread_id, ftp_remote_dir.red(), e));
-            return Err(error_msg);
+            ftp.discard();
+            break Err(TransferError::new(errors::classify_ftp(&e), error_msg));
         }
 
         config_log(&config, &format!("✅ DEBUG: [Thread-{}] Successfully changed to directory '{}'",
             thread_id, ftp_remote_dir.green()));
 
-        // Check file size for stabilization
-        // CRITICAL FIX: Set to BINARY mode before SIZE command (some servers reject SIZE in ASCII mode)
-        if let Err(e) = ftp.transfer_type(ftp::types::FileType::Binary) {
-            config_log(&config, &format!("⚠️ DEBUG: [Thread-{}] Failed to set BINARY mode: {}", thread_id, e));
-        } else {
-            config_log(&config, &format!("✅ DEBUG: [Thread-{}] Set BINARY mode for SIZE command", thread_id));
-        }
-        
-        // DEBUG: Log before file size check
-        config_log(&config, &format!("📏 DEBUG: [Thread-{}] Checking file size for {}", thread_id, filename.cyan()));
-        
-        let initial_size = match ftp.size(filename) {
-            Ok(Some(size)) => {
-                debug!("[Thread-{}] File {} size: {} bytes", thread_id, filename, size);
-                config_log(&config, &format!("✅ DEBUG: [Thread-{}] Server reports {} size: {} bytes",
-                    thread_id, filename.green(), size));
-                Some(size)
-            },
-            Ok(None) => {
-                // File no longer exists on server - skip it
-                config_log(&config, &format!("{} [Thread-{}] {} no longer exists on server, skipping",
-                    "⏭️".yellow(),
-                    thread_id.to_string().cyan(),
-                    filename.green()
-                ));
-                config_log(&config, &format!("❌ DEBUG: [Thread-{}] Server says {} not found (SIZE returned None)",
-                    thread_id, filename.red()));
-                return Ok(()); // Skip this file, don't treat as error
-            },
-            Err(e) => {
-                // SIZE command not supported or failed - continue anyway without stabilization
-                config_log(&config, &format!("⚠️  [Thread-{}] SIZE command failed for {} ({}), will upload without size check",
-                    thread_id, filename.yellow(), e));
-                None
-            },
-        };
-
-            // Hash checking for keep mode - do this BEFORE stabilization
-        if "upload" == "keep" {
-            let key = format!("{}|{}", remote_dir, filename);
-            // Get file modification time for hash computation
-            let mod_time = match get_file_mod_time(&mut ftp, filename) {
-                Ok(time) => time,
-                Err(_) => chrono::Utc::now(), // Fallback to current time
-            };
-            let size_for_hash = initial_size.unwrap_or(0) as u64;
-            let current_hash = compute_file_hash(filename, remote_dir, size_for_hash, mod_time);
-
-            // DEBUG: Log hash comparison details
-            config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: File: {}", thread_id, filename.cyan()));
-            config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: Key: {}", thread_id, key.cyan()));
-            config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: Size: {:?}, ModTime: {}", thread_id, initial_size, mod_time.timestamp()));
-            config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: Current hash: {}", thread_id, current_hash));
-            config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: Loaded {} existing hashes", thread_id, existing_hashes.len()));
-
-            // Check if we have an existing hash and if it matches
-            if let Some(existing_hash) = existing_hashes.get(&key) {
-                config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: Existing hash: {}", thread_id, existing_hash));
-                config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: Hash match: {}", thread_id, *existing_hash == current_hash));
-                
-                if *existing_hash == current_hash {
-                    config_log(&config, &format!("{} [Thread-{}] {} unchanged, skipping upload", 
-                        "⏭️".yellow(), 
-                        thread_id.to_string().cyan(), 
-                        filename.green()
-                    ));
-                    
-                    let _ = status_tx.send(StatusUpdate {
-                        stage: "Skipped (unchanged)".to_string(),
-                        filename: filename.clone(),
-                        progress: file_progress + 0.15,
-                        thread_id,
-                        file_size: None,
-                    });
-                    
-                    // Increment counter for skipped files
-                    files_processed.fetch_add(1, Ordering::SeqCst);
-                    
-                    // No need to quit here - will be handled at end of retry loop
-                    return Ok(()); // Skip this file
-                } else {
-                    config_log(&config, &format!("{} [Thread-{}] {} hash changed, will upload", 
-                        "🔄".blue(), 
-                        thread_id.to_string().cyan(), 
-                        filename.yellow()
-                    ));
-                }
-            } else {
-                config_log(&config, &format!("🔍 HASH DEBUG [Thread-{}]: No existing hash found for key: {}", thread_id, key.cyan()));
-            }
-        }
-
         // Stabilization is now handled in Phase 1 before uploading
         // All files in Phase 2 are already stable, so we can proceed directly to upload
 
@@ -2466,30 +3529,100 @@ fn process_files(
         let local_path = PathBuf::from(remote_dir); // remote_dir contains the full local path
         let relative_path = filename; // filename contains the relative path
 
+        // Size stats on the local file
+        let local_size = fs::metadata(&local_path).map(|m| m.len()).ok();
+
         config_log(&config, &format!("⬆️ DEBUG: [Thread-{}] Starting upload of {} ({:?} bytes) to '{}'",
-            thread_id, relative_path.cyan(), initial_size, config.remote_destination.cyan()));
+            thread_id, relative_path.cyan(), local_size, config.remote_destination.cyan()));
+
+        // Report byte progress for each chunk streamed to the server
+        let mut report_progress = |bytes_sent: u64| {
+            let fraction = match local_size {
+                Some(total) if total > 0 => (bytes_sent as f64 / total as f64).min(1.0),
+                _ => 0.0,
+            };
+            let _ = status_tx.send(StatusUpdate {
+                stage: "Uploading".to_string(),
+                filename: filename.clone(),
+                progress: file_progress + 0.15 * fraction,
+                thread_id,
+                file_size: local_size,
+                bytes_transferred: Some(bytes_sent),
+            });
+        };
+
+        // Content hash from the keep-mode scan, unless the file changed after it was read
+        let scanned_hash = scanned_content.get(remote_dir.as_str())
+            .filter(|(identity, _)| *identity == file_identity(&local_path))
+            .map(|&(_, hash)| hash);
+
+        // Dedupe: the same bytes may already be on the server from another folder or an earlier copy
+        let dedupe_content = match (config.dedupe, local_size) {
+            (DedupeMode::Off, _) | (_, None) => None,
+            (_, Some(size)) => scanned_hash.or_else(|| content_hash(&local_path).ok()).map(|hash| (hash, size)),
+        };
+        let _content_claim = dedupe_content.map(|(hash, size)| in_flight_content.claim(hash, size));
+        if let Some((hash, size)) = dedupe_content {
+            let target = remote_full_path(config, &remote_upload_name(config, relative_path, &local_path));
+            let action = find_remote_duplicate(&mut *ftp, config, &local_path, hash, size)
+                .and_then(|original| resolve_duplicate(&mut *ftp, config, &original, &target).map(|action| (original, action)));
+            if let Some((original, action)) = action {
+                config_log(&config, &format!("{} [Thread-{}] {} has the same content as {} ({})",
+                    "♻️".green(), thread_id.to_string().cyan(), filename.green(), original.cyan(), action));
+                if let Err(e) = db::record_dedupe(&config.config_id, remote_dir, &target, &original, hash, action) {
+                    config_log(&config, &format!("⚠️ [Thread-{}] Failed to record dedupe of {}: {}", thread_id, filename, e));
+                }
+
+                let _ = status_tx.send(StatusUpdate {
+                    stage: if action == "skipped" { "Skipped (duplicate)" } else { "Copied (duplicate)" }.to_string(),
+                    filename: filename.clone(),
+                    progress: file_progress + 0.25,
+                    thread_id,
+                    file_size: Some(size),
+                    bytes_transferred: None,
+                });
+                if finish_local_file(config, filename, &local_path, thread_id, Some(hash)) {
+                    let _ = send_notification(&config, "info", &format!("♻️ {} is a duplicate of {}, not uploaded again", filename, original), Some(filename), None);
+                }
+                files_processed.fetch_add(1, Ordering::SeqCst);
+                history_outcome = Some((if action == "skipped" { "duplicate_skipped" } else { "duplicate_copied" }, 0.0));
+
+                connection_manager_local.record_success();
+                if config.reuse_connections {
+                    drop(ftp);
+                } else {
+                    ftp.discard();
+                }
+                break Ok(());
+            }
+        }
 
         // Upload file to FTP server
         let upload_start = std::time::Instant::now();
-        let upload_result = upload_file(&mut ftp, relative_path, &local_path, &config.remote_destination, config.respect_file_paths);
+        let upload_result = upload_file(&mut *ftp, relative_path, &local_path, config, &mut report_progress);
         
         match upload_result {
-            Ok(_local_path) => {
+            Ok(UploadOutcome { bytes_sent: bytes_uploaded, verified }) => {
                 let _ = status_tx.send(StatusUpdate {
                     stage: "Uploaded".to_string(),
                     filename: filename.clone(),
                     progress: file_progress + 0.15,
                     thread_id,
-                    file_size: initial_size.map(|s| s as u64),
+                    file_size: Some(bytes_uploaded),
+                    bytes_transferred: None,
                 });
 
-                let _ = status_tx.send(StatusUpdate {
-                    stage: "Verified".to_string(),
-                    filename: filename.clone(),
-                    progress: file_progress + 0.2,
-                    thread_id,
-                    file_size: initial_size.map(|s| s as u64),
-                });
+                // Only claim what was checked: ASCII uploads are confirmed present, not compared
+                if config.verify_uploads {
+                    let _ = status_tx.send(StatusUpdate {
+                        stage: if verified { "Verified" } else { "Not verified (ASCII)" }.to_string(),
+                        filename: filename.clone(),
+                        progress: file_progress + 0.2,
+                        thread_id,
+                        file_size: Some(bytes_uploaded),
+                        bytes_transferred: None,
+                    });
+                }
 
                 // Send structured notification for successful download (no progress bar)
                 let _ = send_notification(&config, "success", &format!("Uploaded {}", filename), Some(filename), None);
@@ -2505,7 +3638,7 @@ fn process_files(
                 let upload_time = upload_start.elapsed().as_secs_f64();
                 match session_state_local.lock() {
                     Ok(mut state) => {
-                        state.add_file_upload(initial_size.unwrap_or(0) as usize, upload_time);
+                        state.add_file_upload(bytes_uploaded as usize, upload_time);
 
                         // Debug logging for session stats
                         config_log(&config, &format!("📊 [Thread-{}] Session stats updated: {} files, {} bytes, {:.2}s, {:.2} MB/s avg",
@@ -2535,36 +3668,22 @@ fn process_files(
                     }
                 }
                 
-                // Move local file to FTPU-Sent directory after successful upload
-                let local_path = PathBuf::from(remote_dir); // remote_dir actually contains local file path
-                match move_to_sent_directory(&local_path, &config.local_source_path) {
-                    Ok(sent_path) => {
-                        config_log(&config, &format!("{} [Thread-{}] {} moved to FTPU-Sent",
-                            "📦".green(),
-                            thread_id.to_string().cyan(),
-                            filename.green()
-                        ));
-                        config_log(&config, &format!("   Sent to: {}", sent_path.display()));
-
-                        // Send success notification to Live Notifications UI
-                        let _ = send_notification(&config, "success", &format!("✅ Uploaded: {}", filename), Some(filename), None);
-                    }
-                    Err(e) => {
-                        config_log(&config, &format!("{} [Thread-{}] Failed to move {} to FTPU-Sent: {}",
-                            "⚠️".yellow(),
-                            thread_id.to_string().yellow(),
-                            filename.yellow(),
-                            e.to_string().yellow()
-                        ));
-
-                        // Send warning notification - file uploaded but couldn't be moved
-                        let _ = send_notification(&config, "warning", &format!("⚠️ Uploaded {} but failed to move to FTPU-Sent", filename), Some(filename), None);
+                // Record the content for dedupe, then apply the post-upload action
+                if let Some((hash, size)) = dedupe_content {
+                    let remote_path = remote_full_path(config, &remote_upload_name(config, relative_path, &local_path));
+                    if let Err(e) = db::record_uploaded_content(&config.config_id, hash, size, &remote_path, remote_dir) {
+                        config_log(&config, &format!("⚠️ [Thread-{}] Failed to index content of {}: {}", thread_id, filename, e));
                     }
                 }
-                
+                if finish_local_file(config, filename, &local_path, thread_id, dedupe_content.map(|(hash, _)| hash).or(scanned_hash)) {
+                    // Send success notification to Live Notifications UI
+                    let _ = send_notification(&config, "success", &format!("✅ Uploaded: {}", filename), Some(filename), None);
+                }
+
                 // Calculate download speed for this file
                 let upload_time = upload_start.elapsed().as_secs_f64();
-                let size_mb = initial_size.unwrap_or(0) as f64 / 1024.0 / 1024.0;
+                history_outcome = Some(("uploaded", upload_time));
+                let size_mb = bytes_uploaded as f64 / 1024.0 / 1024.0;
                 let speed_mbps = if upload_time > 0.0 {
                     size_mb / upload_time
                 } else {
@@ -2584,13 +3703,14 @@ fn process_files(
                     stage: "FileComplete".to_string(), // Use different stage to avoid confusion
                     filename: format!("✅ Uploaded: {} ({:.2} MB at {:.2} MB/s in {:.1}s)", 
                         filename,
-                        initial_size.unwrap_or(0) as f64 / 1024.0 / 1024.0,
+                        bytes_uploaded as f64 / 1024.0 / 1024.0,
                         speed_mbps,
                         upload_time
                     ),
                     progress: file_progress + 0.25,
                     thread_id,
-                    file_size: initial_size.map(|s| s as u64),
+                    file_size: Some(bytes_uploaded),
+                    bytes_transferred: None,
                 });
                 
                 let _ = status_tx.send(StatusUpdate {
@@ -2598,7 +3718,8 @@ fn process_files(
                     filename: filename.clone(),
                     progress: file_progress + 0.25,
                     thread_id,
-                    file_size: initial_size.map(|s| s as u64),
+                    file_size: Some(bytes_uploaded),
+                    bytes_transferred: None,
                 });
                 
                 // Increment counter
@@ -2610,8 +3731,27 @@ fn process_files(
                     files_to_process.len().to_string().yellow()
                 ));
             }
+            Err(e) if config.pause.is_aborting() => {
+                // Cut off by rust_ftp_pause; with resume_uploads the resume point lets the next attempt continue,
+                // otherwise it starts over
+                config_log(config, &format!("{} [Thread-{}] Upload of {} aborted by pause", "⏸️".yellow(), thread_id, filename.yellow()));
+                ftp.discard();
+                break Err(TransferError::new(ErrorKind::Cancelled, format!("Aborted by pause: {}", e)));
+            }
+            Err(e) if errors::classify(&*e) == ErrorKind::Vanished => {
+                // Moved or deleted locally since the scan - nothing left to upload
+                config_log(config, &format!("{} [Thread-{}] {} no longer exists locally, skipping",
+                    "⏭️".yellow(),
+                    thread_id.to_string().cyan(),
+                    filename.green()
+                ));
+                abandon_upload(&mut *ftp, config, relative_path, &local_path);
+                ftp.discard();
+                break Ok(()); // Skip this file, don't treat as error
+            }
             Err(e) => {
                 let error_msg = format!("Download failed: {}", e);
+                let kind = errors::classify(&*e);
                 config_log(&config, &format!("{} [Thread-{}] Download failed for {}: {}", 
                     "❌".red(), 
                     thread_id.to_string().red(), 
@@ -2620,7 +3760,7 @@ fn process_files(
                 ));
                 
                 // Record download failure and check if we should retry
-                let (is_server_rejection, retry_delay) = connection_manager_local.record_failure(&error_msg, config.sync_interval);
+                let (is_server_rejection, retry_delay) = connection_manager_local.record_failure(kind, &error_msg, config.sync_interval);
                 
                 let _ = status_tx.send(StatusUpdate {
                     stage: "Download failed".to_string(),
@@ -2628,15 +3768,24 @@ fn process_files(
                     progress: file_progress,
                     thread_id,
                     file_size: None,
+                    bytes_transferred: None,
                 });
                 
                 // Clean up connection and check if we should retry
-                ftp.quit().ok();
+                if kind.is_permanent() {
+                    abandon_upload(&mut *ftp, config, relative_path, &local_path);
+                }
+                ftp.discard();
                 
+                if kind.is_permanent() {
+                    config_log(config, &format!("{} [Thread-{}] Upload of {} failed permanently ({}), not retrying",
+                        "❌".red(), thread_id, filename.red(), kind));
+                    break Err(TransferError::new(kind, error_msg));
+                }
                 if connection_attempt >= max_connection_retries {
                     config_log(&config, &format!("{} [Thread-{}] Max download retries ({}) reached for {}, giving up", 
                         "❌".red(), thread_id, max_connection_retries, filename.red()));
-                    break Err(format!("Download failed after {} attempts: {}", max_connection_retries, e));
+                    break Err(TransferError::new(kind, format!("Download failed after {} attempts: {}", max_connection_retries, e)));
                 }
                 
                 config_log(&config, &format!("{} [Thread-{}] Will retry download for {} in {:.1}s (attempt {})", 
@@ -2649,7 +3798,12 @@ fn process_files(
             // Record successful connection for this file
             connection_manager_local.record_success();
             
-            ftp.quit().ok();
+            // Hand the session back for the next file (or close it when pooling is off)
+            if config.reuse_connections {
+                drop(ftp);
+            } else {
+                ftp.discard();
+            }
             config_log(&config, &format!("{} [Thread-{}] Completed processing {} (connection restored)", 
                 "🎉".green(), 
                 thread_id.to_string().cyan(), 
@@ -2658,10 +3812,36 @@ fn process_files(
             break Ok(()); // Successfully processed file, exit retry loop
         };
         
+        // Upload history (audit trail); files left for a later cycle didn't fail and get no row
+        let (outcome, duration_secs) = match (&file_result, history_outcome) {
+            (Ok(_), Some(outcome)) => outcome,
+            (Ok(_), None) => ("skipped", 0.0),
+            (Err(error), _) if matches!(error.kind, ErrorKind::Cancelled | ErrorKind::ServerUnavailable) => return file_result,
+            (Err(_), _) => ("failed", 0.0),
+        };
+        let record = db::UploadRecord {
+            local_path: remote_dir.clone(),
+            remote_path: remote_full_path(config, &remote_upload_name(config, filename, Path::new(remote_dir))),
+            file_size: history_size,
+            duration_secs,
+            speed_mbps: if outcome == "uploaded" && duration_secs > 0.0 { history_size as f64 / 1024.0 / 1024.0 / duration_secs } else { 0.0 },
+            attempts: connection_attempt,
+            outcome: outcome.to_string(),
+            error: file_result.as_ref().err().map(|e| e.message.clone()),
+            started_at: history_started.timestamp(),
+            finished_at: Utc::now().timestamp(),
+        };
+        if let Err(e) = db::record_upload(&config.config_id, &record) {
+            config_log(config, &format!("⚠️ [Thread-{}] Failed to record upload history for {}: {}", thread_id, filename, e));
+        }
+
         file_result
         }).collect()
     });  // Close pool.install() - custom thread pool execution
 
+    // Keep at most one idle session per worker for the next iteration
+    connection_manager.pool.trim(max_parallel_connections);
+
     // Close status channel
     drop(status_tx);
     
@@ -2676,7 +3856,7 @@ fn process_files(
     let failed_file_names: Vec<String> = results.iter().enumerate()
         .filter_map(|(index, result)| {
             if let Err(error) = result {
-                let (filename, _) = &files_to_process[index];
+                let (filename, _) = &files_to_upload[index];
                 Some(format!("{} ({})", filename, error))
             } else {
                 None
@@ -2684,6 +3864,41 @@ fn process_files(
         })
         .collect();
     
+    // Update the retry queue: uploaded files leave it, failed ones are rescheduled or dead-lettered
+    let succeeded_paths: Vec<&str> = results.iter().zip(&files_to_upload)
+        .filter(|(result, _)| result.is_ok())
+        .map(|(_, (_, local_path))| local_path.as_str())
+        .collect();
+    if let Err(e) = db::clear_retries(&config.config_id, &succeeded_paths) {
+        config_log(config, &format!("⚠️ Failed to update retry queue: {}", e));
+    }
+    let mut retry_notes = Vec::new();
+    for (result, (filename, local_path)) in results.iter().zip(&files_to_upload) {
+        let Err(error) = result else { continue };
+        // Not a failure of the file itself
+        if matches!(error.kind, ErrorKind::Cancelled | ErrorKind::ServerUnavailable) {
+            continue;
+        }
+        // Permanent errors go straight to the dead letters instead of being retried
+        let max_attempts = if error.kind.is_permanent() { 1 } else { config.retry_max_attempts };
+        let (file_size, mod_time) = file_identity(Path::new(local_path));
+        match db::record_failure(&config.config_id, local_path, file_size, mod_time, &error.message,
+            max_attempts, config.retry_backoff_secs) {
+            Ok(entry) if entry.dead_lettered => {
+                abandon_upload(ftp, config, filename, Path::new(local_path));
+                let message = format!("Giving up on {} after {} failed attempt(s): {}", filename, entry.attempts, error);
+                config_log(config, &format!("☠️ {}", message.red()));
+                let _ = send_notification(config, "error", &message, Some(filename), None);
+                retry_notes.push(format!("{} - gave up after {} attempts", filename, entry.attempts));
+            }
+            Ok(entry) => {
+                retry_notes.push(format!("{} - attempt {}, next in {}s",
+                    filename, entry.attempts, entry.next_attempt_at - entry.last_failed_at));
+            }
+            Err(e) => config_log(config, &format!("⚠️ Failed to queue {} for retry: {}", filename, e)),
+        }
+    }
+
     // Get final count from counter (should match successful_files)
     let final_count = files_processed.load(Ordering::SeqCst);
 
@@ -2699,17 +3914,20 @@ fn process_files(
     
     // Log failed files if any
     if !failed_file_names.is_empty() {
-        config_log(&config, &format!("{} Failed files will be retried in next cycle:", "🔄".yellow()));
+        config_log(&config, &format!("{} Failed files:", "🔄".yellow()));
         for failed_file in &failed_file_names {
             config_log(&config, &format!("  ❌ {}", failed_file.red()));
         }
+        for note in &retry_notes {
+            config_log(config, &format!("  ⏳ {}", note));
+        }
     }
     
     config_log(&config, &format!("{}", "=".repeat(80).blue()));
     
     // Send completion status with clear success/failure breakdown
     let status_message = if failed_files > 0 {
-        format!("Processed {}/{} files ({} failed, will retry next cycle)", successful_files, files_to_process.len(), failed_files)
+        format!("Processed {}/{} files ({} failed, queued for retry)", successful_files, files_to_process.len(), failed_files)
     } else {
         format!("Processed {} files successfully", successful_files)
     };
@@ -2732,6 +3950,35 @@ fn process_files(
     Ok(successful_files)
 }
 
+// Whether a failed connect or login means the server is down, for the circuit breaker.
+// A refused connection or a 421 while other sessions are open is the session limit: it
+// only lowers the worker count.
+fn is_server_down(error: &ftp::types::FtpError, kind: ErrorKind, other_sessions: usize) -> bool {
+    if errors::is_connection_refused(error) || kind == ErrorKind::ServerBusy {
+        other_sessions == 0
+    } else {
+        kind.is_network()
+    }
+}
+
+// Tell the user once when the server becomes unavailable and once when it is back
+fn report_circuit_transition(config: &FTPConfig, transition: CircuitTransition) {
+    match transition {
+        CircuitTransition::Opened => {
+            let message = format!("Server {} unavailable after {} failed connection attempts - pausing connections, probing every {}s",
+                config.server_address, CIRCUIT_FAILURE_THRESHOLD, CIRCUIT_COOLDOWN.as_secs());
+            config_log(config, &format!("{} {}", "🚫".red(), message));
+            let _ = send_notification(config, "error", &message, None, None);
+        }
+        CircuitTransition::Recovered => {
+            let message = format!("Server {} recovered - resuming uploads", config.server_address);
+            config_log(config, &format!("{} {}", "✅".green(), message));
+            let _ = send_notification(config, "success", &message, None, None);
+        }
+        CircuitTransition::Unchanged => {}
+    }
+}
+
 fn send_status(status_file: &str, config: &FTPConfig, stage: &str, filename: &str, progress: f64, file_size: Option<u64>) -> Result<(), Box<dyn std::error::Error>> {
     send_status_with_speed(status_file, config, stage, filename, progress, file_size, None, None)
 }
@@ -2780,6 +4027,7 @@ fn send_status_with_speed(status_file: &str, config: &FTPConfig, stage: &str, fi
             .duration_since(std::time::UNIX_EPOCH)?
             .as_secs(),
         file_size,
+        bytes_transferred: None,
         upload_speed_mbps,
         upload_time_secs,
     };
@@ -2840,34 +4088,164 @@ fn write_result(result_file: &str, config: &FTPConfig, success: bool, message: &
     Ok(())
 }
 
-// Move file to FTPU-Sent directory after successful upload
-fn move_to_sent_directory(local_path: &PathBuf, base_dir: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let base_path = PathBuf::from(base_dir);
-    let sent_dir = base_path.join("FTPU-Sent");
+// Post-upload action (move to FTPU-Sent, archive, delete, rename or leave in place) for a file that is on the server now.
+// Returns false if the action failed; the file stays where it is and a warning is sent.
+fn finish_local_file(config: &FTPConfig, filename: &str, local_path: &Path, thread_id: u64, known_content_hash: Option<u64>) -> bool {
+    match config.disposition.apply(local_path, Path::new(&config.local_source_path)) {
+        Ok(disposition) => {
+            config_log(config, &format!("{} [Thread-{}] {} {}",
+                "📦".green(),
+                thread_id.to_string().cyan(),
+                filename.green(),
+                disposition
+            ));
+
+            // Retention only ever prunes files recorded here, aged from this moment
+            if let Disposition::Moved(dest) = &disposition {
+                let size = fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+                if let Err(e) = db::record_sent_files(&config.config_id, &[(dest.to_string_lossy().to_string(), size)]) {
+                    config_log(config, &format!("⚠️ [Thread-{}] Failed to record {} for retention: {}", thread_id, filename, e));
+                }
+            }
 
-    // Create FTPU-Sent directory if it doesn't exist
-    if !sent_dir.exists() {
-        fs::create_dir_all(&sent_dir)?;
+            // Keep mode: remember this version so it is only uploaded again once it changes
+            if config.post_upload_action == PostUploadAction::Leave {
+                if let (Some(hash), Ok(metadata)) = (keep_mode_hash(config, filename, local_path), fs::metadata(local_path)) {
+                    let mod_time = metadata.modified().map(chrono::DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+                    let content = match config.change_detection {
+                        ChangeDetection::Content => known_content_hash.or_else(|| content_hash(local_path).ok()),
+                        ChangeDetection::Metadata => None,
+                    };
+                    if let Err(e) = db::save_hash(&config.config_id, &config.local_source_path, filename, metadata.len(), mod_time, hash, content) {
+                        config_log(config, &format!("⚠️ [Thread-{}] Failed to record {} as sent: {}", thread_id, filename, e));
+                    }
+                }
+            }
+            true
+        }
+        Err(e) => {
+            config_log(config, &format!("{} [Thread-{}] Uploaded {} but post-upload action {:?} failed: {}",
+                "⚠️".yellow(),
+                thread_id.to_string().yellow(),
+                filename.yellow(),
+                config.post_upload_action,
+                e.to_string().yellow()
+            ));
+
+            // Send warning notification - file uploaded but couldn't be moved
+            let _ = send_notification(config, "warning", &format!("⚠️ Uploaded {} but failed to {:?} it", filename, config.post_upload_action), Some(filename), None);
+            false
+        }
     }
+}
 
-    // Preserve directory structure within FTPU-Sent
-    let relative_path = local_path.strip_prefix(&base_path)
-        .unwrap_or(local_path.as_path());
-    let dest_path = sent_dir.join(relative_path);
+// Remote name of an upload, relative to remote_destination
+fn remote_upload_name(config: &FTPConfig, filename: &str, local_path: &Path) -> String {
+    if config.respect_file_paths {
+        // Preserve directory structure - extract relative path from local_path
+        filename.to_string()
+    } else {
+        // Flat structure - just use filename
+        local_path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| filename.to_string())
+    }
+}
 
-    // Create parent directories in FTPU-Sent if needed
-    if let Some(parent) = dest_path.parent() {
-        fs::create_dir_all(parent)?;
+// Absolute remote path for a name relative to remote_destination
+fn remote_full_path(config: &FTPConfig, remote_name: &str) -> String {
+    format!("{}/{}", config.remote_destination.trim_end_matches('/'), remote_name)
+}
+
+// Dedupe: content being uploaded right now, so identical files in the same batch wait for the
+// first copy to finish and then find it in the index instead of uploading in parallel
+#[derive(Default)]
+struct InFlightContent {
+    keys: Mutex<std::collections::HashSet<(u64, u64)>>,
+}
+
+struct InFlightClaim<'a> {
+    owner: &'a InFlightContent,
+    key: (u64, u64),
+}
+
+impl InFlightContent {
+    fn claim(&self, content_hash: u64, file_size: u64) -> InFlightClaim<'_> {
+        let key = (content_hash, file_size);
+        loop {
+            if self.keys.lock().unwrap().insert(key) {
+                return InFlightClaim { owner: self, key };
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+}
+
+impl Drop for InFlightClaim<'_> {
+    fn drop(&mut self) {
+        self.owner.keys.lock().unwrap().remove(&self.key);
+    }
+}
+
+// Dedupe: allowance for the server's clock running ahead of ours when comparing MDTM to the upload time
+const DEDUPE_CLOCK_SKEW_SECS: i64 = 300;
+
+// Dedupe: remote path of an earlier upload with the same bytes that is still on the server
+fn find_remote_duplicate(ftp: &mut dyn Transport, config: &FTPConfig, local_path: &PathBuf, content_hash: u64, file_size: u64) -> Option<String> {
+    let (original, uploaded_at) = match db::find_uploaded_content(&config.config_id, content_hash, file_size) {
+        Ok(original) => original?,
+        Err(e) => {
+            config_log(config, &format!("⚠️ Dedupe lookup failed: {}", e));
+            return None;
+        }
+    };
+
+    // The earlier upload may have been removed from the server since
+    match ftp.size(&original) {
+        Ok(Some(size)) if size as u64 == file_size => {}
+        _ => return None,
+    }
+
+    // Someone may have replaced it with other bytes of the same length: compare digests when
+    // the server has one, otherwise require that it was not modified after we uploaded it
+    if let Ok(Some((algorithm, remote_digest))) = ftp.checksum(&original) {
+        return match local_checksum(local_path, algorithm, config.upload_buffer_size.max(8 * 1024)) {
+            Ok(local_digest) if local_digest == remote_digest => Some(original),
+            _ => None,
+        };
+    }
+    match ftp.mdtm(&original) {
+        Ok(Some(modified)) if modified <= uploaded_at + DEDUPE_CLOCK_SKEW_SECS => Some(original),
+        _ => None,
     }
+}
 
-    // Move the file
-    fs::rename(local_path, &dest_path)?;
+// Dedupe: make `target` available without uploading it; returns the action taken, or None to upload normally
+fn resolve_duplicate(ftp: &mut dyn Transport, config: &FTPConfig, original: &str, target: &str) -> Option<&'static str> {
+    if original == target || config.dedupe == DedupeMode::Skip {
+        return Some("skipped");
+    }
 
-    Ok(dest_path)
+    if let Some((parent, _)) = target.rsplit_once('/') {
+        if !parent.is_empty() {
+            let _ = create_remote_directory(ftp, parent);
+        }
+    }
+    match ftp.server_copy(original, target) {
+        Ok(true) => Some("server_copy"),
+        Ok(false) => {
+            config_log(config, &format!("ℹ️ Server cannot copy files, uploading {} normally", target));
+            None
+        }
+        Err(e) => {
+            config_log(config, &format!("⚠️ Server-side copy of {} to {} failed ({}), uploading normally", original, target, e));
+            None
+        }
+    }
 }
 
 // Create remote directory on FTP server (recursive mkdir)
-fn create_remote_directory(ftp: &mut ftp::FtpStream, remote_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn create_remote_directory(ftp: &mut dyn Transport, remote_path: &str) -> Result<(), Box<dyn std::error::Error>> {
     // Split path into components and create each level
     let components: Vec<&str> = remote_path.trim_matches('/').split('/').filter(|s| !s.is_empty()).collect();
 
@@ -2898,8 +4276,189 @@ fn create_remote_directory(ftp: &mut ftp::FtpStream, remote_path: &str) -> Resul
     Ok(())
 }
 
+// Reader wrapper that streams a local file and reports progress once per buffer-sized chunk
+struct ProgressReader<'a, R: std::io::Read> {
+    inner: R,
+    bytes_read: u64,
+    last_reported: u64,
+    chunk_size: u64,
+    on_progress: &'a mut dyn FnMut(u64),
+    pause: &'a PauseControl,
+}
+
+impl<R: std::io::Read> std::io::Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pause.is_aborting() {
+            return Err(std::io::Error::other("upload aborted by pause"));
+        }
+        let n = self.inner.read(buf)?;
+        self.bytes_read += n as u64;
+
+        // Report every full chunk, plus the final partial chunk at EOF
+        if self.bytes_read - self.last_reported >= self.chunk_size || (n == 0 && self.bytes_read > self.last_reported) {
+            self.last_reported = self.bytes_read;
+            (self.on_progress)(self.bytes_read);
+        }
+        Ok(n)
+    }
+}
+
+// Works out where an interrupted upload of this file can continue from (0 = start over)
+fn find_resume_offset(ftp: &mut dyn Transport, config: &FTPConfig, local_key: &str, remote_filename: &str, file_size: u64, mod_time: i64) -> u64 {
+    let point = match db::get_resume_point(&config.config_id, local_key) {
+        Ok(Some(point)) => point,
+        _ => return 0,
+    };
+
+    // The local file changed since the interrupted attempt, or now goes somewhere else
+    if point.file_size != file_size || point.mod_time != mod_time || point.remote_path != remote_filename {
+        return 0;
+    }
+
+    // The server's copy is authoritative - it may hold less than we managed to send
+    match ftp.size(remote_filename) {
+        Ok(Some(remote_size)) if remote_size > 0 && (remote_size as u64) < file_size => {
+            config_log(config, &format!("⏯️ Resuming {} at byte {} of {} (last attempt reached {})",
+                remote_filename.cyan(), remote_size, file_size, point.offset));
+            remote_size as u64
+        }
+        // Fully transferred, but publishing it failed - only the rename is left to do
+        Ok(Some(remote_size)) if remote_size > 0 && remote_size as u64 == file_size && point.offset == file_size => {
+            config_log(config, &format!("⏯️ {} was already transferred completely, publishing it", remote_filename.cyan()));
+            file_size
+        }
+        _ => 0,
+    }
+}
+
+// Temporary name used while an atomic upload is in flight, in the same remote directory
+fn temp_upload_name(remote_filename: &str, config: &FTPConfig) -> String {
+    let (dir, name) = match remote_filename.rfind('/') {
+        Some(idx) => remote_filename.split_at(idx + 1),
+        None => ("", remote_filename),
+    };
+    format!("{}{}{}{}", dir, config.temp_upload_prefix, name, config.temp_upload_suffix)
+}
+
+// Give up on a file for good: delete its partial temporary upload and forget its resume point
+fn abandon_upload(ftp: &mut dyn Transport, config: &FTPConfig, filename: &str, local_path: &Path) {
+    if config.atomic_uploads {
+        let temp_name = temp_upload_name(&remote_upload_name(config, filename, local_path), config);
+        // Usually there is none - only an interrupted resumable upload leaves one behind
+        let _ = ftp.rm(&remote_full_path(config, &temp_name));
+    }
+    let _ = db::clear_resume_point(&config.config_id, &local_path.to_string_lossy());
+}
+
+// Move a completed temporary upload to its final name
+fn publish_upload(ftp: &mut dyn Transport, temp_name: &str, remote_filename: &str) -> ftp::types::Result<()> {
+    match ftp.rename(temp_name, remote_filename) {
+        // Some servers refuse to rename over an existing file - replace it explicitly.
+        // Anything else (dropped connection, 4xx) must not cost us the existing file.
+        Err(e) if rename_target_rejected(&e) => {
+            ftp.rm(remote_filename)?;
+            ftp.rename(temp_name, remote_filename)
+        }
+        result => result,
+    }
+}
+
+// RNFR was accepted (the reply that failed is the one expected to be 250) and RNTO was refused with 550/553
+fn rename_target_rejected(error: &ftp::types::FtpError) -> bool {
+    matches!(error, ftp::types::FtpError::InvalidResponse(message) if message.starts_with("Expected code [250]"))
+        && matches!(errors::reply_code(error), Some(550 | 553))
+}
+
+// Digest of a local file as lowercase hex, streamed in `buffer_size` chunks
+fn local_checksum(path: &PathBuf, algorithm: ChecksumAlgorithm, buffer_size: usize) -> std::io::Result<String> {
+    use std::io::Read;
+    use md5::Digest;
+
+    let mut file = std::io::BufReader::with_capacity(buffer_size, fs::File::open(path)?);
+    let mut buffer = vec![0u8; buffer_size];
+    let mut crc = crc32fast::Hasher::new();
+    let mut md5 = md5::Md5::new();
+    let mut sha256 = sha2::Sha256::new();
+
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        match algorithm {
+            ChecksumAlgorithm::Crc32 => crc.update(&buffer[..n]),
+            ChecksumAlgorithm::Md5 => md5.update(&buffer[..n]),
+            ChecksumAlgorithm::Sha256 => sha256.update(&buffer[..n]),
+        }
+    }
+
+    let to_hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    Ok(match algorithm {
+        ChecksumAlgorithm::Crc32 => format!("{:08x}", crc.finalize()),
+        ChecksumAlgorithm::Md5 => to_hex(&md5.finalize()),
+        ChecksumAlgorithm::Sha256 => to_hex(&sha256.finalize()),
+    })
+}
+
+// Checks the uploaded copy against the local file: SIZE always, plus a server-side
+// digest when the server offers one. Returns a short description of what was checked.
+fn verify_upload(ftp: &mut dyn Transport, config: &FTPConfig, upload_name: &str, local_path: &PathBuf, file_size: u64) -> Result<String, String> {
+    let mut checks = Vec::new();
+
+    match ftp.size(upload_name) {
+        Ok(Some(remote_size)) if remote_size as u64 == file_size => checks.push(format!("size {}", file_size)),
+        Ok(Some(remote_size)) => {
+            return Err(format!("size mismatch: server has {} bytes, local file has {}", remote_size, file_size));
+        }
+        Ok(None) => return Err("server returned an unreadable SIZE reply".to_string()),
+        // Command not implemented - the server simply cannot tell us
+        Err(e) if matches!(errors::reply_code(&e), Some(500 | 502 | 504)) => checks.push("size unsupported".to_string()),
+        Err(e) => return Err(format!("SIZE failed: {}", e)),
+    }
+
+    if config.verify_checksums {
+        match ftp.checksum(upload_name) {
+            Ok(Some((algorithm, remote_digest))) => {
+                let local_digest = local_checksum(local_path, algorithm, config.upload_buffer_size.max(8 * 1024))
+                    .map_err(|e| format!("could not hash local file: {}", e))?;
+                if remote_digest != local_digest {
+                    return Err(format!("{:?} mismatch: server {} vs local {}", algorithm, remote_digest, local_digest));
+                }
+                checks.push(format!("{:?} {}", algorithm, local_digest));
+            }
+            Ok(None) => {}
+            Err(e) if matches!(errors::reply_code(&e), Some(500 | 502 | 504)) => {}
+            Err(e) => return Err(format!("checksum failed: {}", e)),
+        }
+    }
+
+    Ok(checks.join(", "))
+}
+
+// ASCII transfers rewrite line endings, so neither the size nor a digest can match the local
+// file; only confirm the server has the file and that it is not empty when the local one isn't
+fn verify_text_upload(ftp: &mut dyn Transport, upload_name: &str, file_size: u64) -> Result<String, String> {
+    match ftp.size(upload_name) {
+        Ok(Some(0)) if file_size > 0 => Err(format!("server has an empty file, local file has {} bytes", file_size)),
+        Ok(Some(remote_size)) => Ok(format!("present, {} bytes after line-ending conversion", remote_size)),
+        Ok(None) => Err("server returned an unreadable SIZE reply".to_string()),
+        Err(e) if matches!(errors::reply_code(&e), Some(500 | 502 | 504)) => Ok("size unsupported".to_string()),
+        Err(e) => Err(format!("SIZE failed: {}", e)),
+    }
+}
+
+// What an upload attempt achieved
+struct UploadOutcome {
+    bytes_sent: u64, // bytes sent by this attempt
+    verified: bool,  // the server's copy was checked against the local file's size / digest
+}
+
 // Helper function to upload files to FTP server
-fn upload_file(ftp: &mut ftp::FtpStream, filename: &str, local_path: &PathBuf, remote_dir: &str, respect_file_paths: bool) -> Result<PathBuf, Box<dyn std::error::Error>> {
+// Uploads a local file, streaming it from disk in `upload_buffer_size` chunks and resuming
+// a previously interrupted transfer when possible
+fn upload_file(ftp: &mut dyn Transport, filename: &str, local_path: &PathBuf, config: &FTPConfig, on_progress: &mut dyn FnMut(u64)) -> Result<UploadOutcome, Box<dyn std::error::Error>> {
+    let remote_dir = config.remote_destination.as_str();
+    let respect_file_paths = config.respect_file_paths;
     println!("🔍 UPLOAD DEBUG: Starting upload_file for {} to {}", filename, remote_dir);
 
     // Detect if file is likely text or binary based on extension
@@ -2917,15 +4476,7 @@ fn upload_file(ftp: &mut ftp::FtpStream, filename: &str, local_path: &PathBuf, r
     }
 
     // Determine remote path based on respect_file_paths setting
-    let remote_filename = if respect_file_paths {
-        // Preserve directory structure - extract relative path from local_path
-        filename.to_string()
-    } else {
-        // Flat structure - just use filename
-        local_path.file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| filename.to_string())
-    };
+    let remote_filename = remote_upload_name(config, filename, local_path);
 
     // Create parent directories if respect_file_paths is enabled and filename contains path
     if respect_file_paths && remote_filename.contains('/') {
@@ -2967,64 +4518,132 @@ fn upload_file(ftp: &mut ftp::FtpStream, filename: &str, local_path: &PathBuf, r
         }
     }
 
-    // Read local file
-    let file_data = fs::read(local_path)?;
-    let file_size = file_data.len();
+    // Stream the local file instead of loading it into memory
+    let buffer_size = config.upload_buffer_size.max(8 * 1024);
+    let mut file = fs::File::open(local_path)?;
+    let metadata = file.metadata()?;
+    let file_size = metadata.len();
+    let mod_time = metadata.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    // Atomic mode writes to a temporary name so nobody sees a half-written file
+    let upload_name = if config.atomic_uploads {
+        temp_upload_name(&remote_filename, config)
+    } else {
+        remote_filename.clone()
+    };
+
+    // Only binary transfers can resume - ASCII mode rewrites line endings, so offsets differ
+    let resumable = config.resume_uploads && !is_text_file;
+    let local_key = local_path.to_string_lossy().to_string();
+    let resume_offset = if resumable {
+        find_resume_offset(ftp, config, &local_key, &upload_name, file_size, mod_time)
+    } else {
+        0
+    };
+    if resume_offset > 0 {
+        use std::io::Seek;
+        file.seek(std::io::SeekFrom::Start(resume_offset))?;
+    }
+
+    // Record the upload as in flight so a restart of the app can pick it up
+    let mut resume_point = db::ResumePoint {
+        remote_path: upload_name.clone(),
+        file_size,
+        mod_time,
+        offset: resume_offset,
+    };
+    if resumable {
+        if let Err(e) = db::save_resume_point(&config.config_id, &local_key, &resume_point) {
+            println!("⚠️ UPLOAD DEBUG: Could not record resume point for {}: {}", upload_name, e);
+        }
+    }
 
-    println!("🔍 UPLOAD DEBUG: Read {} bytes from local file {}", file_size, local_path.display());
-    println!("🔍 UPLOAD DEBUG: About to send STOR command for {}", remote_filename);
+    println!("🔍 UPLOAD DEBUG: Streaming {} bytes from local file {} ({} byte chunks)", file_size - resume_offset, local_path.display(), buffer_size);
+    println!("🔍 UPLOAD DEBUG: About to send {} command for {}", if resume_offset > 0 { "REST+STOR" } else { "STOR" }, upload_name);
 
-    // Upload file using put()
-    let mut cursor = std::io::Cursor::new(file_data);
-    match ftp.put(&remote_filename, &mut cursor) {
+    let mut reader = ProgressReader {
+        inner: config.throttle.reader(std::io::BufReader::with_capacity(buffer_size, file)),
+        bytes_read: resume_offset,
+        last_reported: resume_offset,
+        chunk_size: buffer_size as u64,
+        on_progress,
+        pause: &config.pause,
+    };
+    let put_result = if resume_offset == file_size && file_size > 0 {
+        Ok(())
+    } else if resume_offset > 0 {
+        ftp.resume_put(&upload_name, &mut reader, resume_offset)
+    } else {
+        ftp.put(&upload_name, &mut reader)
+    };
+    match put_result {
         Ok(_) => {
-            println!("🔍 UPLOAD DEBUG: STOR successful for {}, uploaded {} bytes", remote_filename, file_size);
+            println!("🔍 UPLOAD DEBUG: STOR successful for {}, uploaded {} bytes", upload_name, reader.bytes_read - resume_offset);
+            if resumable {
+                let _ = db::clear_resume_point(&config.config_id, &local_key);
+            }
         },
         Err(e) => {
-            println!("❌ UPLOAD DEBUG: STOR FAILED for {}: {}", remote_filename, e);
+            println!("❌ UPLOAD DEBUG: STOR FAILED for {} after {} bytes: {}", upload_name, reader.bytes_read, e);
+            if resumable {
+                // The partial temp file stays on the server: it is what the next attempt resumes
+                // (abandon_upload removes it if the file fails permanently or is dead-lettered)
+                resume_point.offset = reader.bytes_read;
+                let _ = db::save_resume_point(&config.config_id, &local_key, &resume_point);
+            } else if config.atomic_uploads {
+                // Nothing will resume this partial file - don't leave it behind
+                let _ = ftp.rm(&upload_name);
+            }
             return Err(Box::new(e));
         }
     };
 
-    // Reset to binary mode for next file
+    // Reset to binary mode for next file (and so SIZE/digests count raw bytes)
     ftp.transfer_type(ftp::types::FileType::Binary)?;
 
-    Ok(local_path.clone())
-}
-
-// Helper function to get unique filename (append _# if file exists)
-fn get_unique_filename(path: &PathBuf) -> PathBuf {
-    if !path.exists() {
-        return path.clone();
-    }
-    
-    let mut counter = 1;
-    let stem = path.file_stem().unwrap().to_string_lossy();
-    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
-    
-    loop {
-        let new_name = format!("{}_{}{}", stem, counter, extension);
-        let new_path = path.with_file_name(new_name);
-        
-        if !new_path.exists() {
-            return new_path;
+    // Never report (or publish) a file the server did not receive intact
+    let verified = if config.verify_uploads {
+        let verification = if is_text_file {
+            verify_text_upload(ftp, &upload_name, file_size)
+        } else {
+            verify_upload(ftp, config, &upload_name, local_path, file_size)
+        };
+        match verification {
+            Ok(summary) => {
+                println!("✅ UPLOAD DEBUG: {} {} ({})", if is_text_file { "Checked" } else { "Verified" }, upload_name, summary);
+                !is_text_file
+            }
+            Err(e) => {
+                println!("❌ UPLOAD DEBUG: Verification FAILED for {}: {}", upload_name, e);
+                // Remove the bad copy so the retry starts from scratch instead of resuming onto it
+                let _ = ftp.rm(&upload_name);
+                return Err(e.into());
+            }
         }
-        
-        counter += 1;
-        
-        // Prevent infinite loop (max 999 files)
-        if counter > 999 {
-            break;
+    } else {
+        false
+    };
+
+    if config.atomic_uploads {
+        println!("🔍 UPLOAD DEBUG: Publishing {} as {}", upload_name, remote_filename);
+        if let Err(e) = publish_upload(ftp, &upload_name, &remote_filename) {
+            println!("❌ UPLOAD DEBUG: RNFR/RNTO FAILED for {}: {}", upload_name, e);
+            // Keep the complete temp file - the next attempt only has to rename it
+            if resumable {
+                resume_point.offset = file_size;
+                let _ = db::save_resume_point(&config.config_id, &local_key, &resume_point);
+            }
+            return Err(Box::new(e));
         }
     }
-    
-    // Fallback: append timestamp
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let new_name = format!("{}_{}{}", stem, timestamp, extension);
-    path.with_file_name(new_name)
+
+    Ok(UploadOutcome {
+        bytes_sent: reader.bytes_read - resume_offset,
+        verified,
+    })
 }
 
 // Helper function to detect if a file is likely text
@@ -3059,5 +4678,334 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }).expect("Error setting Ctrl-C handler");
 
     // Run the FTP engine
-    run_ftp_with_args(args, shutdown_flag)
+    run_ftp_with_args(args, shutdown_flag, Arc::new(RateLimiter::new(0)), Arc::new(PauseControl::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::memory::MemoryTransport;
+
+    /// Fresh database for one test config
+    fn init_test_database(config_id: &str) {
+        let path = std::env::temp_dir().join(format!("ftpu-engine-{}-{}.db", config_id, std::process::id()));
+        let _ = fs::remove_file(&path);
+        db::init_database(config_id, &path).unwrap();
+    }
+
+    /// Config uploading to /up, with its own database
+    fn test_config(config_id: &str, atomic: bool) -> FTPConfig {
+        init_test_database(config_id);
+        serde_json::from_value(serde_json::json!({
+            "server_address": "memory", "port": 21, "username": "user", "password": "secret",
+            "remote_destination": "/up", "local_source_path": std::env::temp_dir(),
+            "respect_file_paths": false, "sync_interval": 1, "stabilization_interval": 0,
+            "upload_aggressiveness": 1, "auto_tune_aggressiveness": false,
+            "config_id": config_id, "config_name": config_id, "session_id": config_id,
+            "atomic_uploads": atomic, "resume_uploads": true, "verify_uploads": true, "verify_checksums": true,
+        }))
+        .unwrap()
+    }
+
+    /// Local file with `len` bytes of non-repeating content
+    fn local_file(name: &str, len: usize) -> (PathBuf, Vec<u8>) {
+        let dir = std::env::temp_dir().join(format!("ftpu-engine-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let data: Vec<u8> = (0..len).map(|i| (i * 7 % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+        (path, data)
+    }
+
+    fn upload(ftp: &mut MemoryTransport, name: &str, path: &PathBuf, config: &FTPConfig) -> Result<UploadOutcome, Box<dyn std::error::Error>> {
+        upload_file(ftp, name, path, config, &mut |_| {})
+    }
+
+    #[test]
+    fn uploads_and_verifies_in_place() {
+        let config = test_config("plain", false);
+        let (path, data) = local_file("plain.bin", 50_000);
+        let mut ftp = MemoryTransport::new("/up");
+        ftp.checksums = true;
+
+        let outcome = upload(&mut ftp, "plain.bin", &path, &config).unwrap();
+        assert_eq!(outcome.bytes_sent, 50_000);
+        assert!(outcome.verified);
+        assert_eq!(ftp.file("/up/plain.bin"), Some(&data));
+        assert_eq!(ftp.commands, vec!["STOR /up/plain.bin"]);
+    }
+
+    #[test]
+    fn atomic_upload_is_published_by_rename() {
+        let config = test_config("atomic", true);
+        let (path, data) = local_file("atomic.bin", 1_000);
+        let mut ftp = MemoryTransport::new("/up");
+
+        upload(&mut ftp, "atomic.bin", &path, &config).unwrap();
+        let temp = format!("/up/{}", temp_upload_name("atomic.bin", &config));
+        assert_eq!(ftp.commands, vec![format!("STOR {}", temp), format!("RNFR {} RNTO /up/atomic.bin", temp)]);
+        assert_eq!(ftp.file("/up/atomic.bin"), Some(&data));
+        assert_eq!(ftp.file(&temp), None);
+    }
+
+    #[test]
+    fn interrupted_upload_resumes_where_the_server_stopped() {
+        let config = test_config("resume", true);
+        let (path, data) = local_file("resume.bin", 40_000);
+        let temp = format!("/up/{}", temp_upload_name("resume.bin", &config));
+        let mut ftp = MemoryTransport::new("/up");
+
+        ftp.drop_after = Some(12_345);
+        assert!(upload(&mut ftp, "resume.bin", &path, &config).is_err());
+        assert_eq!(ftp.file(&temp).map(Vec::len), Some(12_345), "partial temp file is kept for the next attempt");
+
+        let outcome = upload(&mut ftp, "resume.bin", &path, &config).unwrap();
+        assert_eq!(outcome.bytes_sent, 40_000 - 12_345);
+        assert_eq!(ftp.commands[1], format!("REST 12345 {}", temp));
+        assert_eq!(ftp.file("/up/resume.bin"), Some(&data));
+        assert!(db::get_resume_point("resume", &path.to_string_lossy()).unwrap().is_none());
+    }
+
+    #[test]
+    fn corrupt_upload_is_removed_and_not_published() {
+        let config = test_config("corrupt", true);
+        let (path, _) = local_file("corrupt.bin", 1_000);
+        let mut ftp = MemoryTransport::new("/up");
+        ftp.checksums = true;
+        ftp.corrupt = true;
+
+        let error = match upload(&mut ftp, "corrupt.bin", &path, &config) {
+            Ok(_) => panic!("a corrupt upload must fail verification"),
+            Err(error) => error,
+        };
+        assert!(error.to_string().contains("mismatch"), "{}", error);
+        assert!(ftp.files.is_empty(), "neither the bad temp file nor a published copy may remain");
+    }
+
+    /// Server holding `remote` with `len` bytes, last modified a minute before it was recorded in the dedupe index
+    fn dedupe_setup(config_id: &str, remote: &str, len: usize, same_bytes: bool) -> (FTPConfig, PathBuf, MemoryTransport) {
+        let config = test_config(config_id, false);
+        let (path, data) = local_file(&format!("{}.bin", config_id), len);
+        let mut ftp = MemoryTransport::new("/up");
+        ftp.files.insert(remote.to_string(), if same_bytes { data } else { vec![0; len] });
+        ftp.mtimes.insert(remote.to_string(), Utc::now().timestamp() - 60);
+        db::record_uploaded_content(config_id, 42, len as u64, remote, &path.to_string_lossy()).unwrap();
+        (config, path, ftp)
+    }
+
+    #[test]
+    fn duplicate_is_confirmed_by_digest() {
+        let (config, path, mut ftp) = dedupe_setup("dup-digest", "/up/a.bin", 500, true);
+        ftp.checksums = true;
+        assert_eq!(find_remote_duplicate(&mut ftp, &config, &path, 42, 500), Some("/up/a.bin".to_string()));
+
+        let (config, path, mut ftp) = dedupe_setup("dup-digest-differs", "/up/b.bin", 500, false);
+        ftp.checksums = true;
+        assert_eq!(find_remote_duplicate(&mut ftp, &config, &path, 42, 500), None, "same size is not the same bytes");
+    }
+
+    #[test]
+    fn without_digest_duplicate_must_predate_its_record() {
+        let (config, path, mut ftp) = dedupe_setup("dup-mdtm", "/up/c.bin", 500, false);
+        assert_eq!(find_remote_duplicate(&mut ftp, &config, &path, 42, 500), Some("/up/c.bin".to_string()));
+
+        ftp.mtimes.insert("/up/c.bin".to_string(), Utc::now().timestamp() + DEDUPE_CLOCK_SKEW_SECS + 60);
+        assert_eq!(find_remote_duplicate(&mut ftp, &config, &path, 42, 500), None, "replaced after we uploaded it");
+
+        ftp.files.insert("/up/c.bin".to_string(), vec![0; 499]);
+        ftp.mtimes.insert("/up/c.bin".to_string(), Utc::now().timestamp() - 60);
+        assert_eq!(find_remote_duplicate(&mut ftp, &config, &path, 42, 500), None, "size changed");
+    }
+
+    #[test]
+    fn text_upload_is_checked_but_not_verified() {
+        let config = test_config("text", false);
+        let (path, _) = local_file("notes.txt", 100);
+        let mut ftp = MemoryTransport::new("/up");
+
+        let outcome = upload(&mut ftp, "notes.txt", &path, &config).unwrap();
+        assert!(!outcome.verified);
+        assert!(ftp.file("/up/notes.txt").is_some());
+    }
+
+    /// One upload phase that moved `mb_per_sec` MB in a second; returns the tuner's next count
+    fn measure(tuner: &mut AutoTuner, state: &Mutex<SessionState>, manager: &ConnectionManager, mb_per_sec: f64) -> usize {
+        {
+            let mut state = state.lock().unwrap();
+            state.total_files += 4;
+            state.total_bytes += (mb_per_sec * 1024.0 * 1024.0) as usize;
+        }
+        tuner.upload_phase_finished(Duration::from_secs(1));
+        tuner.next_connection_count(state, manager).0
+    }
+
+    fn server_rejection(manager: &ConnectionManager) {
+        manager.record_failure(ErrorKind::ServerBusy, "421 Too many connections", 60.0);
+    }
+
+    #[test]
+    fn auto_tune_grows_while_throughput_rises() {
+        let (state, manager) = (Mutex::new(SessionState::new()), ConnectionManager::new());
+        let mut tuner = AutoTuner::new(4);
+
+        assert_eq!(measure(&mut tuner, &state, &manager, 10.0), 5);
+        assert_eq!(measure(&mut tuner, &state, &manager, 12.0), 6);
+        assert_eq!(measure(&mut tuner, &state, &manager, 14.0), 7);
+        // Within 5% is noise: hold
+        assert_eq!(measure(&mut tuner, &state, &manager, 14.5), 7);
+    }
+
+    #[test]
+    fn auto_tune_reverses_when_throughput_drops() {
+        let (state, manager) = (Mutex::new(SessionState::new()), ConnectionManager::new());
+        let mut tuner = AutoTuner::new(8);
+
+        assert_eq!(measure(&mut tuner, &state, &manager, 10.0), 10);
+        // More than 5% slower: the last step hurt, so go back down
+        assert_eq!(measure(&mut tuner, &state, &manager, 9.0), 8);
+        // A smaller dip is not a signal
+        assert_eq!(measure(&mut tuner, &state, &manager, 8.7), 8);
+        // Shrinking paid off: keep shrinking
+        assert_eq!(measure(&mut tuner, &state, &manager, 10.0), 6);
+    }
+
+    #[test]
+    fn auto_tune_needs_enough_transfers() {
+        let (state, manager) = (Mutex::new(SessionState::new()), ConnectionManager::new());
+        let mut tuner = AutoTuner::new(4);
+
+        state.lock().unwrap().total_files = 1;
+        tuner.upload_phase_finished(Duration::from_secs(1));
+        assert_eq!(tuner.next_connection_count(&state, &manager).0, 4);
+    }
+
+    #[test]
+    fn auto_tune_halves_and_caps_on_rejections() {
+        let (state, manager) = (Mutex::new(SessionState::new()), ConnectionManager::new());
+        let mut tuner = AutoTuner::new(10);
+
+        server_rejection(&manager);
+        assert_eq!(tuner.next_connection_count(&state, &manager).0, 5);
+        assert_eq!(tuner.max_connections(), 9);
+
+        // Probes back up, but never past the count that was rejected
+        let counts: Vec<usize> = (1..=6).map(|i| measure(&mut tuner, &state, &manager, 10.0 * i as f64)).collect();
+        assert_eq!(counts, vec![6, 7, 8, 9, 9, 9]);
+
+        // Rejections already acted on are not counted again
+        assert_eq!(measure(&mut tuner, &state, &manager, 70.0), 9);
+    }
+
+    #[test]
+    fn auto_tune_ceiling_lifts_with_the_server_limit() {
+        let (state, manager) = (Mutex::new(SessionState::new()), ConnectionManager::new());
+        let mut tuner = AutoTuner::new(10);
+
+        tuner.cap(Some(4));
+        assert_eq!(tuner.current, 4);
+        server_rejection(&manager);
+        assert_eq!(tuner.next_connection_count(&state, &manager).0, 2);
+        assert_eq!(tuner.max_connections(), 3);
+
+        // The same limit again changes nothing
+        tuner.cap(Some(4));
+        assert_eq!(tuner.max_connections(), 3);
+
+        // The server accepts more sessions now: earlier rejections no longer bound the count
+        tuner.cap(Some(8));
+        assert_eq!(tuner.max_connections(), 8);
+        let counts: Vec<usize> = (1..=8).map(|i| measure(&mut tuner, &state, &manager, 10.0 * i as f64)).collect();
+        assert_eq!(counts.last(), Some(&8));
+    }
+
+    #[test]
+    fn session_limit_is_learned_from_successes_and_rejections() {
+        let manager = ConnectionManager::new();
+        for open in [1, 3, 2] {
+            manager.record_session_ok(open);
+        }
+        assert_eq!(manager.highest_ok_sessions.load(Ordering::SeqCst), 3);
+        assert_eq!(manager.session_limit(), None);
+
+        // A lone session refused says nothing about concurrency
+        manager.record_session_rejected(1);
+        assert_eq!(manager.session_limit(), None);
+
+        manager.record_session_rejected(5);
+        assert_eq!((manager.session_limit(), manager.worker_limit()), (Some(4), Some(3)));
+        // Later rejections only ever lower it
+        manager.record_session_rejected(6);
+        assert_eq!(manager.session_limit(), Some(4));
+
+        let update = manager.take_limit_update().unwrap();
+        assert_eq!((update.max_sessions, update.highest_ok), (4, 3));
+        assert!(manager.take_limit_update().is_none());
+
+        // More sessions than the limit logged in: the server allows more now
+        manager.record_session_ok(6);
+        assert_eq!(manager.session_limit(), Some(6));
+        assert_eq!(manager.take_limit_update().map(|limit| (limit.max_sessions, limit.highest_ok)), Some((6, 6)));
+    }
+
+    #[test]
+    fn server_limits_round_trip_per_server() {
+        init_test_database("limits");
+        let limit = |max_sessions, learned_at| db::ServerLimit { max_sessions, highest_ok: max_sessions, learned_at };
+
+        db::save_server_limit("limits", "user@a:21", &limit(4, 100)).unwrap();
+        db::save_server_limit("limits", "user@b:21", &limit(8, 200)).unwrap();
+        db::save_server_limit("limits", "user@a:21", &limit(3, 300)).unwrap();
+
+        let loaded = db::load_server_limit("limits", "user@a:21").unwrap().unwrap();
+        assert_eq!((loaded.max_sessions, loaded.learned_at), (3, 300));
+        assert_eq!(db::load_server_limit("limits", "user@b:21").unwrap().unwrap().max_sessions, 8);
+        assert!(db::load_server_limit("limits", "user@c:21").unwrap().is_none());
+    }
+
+    #[test]
+    fn remembered_session_limit_caps_the_workers() {
+        let config = test_config("remembered", false);
+        let limit = db::ServerLimit { max_sessions: 4, highest_ok: 4, learned_at: Utc::now().timestamp() };
+        db::save_server_limit("remembered", &server_limit_key(&config), &limit).unwrap();
+
+        let manager = ConnectionManager::new();
+        let mut tuner = AutoTuner::new(10);
+        restore_session_limit(&config, &manager, &mut tuner);
+        assert_eq!((manager.session_limit(), manager.worker_limit()), (Some(4), Some(3)));
+        assert_eq!(tuner.current, 3);
+        // Nothing new to save
+        assert!(manager.take_limit_update().is_none());
+    }
+
+    #[test]
+    fn outdated_session_limit_is_learned_again() {
+        let config = test_config("outdated", false);
+        let learned_at = Utc::now().timestamp() - SERVER_LIMIT_MAX_AGE.as_secs() as i64 - 60;
+        let limit = db::ServerLimit { max_sessions: 4, highest_ok: 4, learned_at };
+        db::save_server_limit("outdated", &server_limit_key(&config), &limit).unwrap();
+
+        let manager = ConnectionManager::new();
+        let mut tuner = AutoTuner::new(10);
+        restore_session_limit(&config, &manager, &mut tuner);
+        assert_eq!(manager.session_limit(), None);
+        assert_eq!(tuner.current, 10);
+    }
+
+    #[test]
+    fn server_down_is_told_from_a_session_limit() {
+        let refused = ftp::types::FtpError::ConnectionError(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "connection refused"));
+        let busy = ftp::types::FtpError::InvalidResponse("Expected code [220], got response: 421 Too many connections\r\n".to_string());
+        let reset = ftp::types::FtpError::ConnectionError(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset by peer"));
+        let login = ftp::types::FtpError::InvalidResponse("Expected code [230], got response: 530 Too many users\r\n".to_string());
+
+        // Refused with nothing else connected: the server is down
+        assert!(is_server_down(&refused, ErrorKind::Network, 0));
+        assert!(is_server_down(&busy, ErrorKind::ServerBusy, 0));
+        // Refused while our other sessions are logged in: that's the session limit
+        assert!(!is_server_down(&refused, ErrorKind::Network, 2));
+        assert!(!is_server_down(&busy, ErrorKind::ServerBusy, 1));
+        assert!(!is_server_down(&login, ErrorKind::LoginRefused, 1));
+        // Other network failures mean an outage either way
+        assert!(is_server_down(&reset, ErrorKind::Network, 3));
+    }
 }
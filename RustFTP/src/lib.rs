@@ -18,9 +18,41 @@ use std::sync::atomic::{AtomicBool, Ordering};
 // Include the existing FTP engine as a module
 mod ftp_engine;
 
+// FTP/FTPS control-channel client used by the engine
+mod ftp_client;
+
+// Transport trait shared by the FTP/FTPS and SFTP backends
+mod transport;
+
+// SFTP (SSH) transport backend
+mod sftp;
+// Reusable logged-in connections for the upload workers
+mod pool;
+// Event-driven source folder watching (watch mode)
+mod watcher;
+// Include/exclude/size rules for the local scan
+mod filters;
+// What happens to local files after they are uploaded
+mod disposition;
+// Size/age/count limits for the FTPU-Sent archive
+mod retention;
+
+// Reply-code based classification of transfer errors
+mod errors;
+
+// Token-bucket bandwidth limits for uploads
+mod throttle;
+// Time-of-day upload windows and blackouts
+mod schedule;
+use throttle::RateLimiter;
+
 // Include the database module
 mod db;
 
+// Helpers shared by the unit tests
+#[cfg(test)]
+mod test_support;
+
 // C function pointer type for notification callbacks from Swift
 pub type NotificationCallback = Option<extern "C" fn(
     u32,                    // config_id (config hash)
@@ -36,12 +68,16 @@ lazy_static::lazy_static! {
     static ref SESSIONS: Arc<Mutex<HashMap<String, SessionHandle>>> = Arc::new(Mutex::new(HashMap::new()));
     // Global registry mapping config_id (UUID string) to notification callback
     pub(crate) static ref NOTIFICATION_CALLBACKS: Arc<Mutex<HashMap<String, NotificationCallback>>> = Arc::new(Mutex::new(HashMap::new()));
+    // Upload bandwidth cap shared by all sessions (0 = unlimited)
+    pub(crate) static ref GLOBAL_RATE_LIMITER: Arc<RateLimiter> = Arc::new(RateLimiter::new(0));
 }
 
 struct SessionHandle {
     thread_handle: Option<thread::JoinHandle<()>>,
     shutdown_signal: Arc<AtomicBool>,
     notification_callback: NotificationCallback,
+    rate_limiter: Arc<RateLimiter>,
+    pause: Arc<ftp_engine::PauseControl>,
 }
 
 /// Start an FTP monitoring session
@@ -153,6 +189,16 @@ pub extern "C" fn rust_ftp_start(
     let shutdown = Arc::new(AtomicBool::new(false));
     let shutdown_clone = shutdown.clone();
 
+    // Bandwidth cap for all workers of this session; set from the config at
+    // startup. rust_ftp_set_bandwidth_limit overrides it, even when called
+    // before the session thread has read its config
+    let rate_limiter = Arc::new(RateLimiter::new(0));
+    let rate_limiter_clone = rate_limiter.clone();
+
+    // Pause state, set by rust_ftp_pause / rust_ftp_resume
+    let pause = Arc::new(ftp_engine::PauseControl::default());
+    let pause_clone = pause.clone();
+
     // Spawn FTP session in background thread
     // This will run the existing main() logic
     let handle = thread::spawn(move || {
@@ -165,7 +211,7 @@ pub extern "C" fn rust_ftp_start(
             hash_str,
         ];
 
-        if let Err(e) = ftp_engine::run_ftp_with_args(args, shutdown_clone) {
+        if let Err(e) = ftp_engine::run_ftp_with_args(args, shutdown_clone, rate_limiter_clone, pause_clone) {
             eprintln!("FTP session error: {}", e);
         }
     });
@@ -175,6 +221,8 @@ pub extern "C" fn rust_ftp_start(
         thread_handle: Some(handle),
         shutdown_signal: shutdown,
         notification_callback,
+        rate_limiter,
+        pause,
     };
 
     let mut sessions = SESSIONS.lock().unwrap();
@@ -215,6 +263,57 @@ pub extern "C" fn rust_ftp_stop(session_id: *const c_char) -> i32 {
     }
 }
 
+/// Pause an FTP monitoring session without stopping it
+/// Uploads in progress finish first, unless abort_transfers is true, in which
+/// case they are cut off; after resuming they continue from where they stopped
+/// if resume_uploads is on, and start over otherwise
+/// Returns 0 on success, non-zero on error
+#[no_mangle]
+pub extern "C" fn rust_ftp_pause(session_id: *const c_char, abort_transfers: bool) -> i32 {
+    let id_str = unsafe {
+        if session_id.is_null() {
+            return -1;
+        }
+        match CStr::from_ptr(session_id).to_str() {
+            Ok(s) => s,
+            Err(_) => return -2,
+        }
+    };
+
+    let sessions = SESSIONS.lock().unwrap();
+    match sessions.get(id_str) {
+        Some(session) => {
+            session.pause.pause(abort_transfers);
+            0
+        }
+        None => -3, // Session not found
+    }
+}
+
+/// Resume a session paused with rust_ftp_pause
+/// Returns 0 on success, non-zero on error
+#[no_mangle]
+pub extern "C" fn rust_ftp_resume(session_id: *const c_char) -> i32 {
+    let id_str = unsafe {
+        if session_id.is_null() {
+            return -1;
+        }
+        match CStr::from_ptr(session_id).to_str() {
+            Ok(s) => s,
+            Err(_) => return -2,
+        }
+    };
+
+    let sessions = SESSIONS.lock().unwrap();
+    match sessions.get(id_str) {
+        Some(session) => {
+            session.pause.resume();
+            0
+        }
+        None => -3, // Session not found
+    }
+}
+
 /// Get status for a session by reading the status file
 /// Returns JSON string (must be freed with rust_ftp_free_string)
 /// Returns null pointer on error
@@ -304,3 +403,148 @@ pub extern "C" fn rust_ftp_clear_config_data(config_id: *const c_char) -> i32 {
         }
     }
 }
+
+/// Query the upload history (audit trail) for a configuration
+/// `filter_json` is an optional JSON object: {"since", "until" (unix seconds),
+/// "outcome", "filename_pattern" (glob on local or remote path), "limit"}
+/// Returns a JSON array of records, newest first, or null on error
+/// The returned string must be freed with rust_ftp_free_string
+#[no_mangle]
+pub extern "C" fn rust_ftp_query_upload_history(config_id: *const c_char, filter_json: *const c_char) -> *mut c_char {
+    let config_id_str = unsafe {
+        if config_id.is_null() {
+            return std::ptr::null_mut();
+        }
+        match CStr::from_ptr(config_id).to_str() {
+            Ok(s) => s,
+            Err(_) => return std::ptr::null_mut(),
+        }
+    };
+
+    // A null filter means everything
+    let filter = if filter_json.is_null() {
+        db::HistoryFilter::default()
+    } else {
+        let json = match unsafe { CStr::from_ptr(filter_json) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return std::ptr::null_mut(),
+        };
+        match serde_json::from_str::<db::HistoryFilter>(json) {
+            Ok(filter) => filter,
+            Err(e) => {
+                eprintln!("❌ Invalid upload history filter: {}", e);
+                return std::ptr::null_mut();
+            }
+        }
+    };
+
+    let records = match db::query_upload_history(config_id_str, &filter) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("❌ Failed to query upload history: {}", e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match serde_json::to_string(&records).map(CString::new) {
+        Ok(Ok(c_str)) => c_str.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// List files that failed too often and are no longer retried (dead letters)
+/// Returns a JSON array, most recent failure first, or null on error
+/// The returned string must be freed with rust_ftp_free_string
+#[no_mangle]
+pub extern "C" fn rust_ftp_get_dead_letters(config_id: *const c_char) -> *mut c_char {
+    let config_id_str = unsafe {
+        if config_id.is_null() {
+            return std::ptr::null_mut();
+        }
+        match CStr::from_ptr(config_id).to_str() {
+            Ok(s) => s,
+            Err(_) => return std::ptr::null_mut(),
+        }
+    };
+
+    let entries = match db::dead_letters(config_id_str) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("❌ Failed to load dead letters: {}", e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match serde_json::to_string(&entries).map(CString::new) {
+        Ok(Ok(c_str)) => c_str.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Retry dead-lettered files again on the next scan, with a fresh attempt count
+/// `local_path` selects a single file; null requeues all of them
+/// Returns the number of files requeued, or negative on error
+#[no_mangle]
+pub extern "C" fn rust_ftp_retry_dead_letters(config_id: *const c_char, local_path: *const c_char) -> i32 {
+    let config_id_str = unsafe {
+        if config_id.is_null() {
+            return -1;
+        }
+        match CStr::from_ptr(config_id).to_str() {
+            Ok(s) => s,
+            Err(_) => return -2,
+        }
+    };
+
+    let local_path_str = if local_path.is_null() {
+        None
+    } else {
+        match unsafe { CStr::from_ptr(local_path) }.to_str() {
+            Ok(s) => Some(s),
+            Err(_) => return -2,
+        }
+    };
+
+    match db::requeue_dead_letters(config_id_str, local_path_str) {
+        Ok(requeued) => requeued as i32,
+        Err(e) => {
+            eprintln!("❌ Failed to requeue dead letters: {}", e);
+            -3
+        }
+    }
+}
+
+/// Change the upload bandwidth cap of a running session
+/// The cap is shared by all of the session's parallel workers and replaces
+/// max_upload_rate from its config until the session ends; 0 removes the limit
+/// Returns 0 on success, non-zero on error
+#[no_mangle]
+pub extern "C" fn rust_ftp_set_bandwidth_limit(session_id: *const c_char, bytes_per_sec: u64) -> i32 {
+    let id_str = unsafe {
+        if session_id.is_null() {
+            return -1;
+        }
+        match CStr::from_ptr(session_id).to_str() {
+            Ok(s) => s,
+            Err(_) => return -2,
+        }
+    };
+
+    let sessions = SESSIONS.lock().unwrap();
+    match sessions.get(id_str) {
+        Some(session) => {
+            session.rate_limiter.set_override(bytes_per_sec);
+            0
+        }
+        None => -3, // Session not found
+    }
+}
+
+/// Change the upload bandwidth cap shared by all sessions
+/// Each session stays under both this and its own limit; 0 removes the limit
+/// Returns 0
+#[no_mangle]
+pub extern "C" fn rust_ftp_set_global_bandwidth_limit(bytes_per_sec: u64) -> i32 {
+    GLOBAL_RATE_LIMITER.set_rate(bytes_per_sec);
+    0
+}
@@ -0,0 +1,278 @@
+//!
+//! Connection pool
+//!
+//! Keeps logged-in `Transport` sessions between files so workers do not pay
+//! for connect + login + CWD on every upload. Connections are checked out by
+//! a worker, health-checked with NOOP, and handed back when the guard drops.
+//! A connection that saw an error must be `discard`ed instead, so a broken or
+//! half-way session never returns to the pool.
+//!
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::transport::Transport;
+
+/// Idle sessions older than this are closed rather than probed; most servers
+/// drop idle control connections after a few minutes anyway
+const MAX_IDLE_TIME: Duration = Duration::from_secs(120);
+
+struct IdleConnection {
+    transport: Box<dyn Transport>,
+    idle_since: Instant,
+}
+
+/// Pool of authenticated sessions shared by the upload workers
+pub struct ConnectionPool {
+    idle: Mutex<Vec<IdleConnection>>,
+    /// Sessions opened through the pool and not closed yet, idle or checked out
+    open: AtomicUsize,
+}
+
+impl std::fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("idle", &self.idle_count())
+            .field("open", &self.open_count())
+            .finish()
+    }
+}
+
+impl ConnectionPool {
+    pub fn new() -> Self {
+        ConnectionPool {
+            idle: Mutex::new(Vec::new()),
+            open: AtomicUsize::new(0),
+        }
+    }
+
+    /// Take a healthy idle session, or `None` if the caller must open a new one
+    pub fn checkout(&self) -> Option<PooledConnection<'_>> {
+        loop {
+            let candidate = self.idle.lock().ok()?.pop()?;
+            let mut transport = candidate.transport;
+
+            if candidate.idle_since.elapsed() > MAX_IDLE_TIME {
+                transport.quit().ok();
+                self.closed(1);
+                continue;
+            }
+
+            // The server may have timed the session out while it sat idle, or NAT dropped it
+            // silently and the NOOP times out; either way open a fresh one instead
+            if transport.noop().is_ok() {
+                return Some(PooledConnection {
+                    pool: self,
+                    transport: Some(transport),
+                });
+            }
+            self.closed(1);
+        }
+    }
+
+    /// Wrap a freshly opened (and logged-in) session so it returns to the pool
+    pub fn adopt(&self, transport: Box<dyn Transport>) -> PooledConnection<'_> {
+        self.open.fetch_add(1, Ordering::SeqCst);
+        PooledConnection {
+            pool: self,
+            transport: Some(transport),
+        }
+    }
+
+    /// Close idle sessions beyond `max_idle`, e.g. after auto-tune lowered the worker count
+    pub fn trim(&self, max_idle: usize) {
+        let surplus: Vec<IdleConnection> = match self.idle.lock() {
+            Ok(mut idle) if idle.len() > max_idle => {
+                let keep_from = idle.len() - max_idle;
+                idle.drain(..keep_from).collect()
+            }
+            _ => Vec::new(),
+        };
+        self.closed(surplus.len());
+        for mut connection in surplus {
+            connection.transport.quit().ok();
+        }
+    }
+
+    /// Politely close every idle session
+    pub fn close_all(&self) {
+        self.trim(0);
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().map(|idle| idle.len()).unwrap_or(0)
+    }
+
+    /// Sessions this pool currently holds open on the server, idle or in use
+    pub fn open_count(&self) -> usize {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    fn closed(&self, count: usize) {
+        let _ = self.open.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| Some(open.saturating_sub(count)));
+    }
+
+    fn check_in(&self, transport: Box<dyn Transport>) {
+        match self.idle.lock() {
+            Ok(mut idle) => idle.push(IdleConnection {
+                transport,
+                idle_since: Instant::now(),
+            }),
+            Err(_) => self.closed(1),
+        }
+    }
+}
+
+/// A checked-out session; goes back to the pool when dropped
+pub struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    transport: Option<Box<dyn Transport>>,
+}
+
+impl PooledConnection<'_> {
+    /// Close the session instead of returning it (after any error on it)
+    pub fn discard(mut self) {
+        if let Some(mut transport) = self.transport.take() {
+            transport.quit().ok();
+            self.pool.closed(1);
+        }
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = dyn Transport;
+
+    fn deref(&self) -> &Self::Target {
+        self.transport.as_deref().expect("pooled connection used after discard")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.transport.as_deref_mut().expect("pooled connection used after discard")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(transport) = self.transport.take() {
+            self.pool.check_in(transport);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::memory::MemoryTransport;
+
+    /// Server told apart from the others by the one file it holds
+    fn named(name: &str) -> MemoryTransport {
+        let mut transport = MemoryTransport::new("/");
+        transport.files.insert(format!("/{}", name), Vec::new());
+        transport
+    }
+
+    fn session(name: &str) -> Box<dyn Transport> {
+        Box::new(named(name))
+    }
+
+    /// Session the server timed out while it sat idle
+    fn broken_session(name: &str) -> Box<dyn Transport> {
+        Box::new(MemoryTransport { noop_fails: true, ..named(name) })
+    }
+
+    fn name_of(connection: &mut PooledConnection<'_>) -> String {
+        connection.list(Some("/")).unwrap().remove(0).name
+    }
+
+    /// Check `transports` in, in order
+    fn pool_with(transports: Vec<Box<dyn Transport>>) -> ConnectionPool {
+        let pool = ConnectionPool::new();
+        let connections: Vec<_> = transports.into_iter().map(|transport| pool.adopt(transport)).collect();
+        drop(connections);
+        pool
+    }
+
+    #[test]
+    fn checkout_reuses_the_latest_idle_session() {
+        let pool = pool_with(vec![session("a"), session("b")]);
+        assert_eq!((pool.idle_count(), pool.open_count()), (2, 2));
+
+        let mut connection = pool.checkout().unwrap();
+        assert_eq!(name_of(&mut connection), "b");
+        assert_eq!((pool.idle_count(), pool.open_count()), (1, 2));
+
+        drop(connection);
+        assert_eq!((pool.idle_count(), pool.open_count()), (2, 2));
+    }
+
+    #[test]
+    fn checkout_closes_sessions_that_fail_noop() {
+        let pool = pool_with(vec![session("a"), broken_session("b")]);
+
+        let mut connection = pool.checkout().unwrap();
+        assert_eq!(name_of(&mut connection), "a");
+        assert_eq!(pool.open_count(), 1);
+        connection.discard();
+
+        let pool = pool_with(vec![broken_session("a"), broken_session("b")]);
+        assert!(pool.checkout().is_none());
+        assert_eq!((pool.idle_count(), pool.open_count()), (0, 0));
+    }
+
+    #[test]
+    fn sessions_idle_too_long_are_closed() {
+        let pool = pool_with(vec![session("old"), session("fresh")]);
+        // The older session sits at the bottom of the stack, so expire the newer one
+        pool.idle.lock().unwrap()[1].idle_since = Instant::now() - MAX_IDLE_TIME - Duration::from_secs(1);
+
+        let mut connection = pool.checkout().unwrap();
+        assert_eq!(name_of(&mut connection), "old");
+        assert_eq!((pool.idle_count(), pool.open_count()), (0, 1));
+    }
+
+    #[test]
+    fn discarded_sessions_do_not_return() {
+        let pool = ConnectionPool::new();
+        pool.adopt(session("a")).discard();
+        assert_eq!((pool.idle_count(), pool.open_count()), (0, 0));
+
+        let pool = pool_with(vec![session("a")]);
+        pool.checkout().unwrap().discard();
+        assert!(pool.checkout().is_none());
+        assert_eq!(pool.open_count(), 0);
+    }
+
+    #[test]
+    fn open_count_follows_adopt_discard_and_trim() {
+        let pool = ConnectionPool::new();
+        let mut connections: Vec<_> = ["a", "b", "c", "d"].into_iter().map(|name| pool.adopt(session(name))).collect();
+        assert_eq!(pool.open_count(), 4);
+
+        connections.pop().unwrap().discard();
+        assert_eq!(pool.open_count(), 3);
+
+        drop(connections);
+        assert_eq!((pool.idle_count(), pool.open_count()), (3, 3));
+
+        pool.trim(1);
+        assert_eq!((pool.idle_count(), pool.open_count()), (1, 1));
+
+        pool.close_all();
+        assert_eq!((pool.idle_count(), pool.open_count()), (0, 0));
+    }
+
+    #[test]
+    fn trim_closes_the_oldest_idle_sessions() {
+        let pool = pool_with(vec![session("a"), session("b"), session("c")]);
+        pool.trim(2);
+
+        let mut newest = pool.checkout().unwrap();
+        let mut next = pool.checkout().unwrap();
+        assert_eq!((name_of(&mut newest), name_of(&mut next)), ("c".to_string(), "b".to_string()));
+        assert!(pool.checkout().is_none());
+    }
+}
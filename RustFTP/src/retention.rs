@@ -0,0 +1,263 @@
+//!
+//! Retention for sent files
+//!
+//! Keeps `FTPU-Sent` (or the archive folder) from growing forever. Files are
+//! pruned oldest-first until every configured limit - age, total size, file
+//! count - holds again. Folders emptied by pruning (e.g. old date folders)
+//! are removed too.
+//!
+//! Only files the engine moved there itself are considered: the archive
+//! folder is chosen by the user and may hold anything else. Age is measured
+//! from the arrival time the engine recorded when it moved the file, since
+//! moving keeps the modification time and the inode change time moves with
+//! every metadata change.
+//!
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Limits applied to the sent-files folder; `None` means unlimited
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_total_bytes: Option<u64>,
+    pub max_files: Option<usize>,
+}
+
+/// Which limit caused a file to be pruned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneReason {
+    Age,
+    TotalSize,
+    FileCount,
+}
+
+impl PruneReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PruneReason::Age => "age",
+            PruneReason::TotalSize => "size",
+            PruneReason::FileCount => "count",
+        }
+    }
+}
+
+/// A file removed by `prune`
+#[derive(Debug, Clone)]
+pub struct PrunedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub reason: PruneReason,
+}
+
+/// A file the engine moved into the sent folder, and when
+#[derive(Debug, Clone)]
+pub struct SentFile {
+    pub path: PathBuf,
+    pub archived_at: SystemTime,
+}
+
+/// What a `prune` run removed
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub pruned: Vec<PrunedFile>,
+    /// Files that matched a limit but could not be deleted
+    pub failures: Vec<(PathBuf, io::Error)>,
+    /// Recorded files that are no longer there (moved or deleted by the user)
+    pub missing: Vec<PathBuf>,
+}
+
+impl PruneReport {
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.pruned.iter().map(|f| f.size).sum()
+    }
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_total_bytes.is_some() || self.max_files.is_some()
+    }
+
+    /// Delete the oldest of `sent` (files under `root`) until all limits hold
+    pub fn prune(&self, root: &Path, sent: &[SentFile]) -> PruneReport {
+        let mut report = PruneReport::default();
+        if !self.is_enabled() {
+            return report;
+        }
+
+        let mut files = Vec::new();
+        for file in sent {
+            match fs::symlink_metadata(&file.path) {
+                Ok(metadata) if metadata.is_file() => files.push((file, metadata.len())),
+                _ => report.missing.push(file.path.clone()),
+            }
+        }
+        files.sort_by_key(|(f, _)| f.archived_at);
+
+        let now = SystemTime::now();
+        let mut remaining_files = files.len();
+        let mut remaining_bytes: u64 = files.iter().map(|(_, size)| size).sum();
+
+        for (file, size) in files {
+            let too_old = self
+                .max_age
+                .is_some_and(|max_age| now.duration_since(file.archived_at).unwrap_or_default() > max_age);
+            let reason = if too_old {
+                PruneReason::Age
+            } else if self.max_total_bytes.is_some_and(|max| remaining_bytes > max) {
+                PruneReason::TotalSize
+            } else if self.max_files.is_some_and(|max| remaining_files > max) {
+                PruneReason::FileCount
+            } else {
+                // Oldest-first: once this file is kept, every newer one is too
+                break;
+            };
+
+            match fs::remove_file(&file.path) {
+                Ok(()) => {
+                    remaining_files -= 1;
+                    remaining_bytes = remaining_bytes.saturating_sub(size);
+                    remove_empty_parents(&file.path, root);
+                    report.pruned.push(PrunedFile { path: file.path.clone(), size, reason });
+                }
+                Err(e) => report.failures.push((file.path.clone(), e)),
+            }
+        }
+        report
+    }
+}
+
+/// Every file below `root`, e.g. to adopt sent files moved before arrival times were recorded
+pub fn list_files(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if root.is_dir() {
+        collect_files(root, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else { continue };
+        if metadata.is_dir() {
+            collect_files(&path, files)?;
+        } else if metadata.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Remove the folders `file` was in that are empty now, up to (not including) `root`
+fn remove_empty_parents(file: &Path, root: &Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    /// Sent files of `size` bytes each, the first one arrived `ages_days[0]` days ago and so on
+    fn sent_files(root: &Path, size: usize, ages_days: &[u64]) -> Vec<SentFile> {
+        ages_days
+            .iter()
+            .enumerate()
+            .map(|(i, days)| {
+                let path = root.join(format!("2026-10-{:02}", i + 1)).join(format!("file{}.jpg", i));
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, vec![0u8; size]).unwrap();
+                SentFile { path, archived_at: SystemTime::now() - Duration::from_secs(days * 86_400) }
+            })
+            .collect()
+    }
+
+    fn pruned_names(report: &PruneReport) -> Vec<(String, PruneReason)> {
+        report
+            .pruned
+            .iter()
+            .map(|f| (f.path.file_name().unwrap().to_string_lossy().to_string(), f.reason))
+            .collect()
+    }
+
+    #[test]
+    fn disabled_policy_prunes_nothing() {
+        let root = scratch_dir("disabled");
+        let sent = sent_files(&root, 10, &[400, 1]);
+        assert!(RetentionPolicy::default().prune(&root, &sent).pruned.is_empty());
+    }
+
+    #[test]
+    fn prunes_files_older_than_max_age() {
+        let root = scratch_dir("age");
+        // Recorded out of order: arrival time decides, not list position
+        let sent = sent_files(&root, 10, &[1, 40, 31, 2]);
+        let policy = RetentionPolicy { max_age: Some(Duration::from_secs(30 * 86_400)), ..Default::default() };
+
+        let report = policy.prune(&root, &sent);
+        assert_eq!(pruned_names(&report), vec![("file1.jpg".to_string(), PruneReason::Age), ("file2.jpg".to_string(), PruneReason::Age)]);
+        assert_eq!(report.bytes_reclaimed(), 20);
+        assert!(sent[0].path.exists() && sent[3].path.exists());
+        assert!(!root.join("2026-10-02").exists(), "emptied date folders are removed");
+        assert!(root.exists());
+    }
+
+    #[test]
+    fn prunes_oldest_first_until_under_total_size() {
+        let root = scratch_dir("size");
+        let sent = sent_files(&root, 100, &[3, 5, 1, 4]);
+        let policy = RetentionPolicy { max_total_bytes: Some(250), ..Default::default() };
+
+        let report = policy.prune(&root, &sent);
+        assert_eq!(pruned_names(&report), vec![("file1.jpg".to_string(), PruneReason::TotalSize), ("file3.jpg".to_string(), PruneReason::TotalSize)]);
+    }
+
+    #[test]
+    fn prunes_oldest_first_until_under_file_count() {
+        let root = scratch_dir("count");
+        let sent = sent_files(&root, 10, &[3, 5, 1, 4]);
+        let policy = RetentionPolicy { max_files: Some(3), ..Default::default() };
+
+        let report = policy.prune(&root, &sent);
+        assert_eq!(pruned_names(&report), vec![("file1.jpg".to_string(), PruneReason::FileCount)]);
+    }
+
+    #[test]
+    fn age_is_reported_before_other_limits() {
+        let root = scratch_dir("combined");
+        let sent = sent_files(&root, 100, &[60, 3, 2, 1]);
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(30 * 86_400)),
+            max_total_bytes: Some(250),
+            max_files: Some(1),
+        };
+
+        let report = policy.prune(&root, &sent);
+        assert_eq!(pruned_names(&report), vec![
+            ("file0.jpg".to_string(), PruneReason::Age),
+            ("file1.jpg".to_string(), PruneReason::TotalSize),
+            ("file2.jpg".to_string(), PruneReason::FileCount),
+        ]);
+    }
+
+    #[test]
+    fn missing_files_are_reported_not_counted() {
+        let root = scratch_dir("missing");
+        let sent = sent_files(&root, 100, &[2, 1]);
+        fs::remove_file(&sent[0].path).unwrap();
+        let policy = RetentionPolicy { max_total_bytes: Some(100), ..Default::default() };
+
+        let report = policy.prune(&root, &sent);
+        assert!(report.pruned.is_empty());
+        assert_eq!(report.missing, vec![sent[0].path.clone()]);
+    }
+}
@@ -0,0 +1,321 @@
+//!
+//! Time-of-day upload schedule
+//!
+//! A config can limit uploading to active windows, e.g. weekdays 19:00-07:00,
+//! and exclude blackout periods. Times are local wall-clock time. A window
+//! whose end is not after its start runs past midnight and belongs to the day
+//! it starts on, so "weekdays 19:00-07:00" covers Friday night into Saturday
+//! morning. With no windows the schedule is always open except for blackouts.
+//! Each window can carry its own bandwidth cap and connection count; a cap set
+//! at runtime with rust_ftp_set_bandwidth_limit takes precedence over it.
+//!
+//! ```json
+//! "schedule": {
+//!   "windows": [{"days": "weekdays", "start": "19:00", "end": "07:00", "max_upload_rate": 2000000},
+//!               {"days": ["sat", "sun"], "start": "00:00", "end": "24:00", "upload_aggressiveness": 8}],
+//!   "blackouts": [{"days": "daily", "start": "12:00", "end": "12:30"},
+//!                 {"from": "2026-12-24T00:00:00", "until": "2026-12-27T00:00:00"}]
+//! }
+//! ```
+//!
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use serde::Deserialize;
+
+/// Schedules repeat weekly, so looking a little over a week ahead finds every opening
+const LOOKAHEAD_DAYS: i64 = 8;
+
+/// Set of weekdays, bit 0 = Monday
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "DaysSpec")]
+pub struct Days(u8);
+
+impl Default for Days {
+    fn default() -> Self {
+        Days(0x7f)
+    }
+}
+
+impl Days {
+    fn contains(self, date: NaiveDate) -> bool {
+        self.0 & (1 << date.weekday().num_days_from_monday()) != 0
+    }
+
+    fn parse(name: &str) -> Result<Days, String> {
+        let day = |name: &str| -> Result<u8, String> {
+            match name {
+                "mon" | "monday" => Ok(0),
+                "tue" | "tuesday" => Ok(1),
+                "wed" | "wednesday" => Ok(2),
+                "thu" | "thursday" => Ok(3),
+                "fri" | "friday" => Ok(4),
+                "sat" | "saturday" => Ok(5),
+                "sun" | "sunday" => Ok(6),
+                _ => Err(format!("unknown day '{}'", name)),
+            }
+        };
+        let name = name.trim().to_lowercase();
+        match name.as_str() {
+            "daily" | "all" | "every day" => Ok(Days(0x7f)),
+            "weekdays" => Ok(Days(0x1f)),
+            "weekends" => Ok(Days(0x60)),
+            _ => match name.split_once('-') {
+                // Ranges wrap around the week: "fri-mon"
+                Some((first, last)) => {
+                    let (first, last) = (day(first.trim())?, day(last.trim())?);
+                    let mut days = 0;
+                    let mut current = first;
+                    loop {
+                        days |= 1 << current;
+                        if current == last {
+                            break;
+                        }
+                        current = (current + 1) % 7;
+                    }
+                    Ok(Days(days))
+                }
+                None => Ok(Days(1 << day(&name)?)),
+            },
+        }
+    }
+}
+
+/// "weekdays", "mon-fri", "sat" or a list like ["mon", "wed"]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DaysSpec {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl TryFrom<DaysSpec> for Days {
+    type Error = String;
+
+    fn try_from(spec: DaysSpec) -> Result<Days, String> {
+        match spec {
+            DaysSpec::One(name) => Days::parse(&name),
+            DaysSpec::Many(names) => names.iter().try_fold(Days(0), |days, name| Ok(Days(days.0 | Days::parse(name)?.0))),
+        }
+    }
+}
+
+/// "HH:MM", stored as minutes since midnight; "24:00" is allowed as an end time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(u32);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<TimeOfDay, String> {
+        let invalid = || format!("invalid time '{}', expected HH:MM", text);
+        let (hours, minutes) = text.trim().split_once(':').ok_or_else(invalid)?;
+        let hours: u32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+        if minutes >= 60 || hours * 60 + minutes > 24 * 60 {
+            return Err(invalid());
+        }
+        Ok(TimeOfDay(hours * 60 + minutes))
+    }
+}
+
+/// Recurring daily period on the given days
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeWindow {
+    #[serde(default)]
+    pub days: Days,
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+}
+
+impl TimeWindow {
+    fn contains(&self, at: NaiveDateTime) -> bool {
+        let date = at.date();
+        let minute = at.hour() * 60 + at.minute();
+        let (start, end) = (self.start.0, self.end.0);
+        if start < end {
+            self.days.contains(date) && minute >= start && minute < end
+        } else {
+            // Runs past midnight: the early part belongs to the previous day's window
+            (self.days.contains(date) && minute >= start)
+                || (minute < end && date.pred_opt().is_some_and(|previous| self.days.contains(previous)))
+        }
+    }
+
+    /// Start or end of this window on `date`, as a local time
+    fn on(date: NaiveDate, time: TimeOfDay) -> NaiveDateTime {
+        date.and_hms_opt(0, 0, 0).unwrap_or_default() + Duration::minutes(time.0 as i64)
+    }
+}
+
+/// Period in which uploads may run, with optional limits while it lasts
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleWindow {
+    #[serde(flatten)]
+    pub time: TimeWindow,
+    #[serde(default)]
+    pub max_upload_rate: Option<u64>, // bytes/sec for the session while this window is active; 0 = unlimited
+    #[serde(default)]
+    pub upload_aggressiveness: Option<u32>, // Parallel connections while this window is active
+}
+
+/// Period in which nothing is uploaded, even inside an active window
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Blackout {
+    /// One-off, e.g. a maintenance weekend
+    Dated { from: NaiveDateTime, until: NaiveDateTime },
+    /// Every day/week, e.g. a nightly backup
+    Recurring(TimeWindow),
+}
+
+impl Blackout {
+    fn contains(&self, at: NaiveDateTime) -> bool {
+        match self {
+            Blackout::Dated { from, until } => at >= *from && at < *until,
+            Blackout::Recurring(window) => window.contains(at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Schedule {
+    #[serde(default)]
+    pub windows: Vec<ScheduleWindow>,
+    #[serde(default)]
+    pub blackouts: Vec<Blackout>,
+}
+
+/// Whether uploads may run at a given moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleState {
+    /// Uploading allowed; the index of the window that applies, if the schedule has windows
+    Open(Option<usize>),
+    /// Outside every window or inside a blackout, until the given time (None: never reopens)
+    Paused { blackout: bool, until: Option<NaiveDateTime> },
+}
+
+impl Schedule {
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty() && self.blackouts.is_empty()
+    }
+
+    pub fn state_at(&self, at: NaiveDateTime) -> ScheduleState {
+        if self.is_open(at) {
+            return ScheduleState::Open(self.windows.iter().position(|window| window.time.contains(at)));
+        }
+        let blackout = self.blackouts.iter().any(|blackout| blackout.contains(at));
+        ScheduleState::Paused { blackout, until: self.next_open(at) }
+    }
+
+    /// Uploads may run at `at`: inside a window (or there are none) and outside every blackout
+    pub fn is_open(&self, at: NaiveDateTime) -> bool {
+        !self.blackouts.iter().any(|blackout| blackout.contains(at))
+            && (self.windows.is_empty() || self.windows.iter().any(|window| window.time.contains(at)))
+    }
+
+    /// First moment after `at` when uploading may start: a window opening or a blackout ending
+    fn next_open(&self, at: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut candidates = Vec::new();
+        for offset in -1..=LOOKAHEAD_DAYS {
+            let date = at.date() + Duration::days(offset);
+            candidates.extend(self.windows.iter().map(|window| TimeWindow::on(date, window.time.start)));
+            for blackout in &self.blackouts {
+                if let Blackout::Recurring(window) = blackout {
+                    // A blackout past midnight ends on the following day
+                    let end_date = if window.end.0 > window.start.0 { date } else { date + Duration::days(1) };
+                    candidates.push(TimeWindow::on(end_date, window.end));
+                }
+            }
+        }
+        candidates.extend(self.blackouts.iter().filter_map(|blackout| match blackout {
+            Blackout::Dated { until, .. } => Some(*until),
+            Blackout::Recurring(_) => None,
+        }));
+        candidates.retain(|candidate| *candidate > at);
+        candidates.sort();
+        candidates.into_iter().find(|candidate| self.is_open(*candidate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(json: serde_json::Value) -> Schedule {
+        serde_json::from_value(json).unwrap()
+    }
+
+    /// Local time in the week of Friday 2026-10-16
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn window_start_is_inclusive_and_end_exclusive() {
+        let schedule = schedule(serde_json::json!({"windows": [{"start": "09:00", "end": "17:00"}]}));
+        assert!(!schedule.is_open(at(16, 8, 59)));
+        assert!(schedule.is_open(at(16, 9, 0)));
+        assert!(schedule.is_open(at(16, 16, 59)));
+        assert!(!schedule.is_open(at(16, 17, 0)));
+    }
+
+    #[test]
+    fn overnight_window_belongs_to_the_day_it_starts() {
+        let schedule = schedule(serde_json::json!({"windows": [{"days": "weekdays", "start": "19:00", "end": "07:00"}]}));
+        assert!(!schedule.is_open(at(16, 18, 59)));
+        assert!(schedule.is_open(at(16, 19, 0)), "Friday evening");
+        assert!(schedule.is_open(at(17, 6, 59)), "Saturday morning is Friday's window");
+        assert!(!schedule.is_open(at(17, 7, 0)));
+        assert!(!schedule.is_open(at(17, 19, 0)), "no window starts on Saturday");
+        assert!(!schedule.is_open(at(19, 6, 0)), "Sunday night has no window to run over from");
+        assert!(schedule.is_open(at(19, 19, 30)));
+    }
+
+    #[test]
+    fn paused_schedule_reports_the_next_opening() {
+        let schedule = schedule(serde_json::json!({"windows": [{"days": "weekdays", "start": "19:00", "end": "07:00"}]}));
+        assert_eq!(schedule.state_at(at(17, 12, 0)), ScheduleState::Paused { blackout: false, until: Some(at(19, 19, 0)) });
+        assert_eq!(schedule.state_at(at(16, 23, 0)), ScheduleState::Open(Some(0)));
+    }
+
+    #[test]
+    fn blackout_closes_an_open_window() {
+        let schedule = schedule(serde_json::json!({
+            "windows": [{"start": "00:00", "end": "24:00"}],
+            "blackouts": [{"days": "daily", "start": "12:00", "end": "12:30"}],
+        }));
+        assert!(schedule.is_open(at(16, 11, 59)));
+        assert_eq!(schedule.state_at(at(16, 12, 0)), ScheduleState::Paused { blackout: true, until: Some(at(16, 12, 30)) });
+        assert!(schedule.is_open(at(16, 12, 30)));
+    }
+
+    #[test]
+    fn overnight_blackout_ends_the_next_morning() {
+        let schedule = schedule(serde_json::json!({"blackouts": [{"start": "23:00", "end": "02:00"}]}));
+        assert!(schedule.is_open(at(16, 22, 59)));
+        assert_eq!(schedule.state_at(at(16, 23, 0)), ScheduleState::Paused { blackout: true, until: Some(at(17, 2, 0)) });
+        assert!(!schedule.is_open(at(17, 1, 59)));
+        assert_eq!(schedule.state_at(at(17, 2, 0)), ScheduleState::Open(None));
+    }
+
+    #[test]
+    fn dated_blackout_reopens_into_the_next_window() {
+        let schedule = schedule(serde_json::json!({
+            "windows": [{"days": "weekends", "start": "08:00", "end": "20:00"}],
+            "blackouts": [{"from": "2026-10-17T00:00:00", "until": "2026-10-18T10:00:00"}],
+        }));
+        assert_eq!(schedule.state_at(at(17, 9, 0)), ScheduleState::Paused { blackout: true, until: Some(at(18, 10, 0)) });
+        assert!(schedule.is_open(at(18, 10, 0)));
+    }
+
+    #[test]
+    fn parses_day_ranges_and_rejects_bad_times() {
+        assert_eq!(Days::parse("fri-mon").unwrap(), Days(0b111_0001));
+        assert_eq!(Days::parse("Weekends").unwrap(), Days(0x60));
+        assert!(Days::parse("someday").is_err());
+        assert!(TimeOfDay::try_from("24:00".to_string()).is_ok());
+        assert!(TimeOfDay::try_from("24:01".to_string()).is_err());
+        assert!(TimeOfDay::try_from("7:60".to_string()).is_err());
+    }
+}
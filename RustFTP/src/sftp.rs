@@ -0,0 +1,377 @@
+//!
+//! SFTP (SSH) transport
+//!
+//! Implements `Transport` on top of libssh2. SFTP has no server-side working
+//! directory, so `cwd` is tracked here and relative paths are resolved against
+//! it. The server's host key is checked against an OpenSSH known_hosts file
+//! before any credentials are sent.
+//!
+//! SFTP status codes are reported as the FTP reply a server would send for
+//! the same failure (permission denied is a 550, quota exceeded a 552), so the
+//! engine classifies both protocols alike. Failed authentication is a 530.
+//!
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+use ftp::types::{FileType, FtpError, Result};
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+
+use crate::transport::{send_file, ChecksumAlgorithm, RemoteEntry, Transport};
+
+/// Longest wait for any SSH/SFTP operation, in milliseconds
+const SESSION_TIMEOUT_MS: u32 = 120_000;
+
+/// Wait for the keep-alive stat, so a session dropped while idle is detected quickly
+const QUICK_REPLY_TIMEOUT_MS: u32 = 15_000;
+
+/// How to authenticate and verify the server for an SFTP session
+#[derive(Debug, Clone, Default)]
+pub struct SftpOptions {
+    /// Private key for public-key auth; password auth is used when `None`
+    pub private_key_path: Option<PathBuf>,
+    /// Passphrase for an encrypted private key
+    pub private_key_passphrase: Option<String>,
+    /// OpenSSH known_hosts file used to verify the server's host key
+    pub known_hosts_path: Option<PathBuf>,
+    /// Trust (and record) host keys that are not in known_hosts yet.
+    /// A key that *differs* from a recorded one is always rejected.
+    pub accept_new_host_keys: bool,
+}
+
+/// SFTP session implementing the engine's `Transport`
+pub struct SftpTransport {
+    session: Session,
+    sftp: Option<Sftp>,
+    cwd: PathBuf,
+    options: SftpOptions,
+}
+
+impl std::fmt::Debug for SftpTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SftpTransport")
+            .field("cwd", &self.cwd)
+            .field("authenticated", &self.session.authenticated())
+            .finish()
+    }
+}
+
+/// FTP reply code equivalent to an SFTP status (`LIBSSH2_FX_*`), or `None`
+/// for statuses that are not a verdict on the request
+fn status_reply_code(status: i32) -> Option<u32> {
+    match status {
+        // NO_SUCH_FILE, PERMISSION_DENIED, FAILURE, NO_SUCH_PATH, WRITE_PROTECT, NO_MEDIA,
+        // UNKNOWN_PRINCIPAL, DIR_NOT_EMPTY, NOT_A_DIRECTORY, LINK_LOOP
+        2 | 3 | 4 | 10 | 12 | 13 | 16 | 18 | 19 | 21 => Some(550),
+        // NO_SPACE_ON_FILESYSTEM, QUOTA_EXCEEDED
+        14 | 15 => Some(552),
+        // FILE_ALREADY_EXISTS, INVALID_FILENAME
+        11 | 20 => Some(553),
+        // OP_UNSUPPORTED
+        8 => Some(502),
+        // LOCK_CONFLICT: someone else has the file open
+        17 => Some(450),
+        _ => None,
+    }
+}
+
+/// Error in the form of an FTP reply, so `errors::reply_code` reads it
+fn reply_error(code: u32, message: &str) -> FtpError {
+    FtpError::InvalidResponse(format!("SFTP request failed, got response: {} {}", code, message))
+}
+
+fn ssh_error(err: ssh2::Error) -> FtpError {
+    match err.code() {
+        // NO_CONNECTION, CONNECTION_LOST
+        ErrorCode::SFTP(6 | 7) => FtpError::ConnectionError(io::Error::new(io::ErrorKind::ConnectionAborted, err.message().to_string())),
+        ErrorCode::SFTP(status) => match status_reply_code(status) {
+            Some(code) => reply_error(code, err.message()),
+            None => FtpError::ConnectionError(io::Error::from(err)),
+        },
+        ErrorCode::Session(_) => FtpError::ConnectionError(io::Error::from(err)),
+    }
+}
+
+/// Failed write to a remote file: ssh2 hands it over as an `io::Error` that only
+/// keeps libssh2's message for the status, so the status is recovered from that
+fn write_error(err: io::Error) -> FtpError {
+    let status = match err.to_string().as_str() {
+        "permission denied" => 3,
+        "failure" => 4,
+        "file is write protected" => 12,
+        "no space on filesystem" => 14,
+        "quota exceeded" => 15,
+        _ => return FtpError::ConnectionError(err),
+    };
+    status_reply_code(status).map_or(FtpError::ConnectionError(err), |code| reply_error(code, "write failed"))
+}
+
+fn auth_error(message: String) -> FtpError {
+    reply_error(530, &message)
+}
+
+impl SftpTransport {
+    /// Open the SSH connection and verify the host key
+    ///
+    /// Authentication happens in `login`, mirroring the FTP flow.
+    pub fn connect(host: &str, port: u16, options: SftpOptions) -> Result<SftpTransport> {
+        let tcp = TcpStream::connect((host, port)).map_err(FtpError::ConnectionError)?;
+
+        let mut session = Session::new().map_err(ssh_error)?;
+        session.set_timeout(SESSION_TIMEOUT_MS);
+        session.set_tcp_stream(tcp);
+        session.handshake().map_err(ssh_error)?;
+
+        Self::verify_host_key(&session, host, port, &options)?;
+
+        Ok(SftpTransport {
+            session,
+            sftp: None,
+            cwd: PathBuf::from("/"),
+            options,
+        })
+    }
+
+    fn verify_host_key(session: &Session, host: &str, port: u16, options: &SftpOptions) -> Result<()> {
+        let (key, key_type) = session
+            .host_key()
+            .ok_or_else(|| FtpError::SecureError("Server did not present a host key".to_string()))?;
+
+        let known_hosts_path = options.known_hosts_path.clone().or_else(default_known_hosts_path);
+        let mut known_hosts = session.known_hosts().map_err(ssh_error)?;
+        if let Some(path) = &known_hosts_path {
+            if path.exists() {
+                known_hosts.read_file(path, KnownHostFileKind::OpenSSH).map_err(ssh_error)?;
+            }
+        }
+
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(FtpError::SecureError(format!(
+                "Host key for {}:{} does not match known_hosts - refusing to connect (possible man-in-the-middle)",
+                host, port
+            ))),
+            CheckResult::NotFound if options.accept_new_host_keys => {
+                // Record the key so later sessions detect a change
+                let entry = if port == 22 { host.to_string() } else { format!("[{}]:{}", host, port) };
+                if let Some(path) = &known_hosts_path {
+                    known_hosts
+                        .add(&entry, key, "added by FTPUploader", key_type.into())
+                        .and_then(|_| known_hosts.write_file(path, KnownHostFileKind::OpenSSH))
+                        .map_err(ssh_error)?;
+                }
+                Ok(())
+            }
+            CheckResult::NotFound => Err(FtpError::SecureError(format!(
+                "Host {}:{} is not in known_hosts ({}) - add its key or enable accepting new host keys",
+                host,
+                port,
+                known_hosts_path.map(|p| p.display().to_string()).unwrap_or_else(|| "none".to_string())
+            ))),
+            CheckResult::Failure => Err(FtpError::SecureError(format!("Host key check failed for {}:{}", host, port))),
+        }
+    }
+
+    fn sftp(&self) -> Result<&Sftp> {
+        self.sftp
+            .as_ref()
+            .ok_or_else(|| FtpError::ConnectionError(io::Error::other("SFTP session used before login")))
+    }
+
+    /// Resolve an FTP-style path against the tracked working directory
+    fn resolve(&self, path: &str) -> PathBuf {
+        if path.starts_with('/') {
+            PathBuf::from(path)
+        } else {
+            self.cwd.join(path)
+        }
+    }
+}
+
+fn default_known_hosts_path() -> Option<PathBuf> {
+    std::env::var("HOME").ok().map(|home| Path::new(&home).join(".ssh").join("known_hosts"))
+}
+
+impl Transport for SftpTransport {
+    fn login(&mut self, user: &str, password: &str) -> Result<()> {
+        let result = match &self.options.private_key_path {
+            Some(key_path) => self.session.userauth_pubkey_file(
+                user,
+                None,
+                key_path,
+                self.options.private_key_passphrase.as_deref(),
+            ),
+            None => self.session.userauth_password(user, password),
+        };
+        result.map_err(|e| auth_error(format!("SFTP authentication failed for {}: {}", user, e.message())))?;
+
+        if !self.session.authenticated() {
+            return Err(auth_error(format!("SFTP authentication failed for {}", user)));
+        }
+
+        self.sftp = Some(self.session.sftp().map_err(ssh_error)?);
+        Ok(())
+    }
+
+    fn cwd(&mut self, path: &str) -> Result<()> {
+        let target = self.resolve(path);
+        let stat = self.sftp()?.stat(&target).map_err(ssh_error)?;
+        if !stat.is_dir() {
+            return Err(FtpError::ConnectionError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", target.display()),
+            )));
+        }
+        self.cwd = target;
+        Ok(())
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<()> {
+        let target = self.resolve(path);
+        self.sftp()?.mkdir(&target, 0o755).map_err(ssh_error)
+    }
+
+    fn size(&mut self, path: &str) -> Result<Option<usize>> {
+        let target = self.resolve(path);
+        let stat = self.sftp()?.stat(&target).map_err(ssh_error)?;
+        Ok(stat.size.map(|s| s as usize))
+    }
+
+    fn mdtm(&mut self, path: &str) -> Result<Option<i64>> {
+        let target = self.resolve(path);
+        let stat = self.sftp()?.stat(&target).map_err(ssh_error)?;
+        Ok(stat.mtime.map(|t| t as i64))
+    }
+
+    fn put(&mut self, path: &str, reader: &mut dyn Read) -> Result<()> {
+        let target = self.resolve(path);
+        let mut remote = self.sftp()?.create(&target).map_err(ssh_error)?;
+        send_file(reader, &mut remote, write_error)?;
+        Ok(())
+    }
+
+    fn resume_put(&mut self, path: &str, reader: &mut dyn Read, offset: u64) -> Result<()> {
+        let target = self.resolve(path);
+        // Open without truncating so the bytes already on the server are kept
+        let mut remote = self
+            .sftp()?
+            .open_mode(&target, OpenFlags::WRITE, 0o644, OpenType::File)
+            .map_err(ssh_error)?;
+        remote.seek(SeekFrom::Start(offset)).map_err(FtpError::ConnectionError)?;
+        send_file(reader, &mut remote, write_error)?;
+        Ok(())
+    }
+
+    fn rename(&mut self, from_name: &str, to_name: &str) -> Result<()> {
+        let source = self.resolve(from_name);
+        let target = self.resolve(to_name);
+        // Default flags: atomic, replacing an existing target
+        self.sftp()?.rename(&source, &target, None).map_err(ssh_error)
+    }
+
+    fn rm(&mut self, path: &str) -> Result<()> {
+        let target = self.resolve(path);
+        self.sftp()?.unlink(&target).map_err(ssh_error)
+    }
+
+    fn server_copy(&mut self, from_name: &str, to_name: &str) -> Result<bool> {
+        // Core SFTP cannot copy, but a symlink gives the same result for readers
+        let source = self.resolve(from_name);
+        let target = self.resolve(to_name);
+        self.sftp()?.symlink(&source, &target).map_err(ssh_error)?;
+        Ok(true)
+    }
+
+    fn list(&mut self, path: Option<&str>) -> Result<Vec<RemoteEntry>> {
+        let target = path.map(|p| self.resolve(p)).unwrap_or_else(|| self.cwd.clone());
+        let entries = self.sftp()?.readdir(&target).map_err(ssh_error)?;
+        Ok(entries
+            .into_iter()
+            .map(|(entry_path, stat)| RemoteEntry {
+                name: entry_path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                is_dir: stat.is_dir(),
+                size: stat.size,
+            })
+            .collect())
+    }
+
+    fn simple_retr(&mut self, path: &str) -> Result<Cursor<Vec<u8>>> {
+        let target = self.resolve(path);
+        let mut remote = self.sftp()?.open(&target).map_err(ssh_error)?;
+        let mut buffer = Vec::new();
+        remote.read_to_end(&mut buffer).map_err(FtpError::ConnectionError)?;
+        Ok(Cursor::new(buffer))
+    }
+
+    fn checksum(&mut self, _path: &str) -> Result<Option<(ChecksumAlgorithm, String)>> {
+        // Core SFTP has no digest request; verification falls back to the size check
+        Ok(None)
+    }
+
+    fn transfer_type(&mut self, _file_type: FileType) -> Result<()> {
+        // SFTP always transfers bytes verbatim
+        Ok(())
+    }
+
+    fn noop(&mut self) -> Result<()> {
+        // A stat round-trip proves both the SSH session and the SFTP channel are alive
+        let cwd = self.cwd.clone();
+        self.session.set_timeout(QUICK_REPLY_TIMEOUT_MS);
+        let result = self.sftp().and_then(|sftp| sftp.stat(&cwd).map(|_| ()).map_err(ssh_error));
+        self.session.set_timeout(SESSION_TIMEOUT_MS);
+        result
+    }
+
+    fn quit(&mut self) -> Result<()> {
+        self.sftp = None;
+        self.session.set_timeout(QUICK_REPLY_TIMEOUT_MS);
+        self.session.disconnect(None, "closing", None).map_err(ssh_error)
+    }
+
+    fn is_secure(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{self, ErrorKind};
+
+    fn status(code: i32) -> ErrorKind {
+        errors::classify_ftp(&ssh_error(ssh2::Error::new(ErrorCode::SFTP(code), "status")))
+    }
+
+    #[test]
+    fn sftp_statuses_classify_like_ftp_replies() {
+        assert_eq!(status(3), ErrorKind::PermissionDenied(550));
+        assert_eq!(status(2), ErrorKind::PermissionDenied(550));
+        assert_eq!(status(4), ErrorKind::PermissionDenied(550));
+        assert_eq!(status(15), ErrorKind::QuotaExceeded);
+        assert_eq!(status(14), ErrorKind::QuotaExceeded);
+        assert_eq!(status(20), ErrorKind::PermissionDenied(553));
+        assert_eq!(status(17), ErrorKind::Transient(450));
+        assert_eq!(status(7), ErrorKind::Network);
+        assert_eq!(errors::reply_code(&ssh_error(ssh2::Error::new(ErrorCode::SFTP(8), "unsupported"))), Some(502));
+    }
+
+    #[test]
+    fn failed_writes_keep_their_status() {
+        let write = |message: &str| errors::classify_ftp(&write_error(io::Error::other(message.to_string())));
+        assert_eq!(write("quota exceeded"), ErrorKind::QuotaExceeded);
+        assert_eq!(write("permission denied"), ErrorKind::PermissionDenied(550));
+        assert_eq!(errors::classify_ftp(&write_error(io::Error::new(io::ErrorKind::TimedOut, "timed out"))), ErrorKind::Network);
+    }
+
+    #[test]
+    fn only_login_reports_auth_failures() {
+        let login = auth_error("SFTP authentication failed for user".to_string());
+        assert_eq!(errors::classify_login(&login, 0), ErrorKind::AuthFailed);
+        assert_eq!(errors::classify_login(&login, 1), ErrorKind::LoginRefused);
+        assert_ne!(status(3), ErrorKind::AuthFailed);
+    }
+}
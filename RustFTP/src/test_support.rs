@@ -0,0 +1,44 @@
+//!
+//! Helpers shared by the unit tests
+//!
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_SCRATCH_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// Empty folder under the system temp dir, removed with everything in it when dropped
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+/// Fresh scratch folder for one test; `name` only makes leftovers easier to recognise
+pub fn scratch_dir(name: &str) -> ScratchDir {
+    let number = NEXT_SCRATCH_DIR.fetch_add(1, Ordering::SeqCst);
+    let path = std::env::temp_dir().join(format!("ftpu-test-{}-{}-{}", std::process::id(), number, name));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    ScratchDir { path }
+}
+
+impl Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for ScratchDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,224 @@
+//!
+//! Bandwidth throttling
+//!
+//! Token buckets that cap upload speed in bytes per second. Every session has
+//! its own bucket shared by all of its parallel workers, and all sessions
+//! also draw from one global bucket, so a transfer runs at the lower of the
+//! two limits. Both can be changed while uploads are running; a rate of 0
+//! means unlimited. A session's limit comes from its config (or the active
+//! schedule window) unless the app overrides it at runtime, in which case the
+//! override wins until the session ends.
+//!
+
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Smallest slice a throttled read asks for, so slow limits still make progress
+const MIN_READ_SIZE: usize = 4 * 1024;
+
+#[derive(Debug)]
+struct Bucket {
+    configured: u64,
+    override_rate: Option<u64>,
+    /// May go negative: a worker that takes more than is available waits for the debt
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn rate(&self) -> u64 {
+        self.override_rate.unwrap_or(self.configured)
+    }
+
+    // Start a new rate with at most one second of burst
+    fn rate_changed(&mut self) {
+        self.tokens = self.tokens.min(self.rate() as f64);
+        self.refilled_at = Instant::now();
+    }
+}
+
+/// Token bucket shared between threads
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// `bytes_per_sec` of 0 means unlimited
+    pub fn new(bytes_per_sec: u64) -> RateLimiter {
+        RateLimiter {
+            bucket: Mutex::new(Bucket {
+                configured: bytes_per_sec,
+                override_rate: None,
+                tokens: bytes_per_sec as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.bucket.lock().map(|bucket| bucket.rate()).unwrap_or(0)
+    }
+
+    /// Change the configured limit; takes effect for the next read of every worker
+    /// unless a runtime override is in place
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        if let Ok(mut bucket) = self.bucket.lock() {
+            bucket.configured = bytes_per_sec;
+            bucket.rate_changed();
+        }
+    }
+
+    /// Replace the configured limit until the limiter is dropped, whatever `set_rate` is called with later
+    pub fn set_override(&self, bytes_per_sec: u64) {
+        if let Ok(mut bucket) = self.bucket.lock() {
+            bucket.override_rate = Some(bytes_per_sec);
+            bucket.rate_changed();
+        }
+    }
+
+    pub fn is_overridden(&self) -> bool {
+        self.bucket.lock().map(|bucket| bucket.override_rate.is_some()).unwrap_or(false)
+    }
+
+    /// Take `bytes` from the bucket, sleeping until they are covered
+    pub fn acquire(&self, bytes: usize) {
+        let wait = {
+            let Ok(mut bucket) = self.bucket.lock() else { return };
+            if bucket.rate() == 0 {
+                return;
+            }
+            let rate = bucket.rate() as f64;
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * rate;
+            // At most one second's worth of burst builds up while idle
+            bucket.tokens = (bucket.tokens + refill).min(rate);
+            bucket.refilled_at = now;
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        };
+        std::thread::sleep(wait);
+    }
+
+    /// Largest read worth doing at once: about a quarter second of transfer
+    fn read_size(&self) -> usize {
+        match self.rate() {
+            0 => usize::MAX,
+            rate => ((rate / 4) as usize).max(MIN_READ_SIZE),
+        }
+    }
+}
+
+/// The session and global buckets an upload draws from
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    pub session: Arc<RateLimiter>,
+    pub global: Arc<RateLimiter>,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Throttle {
+            session: Arc::new(RateLimiter::new(0)),
+            global: crate::GLOBAL_RATE_LIMITER.clone(),
+        }
+    }
+}
+
+impl Throttle {
+    /// Wrap a reader so it is read no faster than both limits allow
+    pub fn reader<R: Read>(&self, inner: R) -> ThrottledReader<R> {
+        ThrottledReader {
+            inner,
+            throttle: self.clone(),
+        }
+    }
+}
+
+/// Reader that waits for tokens after each read
+pub struct ThrottledReader<R: Read> {
+    inner: R,
+    throttle: Throttle,
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = self.throttle.session.read_size().min(self.throttle.global.read_size());
+        let len = buf.len().min(max);
+        let n = self.inner.read(&mut buf[..len])?;
+        self.throttle.session.acquire(n);
+        self.throttle.global.acquire(n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How long `acquire(bytes)` blocked
+    fn time_acquire(limiter: &RateLimiter, bytes: usize) -> Duration {
+        let start = Instant::now();
+        limiter.acquire(bytes);
+        start.elapsed()
+    }
+
+    #[test]
+    fn unlimited_never_waits() {
+        let limiter = RateLimiter::new(0);
+        assert!(time_acquire(&limiter, 1 << 30) < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn waits_for_debt_after_the_burst() {
+        let limiter = RateLimiter::new(100_000);
+        assert!(time_acquire(&limiter, 100_000) < Duration::from_millis(50), "a full second of burst is available");
+        let waited = time_acquire(&limiter, 20_000);
+        assert!(waited >= Duration::from_millis(150) && waited < Duration::from_millis(1_000), "{:?}", waited);
+    }
+
+    #[test]
+    fn lowering_the_rate_caps_the_burst() {
+        let limiter = RateLimiter::new(10_000_000);
+        limiter.set_rate(10_000);
+        assert_eq!(limiter.rate(), 10_000);
+        assert!(time_acquire(&limiter, 10_000) < Duration::from_millis(50));
+        let waited = time_acquire(&limiter, 2_000);
+        assert!(waited >= Duration::from_millis(150) && waited < Duration::from_millis(1_000), "{:?}", waited);
+    }
+
+    #[test]
+    fn raising_the_rate_to_unlimited_stops_waiting() {
+        let limiter = RateLimiter::new(1_000);
+        limiter.acquire(1_000);
+        limiter.set_rate(0);
+        assert!(time_acquire(&limiter, 1 << 20) < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn override_wins_over_later_rate_changes() {
+        let limiter = RateLimiter::new(1_000);
+        limiter.set_override(5_000);
+        limiter.set_rate(0);
+        assert!(limiter.is_overridden());
+        assert_eq!(limiter.rate(), 5_000);
+    }
+
+    #[test]
+    fn reads_are_sliced_by_the_lower_limit() {
+        let throttle = Throttle {
+            session: Arc::new(RateLimiter::new(0)),
+            global: Arc::new(RateLimiter::new(40_000)),
+        };
+        let mut reader = throttle.reader(io::repeat(7));
+        let mut buf = vec![0u8; 64 * 1024];
+        assert_eq!(reader.read(&mut buf).unwrap(), 10_000);
+
+        throttle.session.set_rate(8_000);
+        assert_eq!(reader.read(&mut buf).unwrap(), MIN_READ_SIZE);
+    }
+}
@@ -0,0 +1,615 @@
+//!
+//! Transport abstraction
+//!
+//! The engine talks to the destination server through `Transport`, so the same
+//! scan/upload/monitor-file logic works over FTP, FTPS (`FtpClient`) and SFTP
+//! (`SftpTransport`). Errors stay `ftp::FtpError` for every backend so the
+//! existing retry and rejection handling applies unchanged.
+//!
+
+use std::io::{self, Cursor, Read, Write};
+
+use ftp::types::{FileType, FtpError, Result};
+use serde::Deserialize;
+
+use crate::errors;
+use crate::ftp_client::FtpClient;
+
+/// Wire protocol used to reach the destination server
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// FTP, optionally protected with TLS (see `ftps_mode`)
+    #[default]
+    Ftp,
+    /// SSH File Transfer Protocol
+    Sftp,
+}
+
+/// Digest algorithms a server can compute for post-upload verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    Md5,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Map a `HASH` algorithm name (as listed in FEAT) to a supported algorithm
+    fn from_hash_name(name: &str) -> Option<ChecksumAlgorithm> {
+        match name.to_ascii_uppercase().as_str() {
+            "CRC32" => Some(ChecksumAlgorithm::Crc32),
+            "MD5" => Some(ChecksumAlgorithm::Md5),
+            "SHA-256" => Some(ChecksumAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Length of the lowercase hex digest
+    fn hex_len(self) -> usize {
+        match self {
+            ChecksumAlgorithm::Crc32 => 8,
+            ChecksumAlgorithm::Md5 => 32,
+            ChecksumAlgorithm::Sha256 => 64,
+        }
+    }
+}
+
+/// One entry of a remote directory listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub is_dir: bool,
+    /// `None` when the server's listing does not say
+    pub size: Option<u64>,
+}
+
+/// Split off the first `count` whitespace-separated fields; the rest (a file
+/// name that may contain spaces) is returned as is
+fn split_fields(line: &str, count: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(count);
+    let mut rest = line.trim_start();
+    for _ in 0..count {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some((fields, rest))
+}
+
+/// Parse one `LIST` line: Unix `ls -l` style, or the DOS style IIS uses
+///
+/// Lines in neither format are taken to be a bare file name.
+fn parse_list_line(line: &str) -> Option<RemoteEntry> {
+    let line = line.trim_end();
+    if line.trim().is_empty() || line.starts_with("total ") {
+        return None;
+    }
+
+    // -rw-r--r--   1 owner group   1234 Oct 16 21:14 name
+    if line.starts_with(['-', 'd', 'l']) {
+        if let Some((fields, name)) = split_fields(line, 8).filter(|(_, name)| !name.is_empty()) {
+            // Symlinks are listed as "name -> target"
+            let name = if line.starts_with('l') { name.split(" -> ").next().unwrap_or(name) } else { name };
+            return Some(RemoteEntry {
+                name: name.to_string(),
+                is_dir: line.starts_with('d'),
+                size: fields[4].parse().ok(),
+            });
+        }
+    }
+
+    // 10-16-26  09:14PM       <DIR>          name
+    // 10-16-26  09:14PM                 1234 name
+    if let Some((fields, name)) = split_fields(line, 3).filter(|(_, name)| !name.is_empty()) {
+        if fields[0].contains('-') && fields[0].starts_with(|c: char| c.is_ascii_digit()) && fields[1].contains(':') {
+            let is_dir = fields[2].eq_ignore_ascii_case("<DIR>");
+            return Some(RemoteEntry {
+                name: name.to_string(),
+                is_dir,
+                size: if is_dir { None } else { fields[2].parse().ok() },
+            });
+        }
+    }
+
+    Some(RemoteEntry { name: line.trim().to_string(), is_dir: false, size: None })
+}
+
+/// Stream an upload from `reader` to `writer`
+///
+/// Failed reads are the local file's fault and are reported with
+/// `errors::local_read_error`; failed writes go through `write_error`.
+pub fn send_file(reader: &mut dyn Read, writer: &mut dyn Write, write_error: impl Fn(io::Error) -> FtpError) -> Result<u64> {
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut sent = 0;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(errors::local_read_error(e)),
+        };
+        writer.write_all(&buffer[..n]).map_err(&write_error)?;
+        sent += n as u64;
+    }
+    writer.flush().map_err(&write_error)?;
+    Ok(sent)
+}
+
+/// Pull the hex digest out of the reply to `command` (the text after the reply code)
+///
+/// `HASH` replies are `<algorithm> <start>-<end> <digest> <filename>`; `XCRC`,
+/// `XMD5` and `XSHA256` reply with the digest first. Only that position is read,
+/// so a hex-looking filename in the reply cannot be taken for the digest.
+fn extract_digest(command: &str, reply: &str, algorithm: ChecksumAlgorithm) -> Option<String> {
+    let mut tokens = reply.split_whitespace();
+    let token = if command.eq_ignore_ascii_case("HASH") {
+        if ChecksumAlgorithm::from_hash_name(tokens.next()?) != Some(algorithm) {
+            return None;
+        }
+        tokens.nth(1)?
+    } else {
+        tokens.next()?
+    };
+
+    let valid_len = match algorithm {
+        // CRCs are sometimes printed without leading zeros
+        ChecksumAlgorithm::Crc32 => (1..=8).contains(&token.len()),
+        _ => token.len() == algorithm.hex_len(),
+    };
+    if !valid_len || !token.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("{:0>width$}", token.to_ascii_lowercase(), width = algorithm.hex_len()))
+}
+
+/// Operations the engine performs on the remote server
+///
+/// Paths follow FTP conventions: absolute paths start with `/`, anything else
+/// is relative to the directory selected with `cwd`.
+pub trait Transport: Send {
+    /// Authenticate the session
+    fn login(&mut self, user: &str, password: &str) -> Result<()>;
+
+    /// Change the working directory
+    fn cwd(&mut self, path: &str) -> Result<()>;
+
+    /// Create a single directory level
+    fn mkdir(&mut self, path: &str) -> Result<()>;
+
+    /// Size of a remote file in bytes (errors if it does not exist)
+    fn size(&mut self, path: &str) -> Result<Option<usize>>;
+
+    /// Modification time of a remote file in unix seconds, `None` if unknown
+    fn mdtm(&mut self, path: &str) -> Result<Option<i64>>;
+
+    /// Upload `reader` to `path`, replacing any existing file
+    fn put(&mut self, path: &str, reader: &mut dyn Read) -> Result<()>;
+
+    /// Continue a partial upload: write `reader` (already positioned at
+    /// `offset`) into `path` starting at byte `offset`
+    fn resume_put(&mut self, path: &str, reader: &mut dyn Read, offset: u64) -> Result<()>;
+
+    /// Rename a remote file
+    fn rename(&mut self, from_name: &str, to_name: &str) -> Result<()>;
+
+    /// Delete a remote file
+    fn rm(&mut self, path: &str) -> Result<()>;
+
+    /// Make `to_name` a server-side copy (or link) of `from_name` without
+    /// re-sending the bytes; `Ok(false)` if the server cannot
+    fn server_copy(&mut self, from_name: &str, to_name: &str) -> Result<bool>;
+
+    /// Entries of a directory (the working directory when `path` is `None`)
+    fn list(&mut self, path: Option<&str>) -> Result<Vec<RemoteEntry>>;
+
+    /// Download a (small) remote file into memory
+    fn simple_retr(&mut self, path: &str) -> Result<Cursor<Vec<u8>>>;
+
+    /// Server-computed digest of a remote file, `None` if the server cannot provide one
+    fn checksum(&mut self, path: &str) -> Result<Option<(ChecksumAlgorithm, String)>>;
+
+    /// Select ASCII/binary transfers; a no-op for protocols without the concept
+    fn transfer_type(&mut self, file_type: FileType) -> Result<()>;
+
+    /// Keep-alive / health check; fails within seconds, not minutes, on a dead session
+    fn noop(&mut self) -> Result<()>;
+
+    /// Close the session politely
+    fn quit(&mut self) -> Result<()>;
+
+    /// Whether credentials and data are encrypted on the wire
+    fn is_secure(&self) -> bool;
+}
+
+impl Transport for FtpClient {
+    fn login(&mut self, user: &str, password: &str) -> Result<()> {
+        FtpClient::login(self, user, password)
+    }
+
+    fn cwd(&mut self, path: &str) -> Result<()> {
+        FtpClient::cwd(self, path)
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<()> {
+        FtpClient::mkdir(self, path)
+    }
+
+    fn size(&mut self, path: &str) -> Result<Option<usize>> {
+        FtpClient::size(self, path)
+    }
+
+    fn mdtm(&mut self, path: &str) -> Result<Option<i64>> {
+        FtpClient::mdtm(self, path)
+    }
+
+    fn put(&mut self, path: &str, mut reader: &mut dyn Read) -> Result<()> {
+        FtpClient::put(self, path, &mut reader)
+    }
+
+    fn resume_put(&mut self, path: &str, mut reader: &mut dyn Read, offset: u64) -> Result<()> {
+        FtpClient::resume_put(self, path, &mut reader, offset)
+    }
+
+    fn rename(&mut self, from_name: &str, to_name: &str) -> Result<()> {
+        FtpClient::rename(self, from_name, to_name)
+    }
+
+    fn rm(&mut self, path: &str) -> Result<()> {
+        FtpClient::rm(self, path)
+    }
+
+    fn server_copy(&mut self, from_name: &str, to_name: &str) -> Result<bool> {
+        FtpClient::site_copy(self, from_name, to_name)
+    }
+
+    fn list(&mut self, path: Option<&str>) -> Result<Vec<RemoteEntry>> {
+        Ok(FtpClient::list(self, path)?.iter().filter_map(|line| parse_list_line(line)).collect())
+    }
+
+    fn simple_retr(&mut self, path: &str) -> Result<Cursor<Vec<u8>>> {
+        FtpClient::simple_retr(self, path)
+    }
+
+    fn checksum(&mut self, path: &str) -> Result<Option<(ChecksumAlgorithm, String)>> {
+        let features = self.features()?;
+
+        // HASH marks the currently selected algorithm with '*', e.g. "HASH SHA-256*;MD5;CRC32"
+        let hash_algorithm = features.iter().find_map(|feature| {
+            let mut parts = feature.split_whitespace();
+            if !parts.next()?.eq_ignore_ascii_case("HASH") {
+                return None;
+            }
+            parts
+                .next()?
+                .split(';')
+                .find(|name| name.ends_with('*'))
+                .and_then(|name| ChecksumAlgorithm::from_hash_name(name.trim_end_matches('*')))
+        });
+        if let Some(algorithm) = hash_algorithm {
+            let reply = self.digest("HASH", path)?;
+            return Ok(extract_digest("HASH", &reply, algorithm).map(|digest| (algorithm, digest)));
+        }
+
+        // Fall back to the older non-standard commands, strongest first
+        let fallbacks = [
+            ("XSHA256", ChecksumAlgorithm::Sha256),
+            ("XMD5", ChecksumAlgorithm::Md5),
+            ("XCRC", ChecksumAlgorithm::Crc32),
+        ];
+        for (command, algorithm) in fallbacks {
+            if features.iter().any(|f| f.split_whitespace().next().is_some_and(|name| name.eq_ignore_ascii_case(command))) {
+                let reply = self.digest(command, path)?;
+                return Ok(extract_digest(command, &reply, algorithm).map(|digest| (algorithm, digest)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn transfer_type(&mut self, file_type: FileType) -> Result<()> {
+        FtpClient::transfer_type(self, file_type)
+    }
+
+    fn noop(&mut self) -> Result<()> {
+        FtpClient::noop(self)
+    }
+
+    fn quit(&mut self) -> Result<()> {
+        FtpClient::quit(self)
+    }
+
+    fn is_secure(&self) -> bool {
+        FtpClient::is_secure(self)
+    }
+}
+
+/// In-memory server for exercising the engine through the trait in tests
+#[cfg(test)]
+pub mod memory {
+    use std::collections::HashMap;
+    use std::io::{self, Cursor, Read};
+
+    use ftp::types::{FileType, FtpError, Result};
+    use sha2::Digest;
+
+    use super::{ChecksumAlgorithm, RemoteEntry, Transport};
+
+    #[derive(Default)]
+    pub struct MemoryTransport {
+        /// File contents by absolute path
+        pub files: HashMap<String, Vec<u8>>,
+        /// Modification times by absolute path, set to `clock` on upload
+        pub mtimes: HashMap<String, i64>,
+        pub clock: i64,
+        pub cwd: String,
+        /// Every data command, e.g. "STOR /up/a.bin" or "REST 10 /up/a.bin"
+        pub commands: Vec<String>,
+        /// The next upload drops the connection after this many bytes
+        pub drop_after: Option<usize>,
+        /// The server corrupts the last byte of every upload
+        pub corrupt: bool,
+        /// Answer digest commands with SHA-256
+        pub checksums: bool,
+        /// NOOP fails, as on a session the server timed out
+        pub noop_fails: bool,
+    }
+
+    fn not_found(command: &str) -> FtpError {
+        FtpError::InvalidResponse(format!("Expected code [{}], got response: 550 No such file or directory\r\n", command))
+    }
+
+    impl MemoryTransport {
+        pub fn new(cwd: &str) -> MemoryTransport {
+            MemoryTransport { cwd: cwd.to_string(), ..MemoryTransport::default() }
+        }
+
+        pub fn file(&self, path: &str) -> Option<&Vec<u8>> {
+            self.files.get(path)
+        }
+
+        fn resolve(&self, path: &str) -> String {
+            if path.starts_with('/') {
+                path.to_string()
+            } else {
+                format!("{}/{}", self.cwd.trim_end_matches('/'), path)
+            }
+        }
+
+        // Receive an upload, honouring `drop_after` and `corrupt`
+        fn receive(&mut self, path: &str, reader: &mut dyn Read, mut data: Vec<u8>) -> Result<()> {
+            let mut incoming = Vec::new();
+            let limit = self.drop_after.take();
+            match limit {
+                Some(limit) => {
+                    reader.take(limit as u64).read_to_end(&mut incoming).map_err(crate::errors::local_read_error)?;
+                }
+                None => {
+                    reader.read_to_end(&mut incoming).map_err(crate::errors::local_read_error)?;
+                }
+            }
+            data.extend_from_slice(&incoming);
+            if limit.is_none() && self.corrupt {
+                if let Some(last) = data.last_mut() {
+                    *last ^= 0xff;
+                }
+            }
+            self.files.insert(path.to_string(), data);
+            self.mtimes.insert(path.to_string(), self.clock);
+            match limit {
+                Some(_) => Err(FtpError::ConnectionError(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset by peer"))),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Transport for MemoryTransport {
+        fn login(&mut self, _user: &str, _password: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn cwd(&mut self, path: &str) -> Result<()> {
+            self.cwd = self.resolve(path);
+            Ok(())
+        }
+
+        fn mkdir(&mut self, _path: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn size(&mut self, path: &str) -> Result<Option<usize>> {
+            let path = self.resolve(path);
+            self.files.get(&path).map(|data| Some(data.len())).ok_or_else(|| not_found("213"))
+        }
+
+        fn mdtm(&mut self, path: &str) -> Result<Option<i64>> {
+            let path = self.resolve(path);
+            self.files.get(&path).map(|_| self.mtimes.get(&path).copied()).ok_or_else(|| not_found("213"))
+        }
+
+        fn put(&mut self, path: &str, reader: &mut dyn Read) -> Result<()> {
+            let path = self.resolve(path);
+            self.commands.push(format!("STOR {}", path));
+            self.receive(&path, reader, Vec::new())
+        }
+
+        fn resume_put(&mut self, path: &str, reader: &mut dyn Read, offset: u64) -> Result<()> {
+            let path = self.resolve(path);
+            self.commands.push(format!("REST {} {}", offset, path));
+            let mut data = self.files.get(&path).cloned().unwrap_or_default();
+            data.truncate(offset as usize);
+            self.receive(&path, reader, data)
+        }
+
+        fn rename(&mut self, from_name: &str, to_name: &str) -> Result<()> {
+            let (from, to) = (self.resolve(from_name), self.resolve(to_name));
+            self.commands.push(format!("RNFR {} RNTO {}", from, to));
+            let data = self.files.remove(&from).ok_or_else(|| not_found("350"))?;
+            if let Some(mtime) = self.mtimes.remove(&from) {
+                self.mtimes.insert(to.clone(), mtime);
+            }
+            self.files.insert(to, data);
+            Ok(())
+        }
+
+        fn rm(&mut self, path: &str) -> Result<()> {
+            let path = self.resolve(path);
+            self.commands.push(format!("DELE {}", path));
+            self.files.remove(&path).map(|_| ()).ok_or_else(|| not_found("250"))
+        }
+
+        fn server_copy(&mut self, _from_name: &str, _to_name: &str) -> Result<bool> {
+            Ok(false)
+        }
+
+        fn list(&mut self, path: Option<&str>) -> Result<Vec<RemoteEntry>> {
+            let dir = format!("{}/", self.resolve(path.unwrap_or(".")).trim_end_matches("/.").trim_end_matches('/'));
+            Ok(self
+                .files
+                .iter()
+                .filter_map(|(file, data)| {
+                    let name = file.strip_prefix(&dir)?;
+                    (!name.contains('/')).then(|| RemoteEntry { name: name.to_string(), is_dir: false, size: Some(data.len() as u64) })
+                })
+                .collect())
+        }
+
+        fn simple_retr(&mut self, path: &str) -> Result<Cursor<Vec<u8>>> {
+            let path = self.resolve(path);
+            self.files.get(&path).cloned().map(Cursor::new).ok_or_else(|| not_found("226"))
+        }
+
+        fn checksum(&mut self, path: &str) -> Result<Option<(ChecksumAlgorithm, String)>> {
+            if !self.checksums {
+                return Ok(None);
+            }
+            let path = self.resolve(path);
+            let data = self.files.get(&path).ok_or_else(|| not_found("213"))?;
+            let digest = sha2::Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+            Ok(Some((ChecksumAlgorithm::Sha256, digest)))
+        }
+
+        fn transfer_type(&mut self, _file_type: FileType) -> Result<()> {
+            Ok(())
+        }
+
+        fn noop(&mut self) -> Result<()> {
+            if self.noop_fails {
+                return Err(FtpError::ConnectionError(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset by peer")));
+            }
+            Ok(())
+        }
+
+        fn quit(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_secure(&self) -> bool {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, is_dir: bool, size: Option<u64>) -> Option<RemoteEntry> {
+        Some(RemoteEntry { name: name.to_string(), is_dir, size })
+    }
+
+    /// Reader that fails after handing out `good` bytes
+    struct FailingReader {
+        good: usize,
+        kind: io::ErrorKind,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.good == 0 {
+                return Err(io::Error::new(self.kind, "read failed"));
+            }
+            let n = buf.len().min(self.good);
+            self.good -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn unreadable_local_file_is_not_a_connection_error() {
+        let mut reader = FailingReader { good: 100, kind: io::ErrorKind::PermissionDenied };
+        let error = send_file(&mut reader, &mut Vec::new(), FtpError::ConnectionError).unwrap_err();
+        assert_eq!(errors::classify_ftp(&error), errors::ErrorKind::LocalFile);
+
+        let mut reader = FailingReader { good: 0, kind: io::ErrorKind::NotFound };
+        let error = send_file(&mut reader, &mut Vec::new(), FtpError::ConnectionError).unwrap_err();
+        assert_eq!(errors::classify_ftp(&error), errors::ErrorKind::Vanished);
+    }
+
+    #[test]
+    fn failed_writes_go_through_the_transport_mapping() {
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let error = send_file(&mut &b"data"[..], &mut Closed, FtpError::ConnectionError).unwrap_err();
+        assert_eq!(errors::classify_ftp(&error), errors::ErrorKind::Network);
+    }
+
+    #[test]
+    fn parses_unix_listing() {
+        assert_eq!(parse_list_line("-rw-r--r--   1 ftp  ftp      1234 Oct 16 21:14 _monitored.json"),
+            entry("_monitored.json", false, Some(1234)));
+        assert_eq!(parse_list_line("drwxr-xr-x   2 ftp  ftp      4096 Jan  1  2025 my photos"),
+            entry("my photos", true, Some(4096)));
+        assert_eq!(parse_list_line("lrwxrwxrwx   1 ftp  ftp         9 Oct 16 21:14 latest -> a/b.jpg"),
+            entry("latest", false, Some(9)));
+        assert_eq!(parse_list_line("total 12"), None);
+    }
+
+    #[test]
+    fn parses_dos_listing() {
+        assert_eq!(parse_list_line("10-16-26  09:14PM       <DIR>          Sent Files"), entry("Sent Files", true, None));
+        assert_eq!(parse_list_line("10-16-26  09:14PM                 1234 report 1.pdf"), entry("report 1.pdf", false, Some(1234)));
+    }
+
+    #[test]
+    fn unknown_format_is_a_bare_name() {
+        assert_eq!(parse_list_line("_monitored.json"), entry("_monitored.json", false, None));
+    }
+
+    const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn reads_digest_from_hash_reply() {
+        let reply = format!("SHA-256 0-4 {} report.txt", SHA256);
+        assert_eq!(extract_digest("HASH", &reply, ChecksumAlgorithm::Sha256), Some(SHA256.to_string()));
+        assert_eq!(extract_digest("HASH", "CRC32 0-4 1A2B3C report.txt", ChecksumAlgorithm::Crc32), Some("001a2b3c".to_string()));
+    }
+
+    #[test]
+    fn hash_reply_for_another_algorithm_is_ignored() {
+        let reply = format!("SHA-256 0-4 {} report.txt", SHA256);
+        assert_eq!(extract_digest("HASH", &reply, ChecksumAlgorithm::Md5), None);
+    }
+
+    #[test]
+    fn hex_looking_filename_is_not_the_digest() {
+        // The filename is a valid CRC32/MD5-length hex string, the digest field is garbage
+        assert_eq!(extract_digest("HASH", "MD5 0-4 pending deadbeefdeadbeefdeadbeefdeadbeef", ChecksumAlgorithm::Md5), None);
+        assert_eq!(extract_digest("XCRC", "checksum cafe0123", ChecksumAlgorithm::Crc32), None);
+    }
+
+    #[test]
+    fn reads_digest_from_x_command_replies() {
+        assert_eq!(extract_digest("XCRC", "D87F7E0C", ChecksumAlgorithm::Crc32), Some("d87f7e0c".to_string()));
+        assert_eq!(extract_digest("XMD5", "098F6BCD4621D373CADE4E832627B4F6 test.txt", ChecksumAlgorithm::Md5),
+            Some("098f6bcd4621d373cade4e832627b4f6".to_string()));
+        assert_eq!(extract_digest("XSHA256", SHA256, ChecksumAlgorithm::Sha256), Some(SHA256.to_string()));
+        assert_eq!(extract_digest("XSHA256", "abc123", ChecksumAlgorithm::Sha256), None);
+    }
+}
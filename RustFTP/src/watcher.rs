@@ -0,0 +1,254 @@
+//!
+//! Filesystem watcher for event-driven uploads
+//!
+//! Wraps `notify` (inotify on Linux, FSEvents on macOS) and collects the paths
+//! that changed under the source folder. A path is handed out once it has
+//! settled: either the writer closed it, or no event arrived for it for
+//! `settle_time`. Hidden entries and FTPU-Sent are ignored, mirroring the
+//! full directory scan.
+//!
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Changed paths waiting to settle, keyed by path with the time they become ready
+type PendingPaths = Arc<Mutex<HashMap<PathBuf, Instant>>>;
+
+/// Watches a directory tree and queues changed paths for upload
+pub struct DirectoryWatcher {
+    // Kept alive for as long as events should be delivered
+    _watcher: RecommendedWatcher,
+    root: PathBuf,
+    pending: PendingPaths,
+    needs_rescan: Arc<AtomicBool>,
+    settle_time: Duration,
+}
+
+impl std::fmt::Debug for DirectoryWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirectoryWatcher")
+            .field("root", &self.root)
+            .field("settle_time", &self.settle_time)
+            .finish()
+    }
+}
+
+/// Whether `path` is part of what gets uploaded (no hidden entries, nothing in FTPU-Sent)
+fn is_watched(root: &Path, path: &Path) -> bool {
+    match path.strip_prefix(root) {
+        Ok(relative) => relative.components().all(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            !name.starts_with('.') && name != "FTPU-Sent"
+        }),
+        Err(_) => false,
+    }
+}
+
+/// Queue the watched paths of one event, or flag a rescan if events were lost
+fn queue_event(root: &Path, pending: &PendingPaths, needs_rescan: &AtomicBool, settle_time: Duration, result: notify::Result<Event>) {
+    let event = match result {
+        Ok(event) => event,
+        Err(_) => {
+            // Lost track of something (e.g. queue overflow) - let a full scan catch up
+            needs_rescan.store(true, Ordering::SeqCst);
+            return;
+        }
+    };
+
+    if event.need_rescan() {
+        needs_rescan.store(true, Ordering::SeqCst);
+        return;
+    }
+
+    // A writer closing the file means it is complete; anything else restarts the settle timer
+    let ready_at = match event.kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => Instant::now(),
+        EventKind::Create(_) | EventKind::Modify(_) => Instant::now() + settle_time,
+        _ => return,
+    };
+
+    if let Ok(mut pending) = pending.lock() {
+        for path in event.paths.into_iter().filter(|p| is_watched(root, p)) {
+            pending.insert(path, ready_at);
+        }
+    }
+}
+
+impl DirectoryWatcher {
+    /// Start watching `root` recursively
+    pub fn start(root: &Path, settle_time: Duration) -> notify::Result<DirectoryWatcher> {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let pending: PendingPaths = Arc::new(Mutex::new(HashMap::new()));
+        let needs_rescan = Arc::new(AtomicBool::new(false));
+
+        let handler_root = root.clone();
+        let handler_pending = pending.clone();
+        let handler_rescan = needs_rescan.clone();
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            queue_event(&handler_root, &handler_pending, &handler_rescan, settle_time, result);
+        })?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+
+        Ok(DirectoryWatcher {
+            _watcher: watcher,
+            root,
+            pending,
+            needs_rescan,
+            settle_time,
+        })
+    }
+
+    /// Directory being watched (canonicalized)
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Events were lost, so only a full scan can be trusted
+    pub fn take_rescan_request(&self) -> bool {
+        self.needs_rescan.swap(false, Ordering::SeqCst)
+    }
+
+    /// Whether any settled path is waiting
+    pub fn has_settled(&self) -> bool {
+        let now = Instant::now();
+        self.pending
+            .lock()
+            .map(|pending| pending.values().any(|ready_at| *ready_at <= now))
+            .unwrap_or(false)
+    }
+
+    /// Remove and return the settled paths that still exist (files or new directories)
+    pub fn take_settled(&self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        let mut pending = match self.pending.lock() {
+            Ok(pending) => pending,
+            Err(_) => return Vec::new(),
+        };
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, ready_at)| **ready_at <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            pending.remove(path);
+        }
+
+        // Paths that were renamed away or deleted (e.g. moved to FTPU-Sent) are dropped here
+        settled.into_iter().filter(|path| path.exists()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch_dir, ScratchDir};
+    use notify::event::{CreateKind, DataChange, Flag, ModifyKind};
+
+    const SETTLE: Duration = Duration::from_millis(200);
+
+    /// Watcher over a fresh folder holding `files`; events are fed in by hand with `send`
+    fn watch(name: &str, files: &[&str]) -> (ScratchDir, DirectoryWatcher) {
+        let dir = scratch_dir(name);
+        for file in files {
+            std::fs::write(dir.join(file), b"data").unwrap();
+        }
+        let watcher = DirectoryWatcher::start(&dir, SETTLE).unwrap();
+        (dir, watcher)
+    }
+
+    fn send(watcher: &DirectoryWatcher, kind: EventKind, name: &str) {
+        let event = Event::new(kind).add_path(watcher.root().join(name));
+        queue_event(&watcher.root, &watcher.pending, &watcher.needs_rescan, watcher.settle_time, Ok(event));
+    }
+
+    fn closed() -> EventKind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write))
+    }
+
+    fn modified() -> EventKind {
+        EventKind::Modify(ModifyKind::Data(DataChange::Content))
+    }
+
+    #[test]
+    fn closed_files_are_ready_at_once() {
+        let (_dir, watcher) = watch("closed", &["a.jpg"]);
+        send(&watcher, closed(), "a.jpg");
+        assert!(watcher.has_settled());
+        assert_eq!(watcher.take_settled(), vec![watcher.root().join("a.jpg")]);
+        assert!(!watcher.has_settled());
+    }
+
+    #[test]
+    fn created_and_modified_files_wait_for_the_settle_time() {
+        let (_dir, watcher) = watch("settle", &["a.jpg", "b.jpg"]);
+        send(&watcher, EventKind::Create(CreateKind::File), "a.jpg");
+        send(&watcher, modified(), "b.jpg");
+        assert!(!watcher.has_settled());
+        assert!(watcher.take_settled().is_empty());
+
+        std::thread::sleep(SETTLE + Duration::from_millis(50));
+        let mut settled = watcher.take_settled();
+        settled.sort();
+        assert_eq!(settled, vec![watcher.root().join("a.jpg"), watcher.root().join("b.jpg")]);
+    }
+
+    #[test]
+    fn further_writes_restart_the_settle_time() {
+        let (_dir, watcher) = watch("restart", &["a.jpg"]);
+        send(&watcher, modified(), "a.jpg");
+        std::thread::sleep(SETTLE * 3 / 4);
+        send(&watcher, modified(), "a.jpg");
+        std::thread::sleep(SETTLE / 2);
+        assert!(!watcher.has_settled());
+
+        std::thread::sleep(SETTLE / 2 + Duration::from_millis(50));
+        assert!(watcher.has_settled());
+    }
+
+    #[test]
+    fn paths_gone_by_the_time_they_settle_are_dropped() {
+        let (_dir, watcher) = watch("gone", &[]);
+        send(&watcher, closed(), "moved.jpg");
+        assert!(watcher.take_settled().is_empty());
+        assert!(!watcher.has_settled());
+    }
+
+    #[test]
+    fn hidden_entries_and_sent_files_are_not_watched() {
+        let root = Path::new("/photos");
+        assert!(is_watched(root, Path::new("/photos/a.jpg")));
+        assert!(is_watched(root, Path::new("/photos/2024/a.jpg")));
+        assert!(!is_watched(root, Path::new("/photos/.DS_Store")));
+        assert!(!is_watched(root, Path::new("/photos/.cache/a.jpg")));
+        assert!(!is_watched(root, Path::new("/photos/2024/.a.jpg.part")));
+        assert!(!is_watched(root, Path::new("/photos/FTPU-Sent/a.jpg")));
+        assert!(!is_watched(root, Path::new("/elsewhere/a.jpg")));
+
+        let (_dir, watcher) = watch("hidden", &[".hidden.jpg"]);
+        send(&watcher, closed(), ".hidden.jpg");
+        assert!(!watcher.has_settled());
+    }
+
+    #[test]
+    fn lost_events_request_a_rescan_once() {
+        let (_dir, watcher) = watch("rescan", &[]);
+        assert!(!watcher.take_rescan_request());
+
+        let result = Err(notify::Error::generic("event queue overflow"));
+        queue_event(&watcher.root, &watcher.pending, &watcher.needs_rescan, watcher.settle_time, result);
+        assert!(watcher.take_rescan_request());
+        assert!(!watcher.take_rescan_request());
+
+        let event = Event::new(EventKind::Other).set_flag(Flag::Rescan);
+        queue_event(&watcher.root, &watcher.pending, &watcher.needs_rescan, watcher.settle_time, Ok(event));
+        assert!(watcher.take_rescan_request());
+        assert!(!watcher.has_settled());
+    }
+}